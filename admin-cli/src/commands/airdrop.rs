@@ -0,0 +1,244 @@
+use anchor_client::{
+    anchor_lang::{InstructionData, ToAccountMetas},
+    solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    },
+};
+use anyhow::{anyhow, Result};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+
+use crate::{
+    fetch_root_and_stats, fetch_user_details, read_allocations, sender::Sender, Checkpoint,
+    MerkleTree, TxOpts,
+};
+
+pub(crate) fn airdrop(
+    sender: &Sender,
+    distributor_key: Pubkey,
+    recipients: String,
+    batch_size: usize,
+    output: String,
+    checkpoint: Option<String>,
+) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let rpc = client.rpc();
+    let distributor: claiming_factory::MerkleDistributor = client.account(distributor_key)?;
+    let (root, _stats) = fetch_root_and_stats(client, &distributor_key)?;
+    let (root_address, _bump) = claiming_factory::MerkleRoot::find_address(&distributor_key);
+    let (stats_address, _bump) = claiming_factory::ClaimStats::find_address(&distributor_key);
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    let (vault_authority, _bump) =
+        Pubkey::find_program_address(&[distributor_key.as_ref()], &client.id());
+
+    let recipients = read_allocations(&recipients)?;
+    let tree = MerkleTree::new(&recipients);
+    if tree.root != root.merkle_root {
+        return Err(anyhow!(
+            "recipients don't match the current merkle root of the distributor"
+        ));
+    }
+
+    let mut writer = csv::Writer::from_path(&output)?;
+    writer.write_record(&["wallet", "status", "signature"])?;
+
+    let mut checkpoint = Checkpoint::open(checkpoint.as_deref(), &distributor_key)?;
+    let (recorded, proofs): (Vec<_>, Vec<_>) = recipients
+        .iter()
+        .map(|(wallet, _amount)| *wallet)
+        .zip(&tree.proofs)
+        .partition(|(wallet, _proof)| checkpoint.contains(wallet));
+    for (wallet, proof) in &recorded {
+        writer.write_record(&[
+            proof.address.clone(),
+            "sent".to_string(),
+            checkpoint
+                .signature(wallet)
+                .map_or(String::new(), |s| s.to_string()),
+        ])?;
+    }
+    println!(
+        "{} recipients are recorded in the checkpoint",
+        recorded.len()
+    );
+
+    let wallets: Vec<Pubkey> = proofs.iter().map(|(wallet, _proof)| *wallet).collect();
+    let user_details = fetch_user_details(&rpc, &distributor_key, &root, &wallets)?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+
+    // claims with nothing to transfer would fail the whole transaction
+    let mut batch = Vec::new();
+    for ((user, proof), details) in proofs.into_iter().zip(user_details) {
+        let empty = claiming_factory::UserDetails::empty();
+        let claimable =
+            distributor.claimable_amounts(details.as_ref().unwrap_or(&empty), proof.amount, now);
+        let pending = details.as_ref().map_or(0, |details| details.pending_amount);
+
+        if claimable.amount + claimable.amount_to_add + pending == 0 {
+            writer.write_record(&[proof.address.as_str(), "nothing to claim", ""])?;
+        } else {
+            batch.push((user, proof, details));
+        }
+    }
+
+    for chunk in batch.chunks(batch_size) {
+        let mut instructions = Vec::new();
+        for (user, proof, details) in chunk {
+            let user = *user;
+            let (user_details, bump) = claiming_factory::UserDetails::find_address(
+                &distributor_key,
+                root.merkle_index,
+                &user,
+            );
+            if details.is_none() {
+                instructions.push(Instruction {
+                    program_id: client.id(),
+                    accounts: claiming_factory::accounts::InitUserDetails {
+                        payer: authority,
+                        user,
+                        user_details,
+                        distributor: distributor_key,
+                        root: root_address,
+                        system_program: solana_sdk::system_program::id(),
+                    }
+                    .to_account_metas(None),
+                    data: claiming_factory::instruction::InitUserDetails { bump }.data(),
+                });
+            }
+
+            let target_wallet = get_associated_token_address(&user, &distributor.mint);
+            if rpc.get_multiple_accounts(&[target_wallet])?[0].is_none() {
+                instructions.push(create_associated_token_account(
+                    &authority,
+                    &user,
+                    &distributor.mint,
+                ));
+            }
+
+            let mut accounts = claiming_factory::accounts::ClaimOnBehalf {
+                distributor: distributor_key,
+                root: root_address,
+                stats: stats_address,
+                config,
+                relayer: authority,
+                user,
+                user_details,
+                vault_authority,
+                vault: distributor.vault,
+                target_wallet,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None);
+            if distributor.position_nfts {
+                let (position_mint, _bump) =
+                    claiming_factory::find_position_mint_address(&distributor_key, &user);
+                accounts.push(AccountMeta::new_readonly(position_mint, false));
+            }
+            if let Some(holdback) = distributor.holdback {
+                let (holdback_record, _bump) =
+                    claiming_factory::HoldbackRecord::find_address(&distributor_key, &user);
+                if rpc.get_multiple_accounts(&[holdback_record])?[0].is_none() {
+                    instructions.push(Instruction {
+                        program_id: client.id(),
+                        accounts: claiming_factory::accounts::InitHoldbackRecord {
+                            payer: authority,
+                            user,
+                            holdback_record,
+                            distributor: distributor_key,
+                            system_program: solana_sdk::system_program::id(),
+                        }
+                        .to_account_metas(None),
+                        data: claiming_factory::instruction::InitHoldbackRecord {}.data(),
+                    });
+                }
+
+                accounts.push(AccountMeta::new(holdback.vault, false));
+                accounts.push(AccountMeta::new(holdback_record, false));
+            }
+
+            instructions.push(Instruction {
+                program_id: client.id(),
+                accounts,
+                data: claiming_factory::instruction::ClaimOnBehalf {
+                    args: claiming_factory::ClaimArgs {
+                        amount: proof.amount,
+                        merkle_proof: proof.proofs.clone(),
+                        custodial_destination: false,
+                    },
+                }
+                .data(),
+            });
+        }
+
+        let mut request = client.request();
+        for ix in instructions {
+            request = request.instruction(ix);
+        }
+        let result = sender.send(request);
+
+        let users: Vec<Pubkey> = chunk.iter().map(|(user, _, _)| *user).collect();
+        if let Ok(Some(signature)) = result {
+            checkpoint.record(&users, signature)?;
+        }
+        // the transaction can land although its confirmation failed, the claim counts tell
+        let landed = match &result {
+            Ok(_) => false,
+            Err(_) => {
+                let claim_count = |details: &Option<claiming_factory::UserDetails>| {
+                    details.as_ref().map_or(0, |details| details.claim_count)
+                };
+                fetch_user_details(&rpc, &distributor_key, &root, &users).map_or(false, |after| {
+                    chunk
+                        .iter()
+                        .zip(&after)
+                        .all(|((_, _, before), after)| claim_count(after) > claim_count(before))
+                })
+            }
+        };
+        for (_user, proof, _details) in chunk {
+            match &result {
+                Ok(Some(signature)) => writer.write_record(&[
+                    proof.address.clone(),
+                    "sent".to_string(),
+                    signature.to_string(),
+                ])?,
+                // nothing was sent, the transaction was only printed, simulated or estimated
+                Ok(None) => writer.write_record(&[
+                    proof.address.clone(),
+                    not_sent_status(sender.opts).to_string(),
+                    String::new(),
+                ])?,
+                Err(_) if landed => writer.write_record(&[
+                    proof.address.clone(),
+                    "sent, unconfirmed".to_string(),
+                    String::new(),
+                ])?,
+                Err(err) => writer.write_record(&[
+                    proof.address.clone(),
+                    format!("failed: {}", err),
+                    String::new(),
+                ])?,
+            }
+        }
+        writer.flush()?;
+    }
+
+    println!("Results are written to {}", output);
+
+    Ok(())
+}
+
+/// Status of a claim that `Sender` didn't send.
+fn not_sent_status(opts: &TxOpts) -> &'static str {
+    if opts.print_tx {
+        "printed"
+    } else if opts.dry_run {
+        "simulated"
+    } else {
+        "estimated"
+    }
+}
@@ -0,0 +1,222 @@
+use anchor_client::{
+    solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+};
+use anyhow::{anyhow, Result};
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::{
+    fetch_root_and_stats, fetch_user_details, parse_events, read_allocations, sender::Sender,
+    token_balance, Since,
+};
+
+pub(crate) fn export_claims(
+    sender: &Sender,
+    distributor: Pubkey,
+    since: Since,
+    output: String,
+) -> Result<()> {
+    let client = sender.client;
+    let rpc = client.rpc();
+    let mut writer = csv::Writer::from_path(&output)?;
+    writer.write_record(&["user", "token_account", "amount", "tx", "timestamp"])?;
+
+    let mut before = None;
+    let mut exported = 0;
+    'pages: loop {
+        let signatures = rpc.get_signatures_for_address_with_config(
+            &distributor,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: Some(1000),
+                commitment: None,
+            },
+        )?;
+        if signatures.is_empty() {
+            break;
+        }
+
+        for status in &signatures {
+            if !since.includes(status.slot, status.block_time) {
+                break 'pages;
+            }
+            if status.err.is_some() {
+                continue;
+            }
+
+            let signature: Signature = status.signature.parse()?;
+            let tx = rpc.get_transaction(&signature, UiTransactionEncoding::Json)?;
+            let logs = tx
+                .transaction
+                .meta
+                .and_then(|meta| meta.log_messages)
+                .unwrap_or_default();
+
+            for claimed in parse_events::<claiming_factory::Claimed>(&logs) {
+                writer.write_record(&[
+                    claimed.account.to_string(),
+                    claimed.token_account.to_string(),
+                    claimed.amount.to_string(),
+                    status.signature.clone(),
+                    status.block_time.map_or(String::new(), |ts| ts.to_string()),
+                ])?;
+                exported += 1;
+            }
+        }
+
+        before = signatures
+            .last()
+            .map(|status| status.signature.parse())
+            .transpose()?;
+    }
+
+    writer.flush()?;
+    println!("Exported {} claims to {}", exported, output);
+
+    Ok(())
+}
+
+pub(crate) fn surplus(sender: &Sender, distributor_key: Pubkey, allocations: String) -> Result<()> {
+    let client = sender.client;
+    let rpc = client.rpc();
+    let distributor: claiming_factory::MerkleDistributor = client.account(distributor_key)?;
+    let (root, stats) = fetch_root_and_stats(client, &distributor_key)?;
+    let allocations = read_allocations(&allocations)?;
+    let wallets: Vec<Pubkey> = allocations.iter().map(|(wallet, _)| *wallet).collect();
+    let user_details = fetch_user_details(&rpc, &distributor_key, &root, &wallets)?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let empty = claiming_factory::UserDetails::empty();
+
+    let mut vested: u64 = 0;
+    let mut unclaimed: u64 = 0;
+    for ((_wallet, allocation), details) in allocations.iter().zip(&user_details) {
+        let details = details.as_ref().unwrap_or(&empty);
+        let claimable = distributor.claimable_amounts(details, *allocation, now);
+
+        // pending tokens are unlocked already, only held back by the per claim limit
+        vested += claimable.amount + claimable.amount_to_add + details.pending_amount;
+        unclaimed += allocation.saturating_sub(details.claimed_amount);
+    }
+    let bonus_reserve = distributor.bonus_reserve(&stats);
+
+    let balance = token_balance(&rpc, &distributor.vault)?;
+    println!("Vault balance:            {}", balance);
+    println!("Vested, not claimed:      {}", vested);
+    println!("Not vested yet:           {}", unclaimed - vested);
+    println!("Loyalty bonus reserve:    {}", bonus_reserve);
+
+    let surplus = balance as i128 - vested as i128 - bonus_reserve as i128;
+    let fully_covered = balance as i128 - unclaimed as i128 - bonus_reserve as i128;
+    if surplus < 0 {
+        return Err(anyhow!(
+            "vault is short of {} tokens for claims available today",
+            -surplus
+        ));
+    }
+    println!("Surplus over vested:      {}", surplus);
+    println!("Surplus over allocations: {}", fully_covered);
+    if fully_covered < surplus {
+        println!(
+            "Warning: withdrawing more than {} leaves future unlocks uncovered",
+            fully_covered.max(0)
+        );
+    }
+    if distributor.withdrawal_schedule {
+        println!("Withdrawals are limited by the withdrawal schedule of the distributor");
+    }
+    if distributor.frozen_at_ts.is_some() {
+        println!("Withdrawals are blocked while the distributor is frozen");
+    }
+
+    Ok(())
+}
+
+pub(crate) fn reconcile(
+    sender: &Sender,
+    distributor_key: Pubkey,
+    allocations: String,
+) -> Result<()> {
+    let client = sender.client;
+    let rpc = client.rpc();
+    let distributor: claiming_factory::MerkleDistributor = client.account(distributor_key)?;
+    let (root, stats) = fetch_root_and_stats(client, &distributor_key)?;
+    let allocations = read_allocations(&allocations)?;
+    let mut mismatches = Vec::new();
+
+    let allocations_total: u64 = allocations.iter().map(|(_, amount)| amount).sum();
+    println!("Allocations total:    {}", allocations_total);
+    println!("On-chain allocation:  {}", root.total_allocation);
+    if allocations_total != root.total_allocation {
+        mismatches.push("allocations total differs from the on-chain allocation".to_string());
+    }
+
+    let mut wallets: Vec<Pubkey> = allocations.iter().map(|(wallet, _)| *wallet).collect();
+    wallets.sort_unstable();
+    wallets.dedup();
+    if wallets.len() != allocations.len() {
+        mismatches.push(format!(
+            "{} duplicated wallets in allocations",
+            allocations.len() - wallets.len()
+        ));
+    }
+
+    let wallets: Vec<Pubkey> = allocations.iter().map(|(wallet, _)| *wallet).collect();
+    let user_details = fetch_user_details(&rpc, &distributor_key, &root, &wallets)?;
+    let mut users_claimed = 0;
+    let mut claimers = 0;
+    for ((wallet, allocation), details) in allocations.iter().zip(&user_details) {
+        let details = match details {
+            Some(details) => details,
+            None => continue,
+        };
+
+        users_claimed += details.claimed_amount;
+        if details.claimed_amount > 0 {
+            claimers += 1;
+        }
+        if details.claimed_amount + details.pending_amount > *allocation {
+            mismatches.push(format!(
+                "{} claimed {} (+{} pending) of {}",
+                wallet, details.claimed_amount, details.pending_amount, allocation
+            ));
+        }
+    }
+    println!(
+        "Claimed by users:     {} ({} users)",
+        users_claimed, claimers
+    );
+    println!(
+        "On-chain claimed:     {} ({} users)",
+        stats.total_claimed, stats.claimers
+    );
+    if users_claimed != stats.total_claimed {
+        mismatches.push("sum of user claims differs from the on-chain total".to_string());
+    }
+
+    let vault_balance = token_balance(&rpc, &distributor.vault)?;
+    let outstanding = root.total_allocation.saturating_sub(users_claimed);
+    println!("Vault balance:        {}", vault_balance);
+    println!("Outstanding:          {}", outstanding);
+    if vault_balance < outstanding {
+        mismatches.push(format!(
+            "vault is short of {} tokens",
+            outstanding - vault_balance
+        ));
+    }
+    // there are no refunds in the program yet, so nothing is deducted for them
+    println!("Refunds:              n/a");
+
+    if !mismatches.is_empty() {
+        for mismatch in &mismatches {
+            println!("Mismatch: {}", mismatch);
+        }
+        return Err(anyhow!("{} mismatches found", mismatches.len()));
+    }
+    println!("Everything matches");
+
+    Ok(())
+}
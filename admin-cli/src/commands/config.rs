@@ -0,0 +1,144 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Result;
+
+use crate::{sender::Sender, ConfigJson};
+
+pub(crate) fn init_config(sender: &Sender) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    println!("Config address: {}", config);
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::InitializeConfig {
+            system_program: solana_sdk::system_program::id(),
+            owner: authority,
+            config,
+        })
+        .args(claiming_factory::instruction::InitializeConfig { bump });
+
+    if sender.send(request)?.is_some() {
+        sender.print_address_links(&config);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn show_config(sender: &Sender, json: bool) -> Result<()> {
+    let client = sender.client;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+
+    let config_account: claiming_factory::Config = client.account(config)?;
+    if json {
+        let json = ConfigJson::new(&config, &config_account);
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        println!("{:#?}", config_account);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn init_treasury(sender: &Sender) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    let (treasury, _bump) = Pubkey::find_program_address(&["treasury".as_ref()], &client.id());
+    println!("Treasury address: {}", treasury);
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::InitTreasury {
+            config,
+            owner: authority,
+            treasury,
+            system_program: solana_sdk::system_program::id(),
+        })
+        .args(claiming_factory::instruction::InitTreasury {});
+
+    if sender.send(request)?.is_some() {
+        sender.print_address_links(&treasury);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn set_version(sender: &Sender) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::SetVersion {
+            config,
+            owner: authority,
+        })
+        .args(claiming_factory::instruction::SetVersion {});
+
+    sender.send(request)?;
+
+    Ok(())
+}
+
+pub(crate) fn set_creation_fee(sender: &Sender, lamports: u64) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::SetCreationFee {
+            config,
+            owner: authority,
+        })
+        .args(claiming_factory::instruction::SetCreationFee {
+            creation_fee: lamports,
+        });
+
+    sender.send(request)?;
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_fees(sender: &Sender, lamports: u64, target: Option<Pubkey>) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    let (treasury, _bump) = Pubkey::find_program_address(&["treasury".as_ref()], &client.id());
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::WithdrawFees {
+            config,
+            owner: authority,
+            treasury,
+            target: target.unwrap_or(authority),
+        })
+        .args(claiming_factory::instruction::WithdrawFees { amount: lamports });
+
+    sender.send(request)?;
+
+    Ok(())
+}
+
+pub(crate) fn add_admin(sender: &Sender, admin: Pubkey) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    println!("Config address: {}", config);
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::AddAdmin {
+            owner: authority,
+            config,
+            admin,
+        })
+        .args(claiming_factory::instruction::AddAdmin {});
+
+    sender.send(request)?;
+
+    Ok(())
+}
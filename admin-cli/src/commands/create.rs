@@ -0,0 +1,547 @@
+use std::{io::Write, rc::Rc};
+
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{commitment_config::CommitmentConfig, instruction::AccountMeta, pubkey::Pubkey},
+    Client,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    program_pack::Pack,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+
+use crate::{
+    format_duration, format_ts, hex, parse_category, parse_duration, parse_schedule_ts,
+    read_allocations, read_keypair, read_schedule, sender::Sender, DefaultPath, MerkleTree, Opts,
+    Payer, TxOpts,
+};
+
+pub(crate) struct CreateClaimingArgs {
+    pub(crate) merkle_root: [u8; 32],
+    pub(crate) mint: Pubkey,
+    pub(crate) schedule: Vec<claiming_factory::Period>,
+    pub(crate) position_nfts: bool,
+    pub(crate) max_claim_per_tx: Option<u64>,
+    pub(crate) category: claiming_factory::DistributorCategory,
+    pub(crate) total_allocation: u64,
+    pub(crate) relative_schedule: bool,
+    pub(crate) max_proof_len: Option<u8>,
+    pub(crate) clawback_destination: Pubkey,
+    pub(crate) keypair_vault: bool,
+}
+
+/// Asks for every setting of `create-claiming` and creates the claiming once confirmed.
+pub(crate) fn create_claiming_interactive(sender: &Sender, keypair_vault: bool) -> Result<()> {
+    check_signable(sender.opts)?;
+
+    if let Some(args) = create_claiming_wizard(&sender.client.rpc(), keypair_vault)? {
+        create_claiming(sender, args)?;
+    }
+
+    Ok(())
+}
+
+/// The distributor keypair is new, so the creation can't be printed for a multisig.
+fn check_signable(opts: &TxOpts) -> Result<()> {
+    if opts.print_tx {
+        return Err(anyhow!(
+            "create-claiming is signed by a new distributor keypair, \
+             so it can't be printed for a multisig"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Creates the vault and the distributor, returns their addresses.
+/// Returns addresses of the distributor and its vault, with the signature if the creation was sent.
+pub(crate) fn create_claiming(
+    sender: &Sender,
+    args: CreateClaimingArgs,
+) -> Result<(Pubkey, Pubkey, Option<Signature>)> {
+    check_signable(sender.opts)?;
+
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &sender.client.id());
+    println!("Config address: {}", config);
+    let config_account: claiming_factory::Config = sender.client.account(config)?;
+
+    let distributor = Keypair::new();
+    println!("Distributor address: {}", distributor.pubkey());
+
+    let (vault_authority, vault_bump) =
+        Pubkey::find_program_address(&[distributor.pubkey().as_ref()], &sender.client.id());
+
+    // the associated vault is derivable from the distributor address later
+    let vault = Keypair::new();
+    let (vault_address, create_vault_ixs) = if args.keypair_vault {
+        let rent = sender
+            .client
+            .rpc()
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+
+        let create_token_account_ix = solana_sdk::system_instruction::create_account(
+            &sender.fee_payer,
+            &vault.pubkey(),
+            rent,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::ID,
+        );
+
+        let init_token_account_ix = spl_token::instruction::initialize_account(
+            &spl_token::ID,
+            &vault.pubkey(),
+            &args.mint,
+            &vault_authority,
+        )?;
+
+        (
+            vault.pubkey(),
+            vec![create_token_account_ix, init_token_account_ix],
+        )
+    } else {
+        (
+            get_associated_token_address(&vault_authority, &args.mint),
+            vec![create_associated_token_account(
+                &sender.fee_payer,
+                &vault_authority,
+                &args.mint,
+            )],
+        )
+    };
+    println!("Vault address: {}", vault_address);
+
+    let mut request = sender.client.request();
+    for ix in create_vault_ixs {
+        request = request.instruction(ix);
+    }
+    let mut request = request
+        .accounts(claiming_factory::accounts::Initialize {
+            config,
+            admin_or_owner: sender.fee_payer,
+            distributor: distributor.pubkey(),
+            root: claiming_factory::MerkleRoot::find_address(&distributor.pubkey()).0,
+            stats: claiming_factory::ClaimStats::find_address(&distributor.pubkey()).0,
+            vault_authority,
+            vault: vault_address,
+            mint: args.mint,
+            clawback_destination: args.clawback_destination,
+            system_program: solana_sdk::system_program::id(),
+        })
+        .args(claiming_factory::instruction::Initialize {
+            args: claiming_factory::InitializeArgs {
+                vault_bump,
+                merkle_root: args.merkle_root,
+                schedule: args.schedule,
+                position_nfts: args.position_nfts,
+                max_claim_per_tx: args.max_claim_per_tx,
+                category: args.category,
+                total_allocation: args.total_allocation,
+                relative_schedule: args.relative_schedule,
+                max_proof_len: args.max_proof_len,
+            },
+        });
+    // the treasury is only required to collect the creation fee
+    if config_account.creation_fee > 0 {
+        let (treasury, _bump) =
+            Pubkey::find_program_address(&["treasury".as_ref()], &sender.client.id());
+        request = request.accounts(vec![AccountMeta::new(treasury, false)]);
+    }
+    let signature = if args.keypair_vault {
+        sender.send_with_signers(request, &[&distributor, &vault])?
+    } else {
+        sender.send_with_signers(request, &[&distributor])?
+    };
+    if signature.is_some() {
+        println!("Distributor:");
+        sender.print_address_links(&distributor.pubkey());
+        println!("Vault:");
+        sender.print_address_links(&vault_address);
+    }
+
+    Ok((distributor.pubkey(), vault_address, signature))
+}
+
+/// Reads an answer from stdin, the default is used for an empty one.
+fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+        _ => print!("{}: ", question),
+    }
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Err(anyhow!("stdin is closed"));
+    }
+
+    match (answer.trim(), default) {
+        ("", Some(default)) => Ok(default.to_string()),
+        (answer, _) => Ok(answer.to_string()),
+    }
+}
+
+/// Repeats the question until the answer passes `parse`.
+fn ask_parsed<T>(
+    question: &str,
+    default: Option<&str>,
+    parse: impl Fn(&str) -> Result<T>,
+) -> Result<T> {
+    loop {
+        match parse(&ask(question, default)?) {
+            Ok(value) => return Ok(value),
+            Err(err) => println!("Invalid answer: {}", err),
+        }
+    }
+}
+
+fn ask_yes_no(question: &str, default: bool) -> Result<bool> {
+    ask_parsed(
+        question,
+        Some(if default { "y" } else { "n" }),
+        |answer| match answer.to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err(anyhow!("expected y or n")),
+        },
+    )
+}
+
+/// Walks through the settings of a new claiming, validating each of them.
+/// Returns `None` if the summary isn't confirmed.
+fn create_claiming_wizard(
+    rpc: &RpcClient,
+    keypair_vault: bool,
+) -> Result<Option<CreateClaimingArgs>> {
+    let (mint, decimals) = ask_parsed("Token mint", None, |answer| {
+        let mint = answer.parse::<Pubkey>()?;
+        let account = rpc.get_account(&mint)?;
+        let decimals = spl_token::state::Mint::unpack(&account.data)
+            .map_err(|_| anyhow!("{} is not a token mint", mint))?
+            .decimals;
+        Ok((mint, decimals))
+    })?;
+    let ui_amount = |amount: u64| amount as f64 / 10f64.powi(decimals as i32);
+
+    let clawback_destination = ask_parsed(
+        "Project token account receiving unclaimed tokens",
+        None,
+        |answer| {
+            let key = answer.parse::<Pubkey>()?;
+            let account = spl_token::state::Account::unpack(&rpc.get_account(&key)?.data)
+                .map_err(|_| anyhow!("{} is not a token account", key))?;
+            if account.mint != mint {
+                return Err(anyhow!("{} holds another mint {}", key, account.mint));
+            }
+            Ok(key)
+        },
+    )?;
+
+    let tree = ask_parsed("Allocations CSV with wallet,amount rows", None, |answer| {
+        let allocations = read_allocations(answer)?;
+        if allocations.is_empty() {
+            return Err(anyhow!("no allocations in {}", answer));
+        }
+        Ok(MerkleTree::new(&allocations))
+    })?;
+
+    let category = ask_parsed(
+        "Category (ido, airdrop, team, advisors)",
+        Some("ido"),
+        parse_category,
+    )?;
+    let relative_schedule = ask_yes_no("Are period starts relative to TGE", false)?;
+
+    let schedule = loop {
+        println!(
+            "Enter periods one by one, an empty start finishes the schedule. Starts are {}, \
+             intervals are durations like 30d or 1w.",
+            if relative_schedule {
+                "offsets from TGE like 0 or 90d"
+            } else {
+                "dates like 2025-03-01T12:00:00Z"
+            }
+        );
+
+        let mut schedule = Vec::new();
+        loop {
+            let index = schedule.len();
+            let start_ts = ask_parsed(&format!("Period {} start", index), Some(""), |answer| {
+                if answer.is_empty() {
+                    return Ok(None);
+                }
+                parse_schedule_ts(answer).map(Some)
+            })?;
+            let start_ts = match start_ts {
+                Some(start_ts) => start_ts,
+                None => break,
+            };
+
+            schedule.push(claiming_factory::Period {
+                start_ts,
+                token_percentage: ask_parsed(
+                    &format!("Period {} share of tokens in basis points", index),
+                    None,
+                    |answer| Ok(answer.parse::<u64>()?),
+                )?,
+                interval_sec: ask_parsed(
+                    &format!("Period {} interval", index),
+                    None,
+                    parse_duration,
+                )?,
+                times: ask_parsed(
+                    &format!("Period {} number of intervals", index),
+                    Some("1"),
+                    |answer| Ok(answer.parse::<u64>()?),
+                )?,
+                airdropped: ask_yes_no(&format!("Is period {} airdropped", index), false)?,
+            });
+        }
+
+        match claiming_factory::Vesting::new(schedule.clone(), relative_schedule) {
+            Ok(_) => break schedule,
+            Err(err) => println!("Schedule is rejected by the program: {}, try again", err),
+        }
+    };
+
+    let position_nfts = ask_yes_no("Allow position NFTs", false)?;
+    let max_claim_per_tx = ask_parsed(
+        "Max tokens per claim in base units, empty for no limit",
+        Some(""),
+        |answer| match answer {
+            "" => Ok(None),
+            answer => Ok(Some(answer.parse::<u64>()?)),
+        },
+    )?;
+
+    println!();
+    println!("Mint:                 {} ({} decimals)", mint, decimals);
+    println!("Clawback destination: {}", clawback_destination);
+    println!("Merkle root:          {}", hex(&tree.root));
+    println!("Wallets:              {}", tree.proofs.len());
+    println!(
+        "Total allocation:     {} ({} tokens)",
+        tree.total_tokens,
+        ui_amount(tree.total_tokens)
+    );
+    println!("Tree depth:           {}", tree.depth());
+    println!("Category:             {:?}", category);
+    println!("Position NFTs:        {}", position_nfts);
+    println!(
+        "Max claim per tx:     {}",
+        max_claim_per_tx.map_or("no limit".to_string(), |max| format!(
+            "{} ({} tokens)",
+            max,
+            ui_amount(max)
+        ))
+    );
+    println!(
+        "Vault:                {}",
+        if keypair_vault {
+            "new keypair"
+        } else {
+            "associated token account"
+        }
+    );
+    println!("Schedule:");
+    let mut cumulative = 0;
+    for (index, period) in schedule.iter().enumerate() {
+        cumulative += period.token_percentage;
+        println!(
+            "  {}: {} bps in {} x {} from {}{}, {}% unlocked in total",
+            index,
+            period.token_percentage,
+            period.times,
+            format_duration(period.interval_sec),
+            format_ts(period.start_ts, relative_schedule),
+            if period.airdropped {
+                ", airdropped"
+            } else {
+                ""
+            },
+            cumulative as f64 / 100.0
+        );
+    }
+    println!();
+
+    if !ask_yes_no("Sign and send", false)? {
+        return Ok(None);
+    }
+
+    Ok(Some(CreateClaimingArgs {
+        merkle_root: tree.root,
+        mint,
+        schedule,
+        position_nfts,
+        max_claim_per_tx,
+        category,
+        total_allocation: tree.total_tokens,
+        relative_schedule,
+        max_proof_len: Some(tree.depth()),
+        clawback_destination,
+        keypair_vault,
+    }))
+}
+
+/// Deployment manifest, e.g.
+///
+/// ```yaml
+/// deployments:
+///   - cluster: devnet
+///     program_id: <...>
+///     payer: ~/.config/solana/devnet.json
+///     mint: <...>
+///     clawback_destination: <...>
+///     schedule: schedule.csv
+///     allocations: allocations.csv
+///   - cluster: mainnet
+///     ...
+/// ```
+///
+/// Relative paths are resolved against the manifest directory. Deployments
+/// with the `distributor` already recorded are skipped, so a failed run can be repeated.
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    deployments: Vec<Deployment>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Deployment {
+    cluster: String,
+    // addresses are kept base58 encoded to stay readable in YAML
+    program_id: String,
+    /// `--payer` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payer: Option<String>,
+    mint: String,
+    clawback_destination: String,
+    schedule: String,
+    /// CSV or .xlsx with `wallet,amount` rows to build the tree from.
+    allocations: String,
+    #[serde(default)]
+    position_nfts: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_claim_per_tx: Option<u64>,
+    /// One of: ido, airdrop, team, advisors.
+    #[serde(default = "Deployment::default_category")]
+    category: String,
+    #[serde(default)]
+    relative_schedule: bool,
+    #[serde(default)]
+    keypair_vault: bool,
+    /// Filled in once deployed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    distributor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vault: Option<String>,
+}
+
+impl Deployment {
+    fn default_category() -> String {
+        "ido".to_string()
+    }
+}
+
+pub(crate) fn deploy_claiming(opts: &Opts, path: &str) -> Result<()> {
+    check_signable(&opts.tx)?;
+
+    let mut manifest: Manifest = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+    let dir = std::path::Path::new(path)
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""));
+    let resolve = |file: &str| dir.join(shellexpand::tilde(file).as_ref());
+
+    // validate everything before the first deployment
+    let mut trees = Vec::new();
+    for deployment in &manifest.deployments {
+        deployment.cluster.parse::<anchor_client::Cluster>()?;
+        deployment.program_id.parse::<Pubkey>()?;
+        deployment.mint.parse::<Pubkey>()?;
+        deployment.clawback_destination.parse::<Pubkey>()?;
+        parse_category(&deployment.category)?;
+        claiming_factory::Vesting::new(
+            read_schedule(&resolve(&deployment.schedule).to_string_lossy())?,
+            deployment.relative_schedule,
+        )
+        .map_err(|err| {
+            anyhow!(
+                "schedule of {} is rejected by the program: {}",
+                deployment.cluster,
+                err
+            )
+        })?;
+        trees.push(MerkleTree::new(&read_allocations(
+            &resolve(&deployment.allocations).to_string_lossy(),
+        )?));
+    }
+
+    for (index, tree) in trees.into_iter().enumerate() {
+        let deployment = &manifest.deployments[index];
+        if let Some(distributor) = &deployment.distributor {
+            println!(
+                "Skipping {}, already deployed at {}",
+                deployment.cluster, distributor
+            );
+            continue;
+        }
+        println!("Deploying to {}", deployment.cluster);
+
+        let payer_path = match (&deployment.payer, &opts.payer) {
+            (Some(payer), _) => shellexpand::tilde(payer).to_string(),
+            (None, Some(payer)) => payer.path.clone(),
+            (None, None) => Payer::default_path(),
+        };
+        let payer = Rc::new(read_keypair(&payer_path)?);
+
+        let cluster: anchor_client::Cluster = deployment.cluster.parse()?;
+        let client = Client::new_with_options(
+            cluster.clone(),
+            payer.clone(),
+            CommitmentConfig {
+                commitment: opts.tx.commitment,
+            },
+        );
+        let client = client.program(deployment.program_id.parse()?);
+        let sender = Sender {
+            client: &client,
+            opts: &opts.tx,
+            payer: payer.as_ref(),
+            fee_payer: payer.pubkey(),
+            cluster: &cluster,
+            estimated: Default::default(),
+        };
+
+        let (distributor, vault, signature) = create_claiming(
+            &sender,
+            CreateClaimingArgs {
+                merkle_root: tree.root,
+                mint: deployment.mint.parse()?,
+                schedule: read_schedule(&resolve(&deployment.schedule).to_string_lossy())?,
+                position_nfts: deployment.position_nfts,
+                max_claim_per_tx: deployment.max_claim_per_tx,
+                category: parse_category(&deployment.category)?,
+                total_allocation: tree.total_tokens,
+                relative_schedule: deployment.relative_schedule,
+                max_proof_len: Some(tree.depth()),
+                clawback_destination: deployment.clawback_destination.parse()?,
+                keypair_vault: deployment.keypair_vault,
+            },
+        )?;
+
+        // nothing is created by a dry run or an estimate
+        if signature.is_none() {
+            continue;
+        }
+
+        let deployment = &mut manifest.deployments[index];
+        deployment.distributor = Some(distributor.to_string());
+        deployment.vault = Some(vault.to_string());
+        std::fs::write(path, serde_yaml::to_string(&manifest)?)?;
+        println!("Recorded addresses of {} in {}", deployment.cluster, path);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,348 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::{anyhow, Result};
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::{
+    fetch_root_and_stats, format_countdown, format_percentage, format_tge, format_ts,
+    format_ui_amount, progress_bar, read_allocations, sender::Sender, token_balance,
+    DistributorJson, MerkleData, MerkleTree,
+};
+
+pub(crate) fn update_root(
+    sender: &Sender,
+    claiming: Pubkey,
+    merkle: Option<String>,
+    allocations: Option<String>,
+    total_allocation: Option<u64>,
+    max_proof_len: Option<u8>,
+    unpause: bool,
+) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (merkle_root, total_allocation, max_proof_len) = match (merkle, allocations) {
+        (Some(merkle), _) => {
+            let merkle: MerkleData = serde_json::from_str(&merkle)?;
+            (merkle.data, total_allocation.unwrap(), max_proof_len)
+        }
+        (None, Some(allocations)) => {
+            let tree = MerkleTree::new(&read_allocations(&allocations)?);
+            (
+                tree.root,
+                tree.total_tokens,
+                max_proof_len.or_else(|| Some(tree.depth())),
+            )
+        }
+        (None, None) => unreachable!(),
+    };
+    println!("Merkle root: {:?}", merkle_root);
+    println!("Total allocation: {}", total_allocation);
+
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    let distributor: claiming_factory::MerkleDistributor = client.account(claiming)?;
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::UpdateRoot {
+            distributor: claiming,
+            root: claiming_factory::MerkleRoot::find_address(&claiming).0,
+            stats: claiming_factory::ClaimStats::find_address(&claiming).0,
+            config,
+            admin_or_owner: authority,
+            vault: distributor.vault,
+        })
+        .args(claiming_factory::instruction::UpdateRoot {
+            args: claiming_factory::UpdateRootArgs {
+                merkle_root,
+                unpause,
+                total_allocation,
+                max_proof_len,
+            },
+        });
+
+    sender.send(request)?;
+
+    let (root, _stats) = fetch_root_and_stats(client, &claiming)?;
+    println!("Merkle index: {}", root.merkle_index);
+
+    Ok(())
+}
+
+pub(crate) fn show_claiming(sender: &Sender, claiming: Pubkey, json: bool) -> Result<()> {
+    let client = sender.client;
+    let distributor: claiming_factory::MerkleDistributor = client.account(claiming)?;
+    let (root, stats) = fetch_root_and_stats(client, &claiming)?;
+    if json {
+        let json = DistributorJson::new(&claiming, &distributor, &root, &stats);
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        println!("{:#?}", distributor);
+        println!("{:#?}", root);
+        println!("{:#?}", stats);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn derive(sender: &Sender, claiming: Pubkey, user: Pubkey) -> Result<()> {
+    let client = sender.client;
+    let distributor: claiming_factory::MerkleDistributor = client.account(claiming)?;
+    let (root, _stats) = fetch_root_and_stats(client, &claiming)?;
+    let (user_details, _bump) =
+        claiming_factory::UserDetails::find_address(&claiming, root.merkle_index, &user);
+    let (position_mint, _bump) = claiming_factory::find_position_mint_address(&claiming, &user);
+    let (vault_authority, _bump) = Pubkey::find_program_address(&[claiming.as_ref()], &client.id());
+    let (holdback_vault, _bump) =
+        Pubkey::find_program_address(&[claiming.as_ref(), "holdback".as_ref()], &client.id());
+    let (holdback_record, _bump) = claiming_factory::HoldbackRecord::find_address(&claiming, &user);
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+
+    let addresses = [
+        ("User details", user_details),
+        ("Position mint", position_mint),
+        (
+            "Token account",
+            get_associated_token_address(&user, &distributor.mint),
+        ),
+        ("Vault", distributor.vault),
+        ("Vault authority", vault_authority),
+        ("Holdback vault", holdback_vault),
+        ("Holdback record", holdback_record),
+        ("Config", config),
+    ];
+    let keys: Vec<Pubkey> = addresses.iter().map(|(_, address)| *address).collect();
+    let accounts = client.rpc().get_multiple_accounts(&keys)?;
+
+    println!("Round: {}", root.merkle_index);
+    for ((name, address), account) in addresses.iter().zip(accounts) {
+        println!(
+            "{:<16} {:<44} {}",
+            format!("{}:", name),
+            address,
+            if account.is_some() {
+                "exists"
+            } else {
+                "missing"
+            }
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn report(sender: &Sender, distributor_key: Pubkey) -> Result<()> {
+    let client = sender.client;
+    let rpc = client.rpc();
+    let distributor: claiming_factory::MerkleDistributor = client.account(distributor_key)?;
+    let (root, stats) = fetch_root_and_stats(client, &distributor_key)?;
+    let decimals = distributor.mint_decimals;
+    let tokens = |amount: u64| format_ui_amount(amount, decimals);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let relative = distributor.relative_schedule && distributor.activation_ts == 0;
+
+    let status = if distributor.stopped {
+        "stopped".to_string()
+    } else if let Some(frozen_at_ts) = distributor.frozen_at_ts {
+        format!("frozen since {}", format_ts(frozen_at_ts, false))
+    } else if root.paused {
+        "paused".to_string()
+    } else {
+        "active".to_string()
+    };
+
+    println!("Distributor:       {}", distributor_key);
+    println!("Category:          {:?}", distributor.category);
+    println!("Status:            {}", status);
+    println!(
+        "Mint:              {} ({} decimals)",
+        distributor.mint, decimals
+    );
+    println!("TGE:               {}", format_tge(&distributor));
+    println!("Round:             {}", root.merkle_index);
+    println!();
+    println!("Total allocation:  {}", tokens(root.total_allocation));
+    println!(
+        "Claimed:           {} ({}) by {} users",
+        tokens(stats.total_claimed),
+        format_percentage(stats.total_claimed, root.total_allocation),
+        stats.claimers
+    );
+    if stats.tickets_claimed > 0 {
+        println!("Claimed tickets:   {}", tokens(stats.tickets_claimed));
+    }
+    println!(
+        "Unclaimed:         {}",
+        tokens(
+            root.total_allocation
+                .saturating_sub(stats.claimed_with_tickets())
+        )
+    );
+    println!(
+        "Vault balance:     {}",
+        tokens(token_balance(&rpc, &distributor.vault)?)
+    );
+    if let Some(holdback) = &distributor.holdback {
+        println!(
+            "Holdback balance:  {} ({} of claims for {})",
+            tokens(token_balance(&rpc, &holdback.vault)?),
+            format_percentage(holdback.bps, 10000),
+            format_countdown(holdback.window_sec)
+        );
+    }
+
+    if let Some(frozen_at_ts) = distributor.frozen_at_ts {
+        let unfreeze_ts = frozen_at_ts + claiming_factory::MerkleDistributor::FREEZE_TIMELOCK_SEC;
+        match unfreeze_ts.checked_sub(now) {
+            Some(left) if left > 0 => {
+                println!("Can be unfrozen in {}", format_countdown(left))
+            }
+            _ => println!("Can be unfrozen now"),
+        }
+    }
+    if let Some(pending) = &distributor.pending_clawback_destination {
+        let effective_ts =
+            pending.proposed_at_ts + claiming_factory::MerkleDistributor::CLAWBACK_TIMELOCK_SEC;
+        match effective_ts.checked_sub(now) {
+            Some(left) if left > 0 => println!(
+                "Clawback destination {} takes effect in {}",
+                pending.destination,
+                format_countdown(left)
+            ),
+            _ => println!(
+                "Clawback destination {} can be accepted now",
+                pending.destination
+            ),
+        }
+    }
+
+    println!();
+    println!(
+        "{:>3} {:<24} {:<24} {:>8}  {:<22} {:>8}",
+        "#", "Start", "End", "Share", "Progress", "Claimed"
+    );
+    for (index, period) in distributor.vesting.schedule.iter().enumerate() {
+        let end_ts = period.start_ts + period.times * period.interval_sec;
+        // airdropped periods unlock at once at their start
+        let unlocked = if relative || now < period.start_ts {
+            0
+        } else if period.airdropped || period.interval_sec == 0 {
+            period.times
+        } else {
+            ((now - period.start_ts) / period.interval_sec).min(period.times)
+        };
+        let period_allocation =
+            (root.total_allocation as u128 * period.token_percentage as u128 / 10000) as u64;
+        let claimed = stats.claimed_per_period.get(index).copied().unwrap_or(0);
+
+        let mut notes = Vec::new();
+        if period.airdropped {
+            notes.push("airdropped");
+        }
+        if distributor
+            .completed_periods
+            .get(index)
+            .copied()
+            .unwrap_or(false)
+        {
+            notes.push("completed");
+        }
+
+        println!(
+            "{:>3} {:<24} {:<24} {:>8}  {:<22} {:>8} {}",
+            index,
+            format_ts(period.start_ts, relative),
+            format_ts(end_ts, relative),
+            format_percentage(period.token_percentage, 10000),
+            progress_bar(unlocked, period.times),
+            format_percentage(claimed, period_allocation),
+            notes.join(", ")
+        );
+    }
+
+    let next_unlock = distributor
+        .vesting
+        .schedule
+        .iter()
+        .flat_map(|period| {
+            let first = if period.airdropped { 0 } else { 1 };
+            (first..=period.times)
+                .map(move |interval| period.start_ts + interval * period.interval_sec)
+        })
+        .filter(|ts| *ts > now)
+        .min();
+    match next_unlock {
+        _ if relative => println!("\nUnlocks are dated once TGE is activated"),
+        Some(ts) => println!(
+            "\nNext unlock: {}, in {}",
+            format_ts(ts, false),
+            format_countdown(ts - now)
+        ),
+        None => println!("\nEverything is unlocked"),
+    }
+
+    Ok(())
+}
+
+pub(crate) fn fund(sender: &Sender, distributor_key: Pubkey, amount: u64) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let rpc = client.rpc();
+    let distributor: claiming_factory::MerkleDistributor = client.account(distributor_key)?;
+
+    // there is no fund instruction in the program, so it's a plain token transfer
+    let source = get_associated_token_address(&authority, &distributor.mint);
+    let balance_before = token_balance(&rpc, &distributor.vault)?;
+    println!("Vault balance before: {}", balance_before);
+
+    let request = client
+        .request()
+        .instruction(spl_token::instruction::transfer_checked(
+            &spl_token::ID,
+            &source,
+            &distributor.mint,
+            &distributor.vault,
+            &authority,
+            &[],
+            amount,
+            distributor.mint_decimals,
+        )?);
+
+    if sender.send(request)?.is_some() {
+        let balance_after = token_balance(&rpc, &distributor.vault)?;
+        println!("Vault balance after: {}", balance_after);
+
+        if balance_after != balance_before + amount {
+            return Err(anyhow!(
+                "vault balance changed by {} instead of {}",
+                balance_after as i128 - balance_before as i128,
+                amount
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn activate_tge(
+    sender: &Sender,
+    claiming: Pubkey,
+    activation_ts: Option<u64>,
+) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+
+    let request = client
+        .request()
+        .accounts(claiming_factory::accounts::ActivateTge {
+            distributor: claiming,
+            config,
+            owner: authority,
+        })
+        .args(claiming_factory::instruction::ActivateTge { activation_ts });
+
+    sender.send(request)?;
+
+    Ok(())
+}
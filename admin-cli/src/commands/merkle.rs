@@ -0,0 +1,180 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::{anyhow, Result};
+
+use crate::{
+    fetch_root_and_stats, hex, read_allocations, sender::Sender, verify_proof, write_merkle_tree,
+    ClaimArgsJson, MerkleTree,
+};
+
+pub(crate) fn generate_merkle(
+    allocations: &str,
+    output: &str,
+    evm_output: Option<&str>,
+    shards: usize,
+) -> Result<()> {
+    let allocations = read_allocations(allocations)?;
+    let (root, total_tokens) = write_merkle_tree(&allocations, output, evm_output, shards)?;
+    println!("Merkle root: {}", hex(&root));
+    println!("Total tokens: {}", total_tokens);
+    println!("Tree is written to {}", output);
+    if let Some(evm_output) = evm_output {
+        println!("EVM tree is written to {}", evm_output);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn get_proof(wallet: &Pubkey, tree: &str) -> Result<()> {
+    let tree = MerkleTree::read(tree, wallet)?;
+    let proof = tree
+        .find(wallet)
+        .ok_or_else(|| anyhow!("wallet {} is not in the tree", wallet))?;
+
+    if !verify_proof(wallet, proof.amount, &proof.proofs, &tree.root) {
+        return Err(anyhow!("proof of {} doesn't match the tree root", wallet));
+    }
+
+    let args = ClaimArgsJson {
+        amount: proof.amount,
+        merkle_proof: &proof.proofs,
+        // the claimer's own token account, a custodial one has to be opted in for
+        custodial_destination: false,
+    };
+    println!("{}", serde_json::to_string_pretty(&args)?);
+
+    Ok(())
+}
+
+pub(crate) fn verify_root(
+    sender: &Sender,
+    distributor_key: Pubkey,
+    allocations: String,
+) -> Result<()> {
+    let client = sender.client;
+    let (root, _stats) = fetch_root_and_stats(client, &distributor_key)?;
+    let tree = MerkleTree::new(&read_allocations(&allocations)?);
+
+    println!("Local root:    {}", hex(&tree.root));
+    println!("On-chain root: {}", hex(&root.merkle_root));
+
+    let mut mismatches = Vec::new();
+    if tree.root != root.merkle_root {
+        mismatches.push("merkle root".to_string());
+    }
+    if tree.total_tokens != root.total_allocation {
+        mismatches.push(format!(
+            "total allocation {} vs {} on-chain",
+            tree.total_tokens, root.total_allocation
+        ));
+    }
+    if let Some(max_proof_len) = root.max_proof_len {
+        if tree.depth() > max_proof_len {
+            mismatches.push(format!(
+                "tree depth {} exceeds max proof length {}",
+                tree.depth(),
+                max_proof_len
+            ));
+        }
+    }
+
+    if !mismatches.is_empty() {
+        return Err(anyhow!(
+            "allocations don't match the distributor: {}",
+            mismatches.join(", ")
+        ));
+    }
+    println!(
+        "Root matches, {} allocations, merkle index {}, paused: {}",
+        tree.proofs.len(),
+        root.merkle_index,
+        root.paused
+    );
+
+    Ok(())
+}
+
+pub(crate) fn summarize_allocations(path: &str, expected_total: Option<u64>) -> Result<()> {
+    let file = std::fs::read(path)?;
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(&*file);
+
+    let mut tiers: std::collections::BTreeMap<String, Vec<u64>> = Default::default();
+    let mut wallets = std::collections::HashSet::new();
+    for result in rdr.records() {
+        let record = result?;
+
+        let wallet = record
+            .get(0)
+            .ok_or(anyhow!("missing wallet address"))?
+            .trim()
+            .parse::<Pubkey>()?;
+        if !wallets.insert(wallet) {
+            println!("Warning: {} is listed more than once", wallet);
+        }
+
+        let amount = record
+            .get(1)
+            .ok_or(anyhow!("missing allocation amount of {}", wallet))?
+            .trim()
+            .parse::<u64>()?;
+        if amount == 0 {
+            println!("Warning: {} has zero allocation", wallet);
+        }
+
+        let tier = record.get(2).map(str::trim).unwrap_or_default();
+        let tier = if tier.is_empty() { "-" } else { tier };
+        tiers.entry(tier.to_string()).or_default().push(amount);
+    }
+
+    println!(
+        "{:<16} {:>8} {:>20} {:>20} {:>20} {:>20}",
+        "Tier", "Count", "Total", "Min", "Median", "Max"
+    );
+    let mut grand_total: u128 = 0;
+    let mut all = Vec::new();
+    let print_row = |tier: &str, amounts: &mut Vec<u64>| {
+        amounts.sort_unstable();
+        let total: u128 = amounts.iter().map(|amount| *amount as u128).sum();
+        let median = match amounts.len() {
+            0 => 0,
+            len if len % 2 == 0 => {
+                ((amounts[len / 2 - 1] as u128 + amounts[len / 2] as u128) / 2) as u64
+            }
+            len => amounts[len / 2],
+        };
+        println!(
+            "{:<16} {:>8} {:>20} {:>20} {:>20} {:>20}",
+            tier,
+            amounts.len(),
+            total,
+            amounts.first().copied().unwrap_or_default(),
+            median,
+            amounts.last().copied().unwrap_or_default()
+        );
+        total
+    };
+    for (tier, amounts) in tiers.iter_mut() {
+        grand_total += print_row(tier, amounts);
+        all.extend_from_slice(amounts);
+    }
+    print_row("Total", &mut all);
+
+    if grand_total > u64::MAX as u128 {
+        return Err(anyhow!("grand total {} overflows u64", grand_total));
+    }
+
+    if let Some(expected_total) = expected_total {
+        if grand_total != expected_total as u128 {
+            return Err(anyhow!(
+                "grand total {} doesn't match the expected {}",
+                grand_total,
+                expected_total
+            ));
+        }
+        println!("Grand total matches the expected {}", expected_total);
+    }
+
+    Ok(())
+}
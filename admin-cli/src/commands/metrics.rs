@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use anchor_client::{solana_sdk::pubkey::Pubkey, Program};
+use anyhow::Result;
+
+use crate::{fetch_root_and_stats, sender::Sender, token_balance};
+
+pub(crate) fn serve(
+    sender: &Sender,
+    distributors: Vec<Pubkey>,
+    port: u16,
+    interval: u64,
+) -> Result<()> {
+    let client = sender.client;
+    let metrics = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let listener = std::net::TcpListener::bind(("0.0.0.0", port))?;
+    println!("Serving metrics on http://0.0.0.0:{}/metrics", port);
+
+    let served = metrics.clone();
+    std::thread::spawn(move || serve_metrics(listener, &served));
+
+    loop {
+        let text = poll_metrics(client, &distributors);
+        *metrics.lock().unwrap() = text;
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+    }
+}
+
+/// Renders the state of the distributors in the Prometheus text format.
+fn poll_metrics(client: &Program, distributors: &[Pubkey]) -> String {
+    let rpc = client.rpc();
+    let gauges: [(&str, &str); 9] = [
+        (
+            "claiming_up",
+            "Whether the distributor was fetched by the last poll.",
+        ),
+        ("claiming_vault_balance", "Tokens in the vault."),
+        (
+            "claiming_total_allocation",
+            "Sum of all allocations in the current tree.",
+        ),
+        (
+            "claiming_total_claimed",
+            "Tokens claimed from the current tree.",
+        ),
+        (
+            "claiming_claimers",
+            "Users who claimed from the current tree.",
+        ),
+        ("claiming_merkle_index", "Number of replaced trees."),
+        ("claiming_paused", "Whether claims are paused."),
+        (
+            "claiming_frozen",
+            "Whether claims and withdrawals are frozen.",
+        ),
+        (
+            "claiming_stopped",
+            "Whether the distributor was split and stopped.",
+        ),
+    ];
+
+    let mut values: Vec<Vec<(Pubkey, u64)>> = vec![Vec::new(); gauges.len()];
+    for key in distributors {
+        let state = client
+            .account::<claiming_factory::MerkleDistributor>(*key)
+            .map_err(anyhow::Error::from)
+            .and_then(|distributor| {
+                let (root, stats) = fetch_root_and_stats(client, key)?;
+                let balance = token_balance(&rpc, &distributor.vault)?;
+                Ok((distributor, root, stats, balance))
+            });
+        let (distributor, root, stats, balance) = match state {
+            Ok(state) => state,
+            Err(err) => {
+                println!("Failed to poll {}: {}", key, err);
+                values[0].push((*key, 0));
+                continue;
+            }
+        };
+
+        for (index, value) in [
+            1,
+            balance,
+            root.total_allocation,
+            stats.total_claimed,
+            stats.claimers,
+            root.merkle_index,
+            root.paused as u64,
+            distributor.frozen_at_ts.is_some() as u64,
+            distributor.stopped as u64,
+        ]
+        .iter()
+        .enumerate()
+        {
+            values[index].push((*key, *value));
+        }
+    }
+
+    let mut text = String::new();
+    for ((name, help), values) in gauges.iter().zip(values) {
+        text += &format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name);
+        for (key, value) in values {
+            text += &format!("{}{{distributor=\"{}\"}} {}\n", name, key, value);
+        }
+    }
+
+    text
+}
+
+/// Answers every request for `/metrics` with the latest polled metrics.
+fn serve_metrics(listener: std::net::TcpListener, metrics: &std::sync::Mutex<String>) {
+    use std::io::BufRead;
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        // a client that connects and stays silent can't block the others
+        let timeout = Some(std::time::Duration::from_secs(5));
+        if stream.set_read_timeout(timeout).is_err() || stream.set_write_timeout(timeout).is_err() {
+            continue;
+        }
+
+        let mut request_line = String::new();
+        if std::io::BufReader::new(&stream)
+            .read_line(&mut request_line)
+            .is_err()
+        {
+            continue;
+        }
+
+        // e.g. "GET /metrics HTTP/1.1"
+        let response = match request_line.split(' ').nth(1) {
+            Some("/metrics") => {
+                let body = metrics.lock().unwrap().clone();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+        };
+        let _ = stream.write_all(response.as_bytes());
+    }
+}
@@ -0,0 +1,12 @@
+pub mod airdrop;
+pub mod audit;
+pub mod config;
+pub mod create;
+pub mod distributor;
+pub mod merkle;
+pub mod metrics;
+pub mod nonce;
+pub mod notify;
+pub mod program;
+pub mod schedule;
+pub mod user;
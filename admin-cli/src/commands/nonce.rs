@@ -0,0 +1,50 @@
+use anchor_client::solana_sdk::{nonce, pubkey::Pubkey, system_instruction};
+use anyhow::Result;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::{read_nonce, sender::Sender};
+
+pub(crate) fn create(sender: &Sender, nonce_authority: Option<Pubkey>) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let nonce_account = Keypair::new();
+    let lamports = client
+        .rpc()
+        .get_minimum_balance_for_rent_exemption(nonce::State::size())?;
+    let request = system_instruction::create_nonce_account(
+        &authority,
+        &nonce_account.pubkey(),
+        &nonce_authority.unwrap_or(authority),
+        lamports,
+    )
+    .into_iter()
+    .fold(client.request(), |request, ix| request.instruction(ix));
+
+    sender.send_with_signers(request, &[&nonce_account])?;
+    println!("Nonce account: {}", nonce_account.pubkey());
+
+    Ok(())
+}
+
+pub(crate) fn show(sender: &Sender, address: Pubkey) -> Result<()> {
+    let client = sender.client;
+    let nonce = read_nonce(&client.rpc(), &address)?;
+    println!("Blockhash: {}", nonce.blockhash);
+    println!("Authority: {}", nonce.authority);
+
+    Ok(())
+}
+
+pub(crate) fn advance(sender: &Sender, address: Pubkey) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let request = client
+        .request()
+        .instruction(system_instruction::advance_nonce_account(
+            &address,
+            &sender.opts.nonce_authority.unwrap_or(authority),
+        ));
+    sender.send(request)?;
+
+    Ok(())
+}
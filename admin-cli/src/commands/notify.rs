@@ -0,0 +1,199 @@
+use anchor_client::{
+    solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    Program,
+};
+use anyhow::Result;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::{hex, parse_events, sender::Sender};
+
+pub(crate) fn notify(
+    sender: &Sender,
+    webhook_url: String,
+    distributors: Vec<Pubkey>,
+    claim_threshold: Option<u64>,
+    interval: u64,
+) -> Result<()> {
+    let client = sender.client;
+    let rpc = client.rpc();
+
+    // only events after the start are posted
+    let mut last_signatures = std::collections::HashMap::new();
+    let mut paused = std::collections::HashMap::new();
+    for key in &distributors {
+        let newest = rpc
+            .get_signatures_for_address_with_config(
+                key,
+                GetConfirmedSignaturesForAddress2Config {
+                    limit: Some(1),
+                    ..GetConfirmedSignaturesForAddress2Config::default()
+                },
+            )?
+            .first()
+            .map(|status| status.signature.parse::<Signature>())
+            .transpose()?;
+        last_signatures.insert(*key, newest);
+
+        let root: claiming_factory::MerkleRoot =
+            client.account(claiming_factory::MerkleRoot::find_address(key).0)?;
+        paused.insert(*key, root.paused);
+    }
+    println!("Watching {} distributors", distributors.len());
+
+    loop {
+        for key in &distributors {
+            let notifications = poll_notifications(
+                client,
+                key,
+                claim_threshold,
+                last_signatures.get_mut(key).unwrap(),
+                paused.get_mut(key).unwrap(),
+            );
+            let notifications = match notifications {
+                Ok(notifications) => notifications,
+                Err(err) => {
+                    println!("Failed to poll {}: {}", key, err);
+                    continue;
+                }
+            };
+
+            for notification in notifications {
+                println!("{}", notification["text"].as_str().unwrap_or_default());
+                if let Err(err) = ureq::post(&webhook_url).send_json(notification) {
+                    println!("Failed to post to the webhook: {}", err);
+                }
+            }
+        }
+
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+    }
+}
+
+/// Collects webhook payloads for the transactions of the distributor since `last_signature`
+/// and for the change of its pause state. Both Slack (`text`) and Discord (`content`)
+/// messages are filled in, and the event itself is attached as `event`.
+fn poll_notifications(
+    client: &Program,
+    distributor_key: &Pubkey,
+    claim_threshold: Option<u64>,
+    last_signature: &mut Option<Signature>,
+    paused: &mut bool,
+) -> Result<Vec<serde_json::Value>> {
+    let rpc = client.rpc();
+    let mut events = Vec::new();
+
+    // newest first, a busy distributor might have more between polls but these are enough
+    let signatures = rpc.get_signatures_for_address_with_config(
+        distributor_key,
+        GetConfirmedSignaturesForAddress2Config {
+            until: *last_signature,
+            limit: Some(1000),
+            ..GetConfirmedSignaturesForAddress2Config::default()
+        },
+    )?;
+    for status in signatures.iter().rev() {
+        let signature: Signature = status.signature.parse()?;
+        if status.err.is_none() {
+            let tx = rpc.get_transaction(&signature, UiTransactionEncoding::Json)?;
+            let logs = tx
+                .transaction
+                .meta
+                .and_then(|meta| meta.log_messages)
+                .unwrap_or_default();
+
+            for claimed in parse_events::<claiming_factory::Claimed>(&logs) {
+                if claim_threshold.map_or(false, |threshold| claimed.amount >= threshold) {
+                    events.push((
+                        format!("{} claimed {} tokens", claimed.account, claimed.amount),
+                        serde_json::json!({
+                            "kind": "claim",
+                            "account": claimed.account.to_string(),
+                            "token_account": claimed.token_account.to_string(),
+                            "amount": claimed.amount,
+                        }),
+                        Some(signature),
+                    ));
+                }
+            }
+            for updated in parse_events::<claiming_factory::MerkleRootUpdated>(&logs) {
+                events.push((
+                    format!(
+                        "merkle root is replaced with {}, total allocation {}",
+                        hex(&updated.merkle_root),
+                        updated.total_allocation
+                    ),
+                    serde_json::json!({
+                        "kind": "root_updated",
+                        "merkle_index": updated.merkle_index,
+                        "merkle_root": hex(&updated.merkle_root),
+                        "total_allocation": updated.total_allocation,
+                    }),
+                    Some(signature),
+                ));
+            }
+            for withdrawn in parse_events::<claiming_factory::TokensWithdrawn>(&logs) {
+                events.push((
+                    format!("{} tokens are withdrawn", withdrawn.amount),
+                    serde_json::json!({
+                        "kind": "withdrawal",
+                        "amount": withdrawn.amount,
+                    }),
+                    Some(signature),
+                ));
+            }
+            for frozen in parse_events::<claiming_factory::VaultFrozen>(&logs) {
+                events.push((
+                    "vault is frozen".to_string(),
+                    serde_json::json!({
+                        "kind": "frozen",
+                        "frozen_at_ts": frozen.frozen_at_ts,
+                    }),
+                    Some(signature),
+                ));
+            }
+            for _unfrozen in parse_events::<claiming_factory::VaultUnfrozen>(&logs) {
+                events.push((
+                    "vault is unfrozen".to_string(),
+                    serde_json::json!({ "kind": "unfrozen" }),
+                    Some(signature),
+                ));
+            }
+        }
+
+        *last_signature = Some(signature);
+    }
+
+    // pausing doesn't emit an event, so the flag is compared between polls
+    let root: claiming_factory::MerkleRoot =
+        client.account(claiming_factory::MerkleRoot::find_address(distributor_key).0)?;
+    if root.paused != *paused {
+        *paused = root.paused;
+        events.push((
+            format!(
+                "claims are {}",
+                if root.paused { "paused" } else { "unpaused" }
+            ),
+            serde_json::json!({
+                "kind": "pause",
+                "paused": root.paused,
+            }),
+            None,
+        ));
+    }
+
+    Ok(events
+        .into_iter()
+        .map(|(text, mut event, signature)| {
+            let text = format!("Distributor {}: {}", distributor_key, text);
+            event["distributor"] = serde_json::json!(distributor_key.to_string());
+            event["signature"] =
+                serde_json::json!(signature.map(|signature| signature.to_string()));
+            serde_json::json!({
+                "text": text,
+                "content": text,
+                "event": event,
+            })
+        })
+        .collect())
+}
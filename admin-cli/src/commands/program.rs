@@ -0,0 +1,348 @@
+use anchor_client::{
+    anchor_lang::AnchorDeserialize,
+    solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig},
+    solana_sdk::{
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        instruction::Instruction,
+        message::Message,
+        pubkey::Pubkey,
+        transaction::Transaction,
+    },
+};
+use anyhow::{anyhow, Result};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::{format_ts, read_keypair, sender::Sender, CliKeypair, ProgramKeypair, TxOpts};
+
+pub(crate) fn program_info(sender: &Sender) -> Result<()> {
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let rpc = client.rpc();
+    let program_id = client.id();
+    println!("Program: {}", program_id);
+
+    let program = rpc.get_account(&program_id)?;
+    if program.owner != bpf_loader_upgradeable::id() {
+        println!("Loader: {}, not upgradeable", program.owner);
+    } else if let UpgradeableLoaderState::Program {
+        programdata_address,
+    } = bincode::deserialize(&program.data)?
+    {
+        println!("Program data: {}", programdata_address);
+
+        let programdata = rpc.get_account(&programdata_address)?;
+        if let UpgradeableLoaderState::ProgramData {
+            slot,
+            upgrade_authority_address,
+        } = bincode::deserialize(&programdata.data)?
+        {
+            println!(
+                "Upgrade authority: {}",
+                upgrade_authority_address
+                    .map_or("none, immutable".to_string(), |key| key.to_string())
+            );
+            match rpc.get_block_time(slot) {
+                Ok(ts) => println!(
+                    "Last deployed at slot {}, {}",
+                    slot,
+                    format_ts(ts as u64, false)
+                ),
+                Err(_) => println!("Last deployed at slot {}", slot),
+            }
+            println!(
+                "Program size: {} bytes",
+                programdata.data.len() - UpgradeableLoaderState::programdata_data_offset()?
+            );
+        }
+    }
+
+    // same address as `anchor idl init` uses
+    let (idl_base, _bump) = Pubkey::find_program_address(&[], &program_id);
+    let idl = Pubkey::create_with_seed(&idl_base, "anchor:idl", &program_id)?;
+    match rpc
+        .get_account_with_commitment(&idl, rpc.commitment())?
+        .value
+    {
+        Some(account) => println!("IDL: {}, {} bytes", idl, account.data.len()),
+        None => println!("IDL: not published"),
+    }
+
+    // the deployed build reports its version only via return data
+    let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &program_id);
+    let instructions = client
+        .request()
+        .accounts(claiming_factory::accounts::GetVersion { config })
+        .args(claiming_factory::instruction::GetVersion {})
+        .instructions()?;
+    let mut message = Message::new(&instructions, Some(&authority));
+    message.recent_blockhash = rpc.get_latest_blockhash()?;
+    let result = rpc
+        .simulate_transaction_with_config(
+            &Transaction::new_unsigned(message),
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )?
+        .value;
+
+    // e.g. "Program return: <id> <base64 data>"
+    let return_data = result
+        .logs
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|log| log.strip_prefix("Program return: "))
+        .filter_map(|log| log.split(' ').nth(1))
+        .filter_map(|data| base64::decode(data).ok())
+        .last();
+    match return_data {
+        Some(data) => {
+            let version = claiming_factory::VersionInfo::try_from_slice(&data)?;
+            println!("Deployed version: {}", version.program);
+            println!("Version in the config: {}", version.config);
+        }
+        None => println!(
+            "Deployed version: unknown, get_version failed: {:?}",
+            result.err
+        ),
+    }
+    println!(
+        "CLI built for version: {}",
+        claiming_factory::Config::PROGRAM_VERSION
+    );
+
+    Ok(())
+}
+
+/// Program commands always send transactions, there is nothing to print or simulate.
+fn check_sent(opts: &TxOpts) -> Result<()> {
+    if opts.print_tx || opts.dry_run || opts.estimate {
+        return Err(anyhow!(
+            "program commands always send transactions, \
+             use --buffer-authority to hand an upgrade over to a multisig"
+        ));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn deploy(
+    sender: &Sender,
+    path: String,
+    program_keypair: CliKeypair<ProgramKeypair>,
+    max_len: Option<usize>,
+    tps: Option<f64>,
+    verify_hash: bool,
+) -> Result<()> {
+    check_sent(sender.opts)?;
+
+    let payer = sender.payer;
+    let rpc = sender.client.rpc();
+    let program = std::fs::read(&path)?;
+    let program_keypair = read_keypair(&program_keypair.path)?;
+    let buffer = write_buffer(sender, &program, tps)?;
+
+    let lamports =
+        rpc.get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::program_len()?)?;
+    let deploy = bpf_loader_upgradeable::deploy_with_max_program_len(
+        &payer.pubkey(),
+        &program_keypair.pubkey(),
+        &buffer,
+        &payer.pubkey(),
+        lamports,
+        max_len.unwrap_or(program.len() * 2),
+    )?;
+    let signature = sender
+        .send_and_confirm_all(&[deploy], &[payer, &program_keypair], None)
+        .remove(0)?;
+    println!("Deployed {} in {}", program_keypair.pubkey(), signature);
+    sender.print_tx_links(&signature);
+
+    if verify_hash {
+        verify_program_hash(&rpc, &program_keypair.pubkey(), &program)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn upgrade(
+    sender: &Sender,
+    path: String,
+    buffer_authority: Option<Pubkey>,
+    tps: Option<f64>,
+    verify_hash: bool,
+) -> Result<()> {
+    check_sent(sender.opts)?;
+
+    let client = sender.client;
+    let payer = sender.payer;
+    let rpc = client.rpc();
+    let program_id = client.id();
+    let program = std::fs::read(&path)?;
+    let (_programdata, upgrade_authority) = program_data(&rpc, &program_id)?;
+    let upgrade_authority =
+        upgrade_authority.ok_or_else(|| anyhow!("{} is immutable", program_id))?;
+    if buffer_authority.is_none() && upgrade_authority != payer.pubkey() {
+        return Err(anyhow!(
+            "upgrade authority is {}, pass it as --buffer-authority \
+             to hand the buffer over to it",
+            upgrade_authority
+        ));
+    }
+
+    let buffer = write_buffer(sender, &program, tps)?;
+
+    if let Some(buffer_authority) = buffer_authority {
+        if buffer_authority != upgrade_authority {
+            println!(
+                "Warning: upgrade authority of the program is {}",
+                upgrade_authority
+            );
+        }
+        let request = client
+            .request()
+            .instruction(bpf_loader_upgradeable::set_buffer_authority(
+                &buffer,
+                &payer.pubkey(),
+                &buffer_authority,
+            ));
+        sender.send(request)?;
+        println!(
+            "Buffer {} is handed over to {}, the upgrade has to be signed by it",
+            buffer, buffer_authority
+        );
+        return Ok(());
+    }
+
+    let request = client
+        .request()
+        .instruction(bpf_loader_upgradeable::upgrade(
+            &program_id,
+            &buffer,
+            &payer.pubkey(),
+            &payer.pubkey(),
+        ));
+    if let Some(signature) = sender.send(request)? {
+        sender.print_tx_links(&signature);
+    }
+
+    if verify_hash {
+        verify_program_hash(&rpc, &program_id, &program)?;
+    }
+
+    Ok(())
+}
+
+/// Bytes of the program written by a single transaction, leaves room for the signature,
+/// accounts and the rest of the write instruction within the packet size.
+const BUFFER_WRITE_LEN: usize = 900;
+
+/// Creates a buffer owned by the payer and writes the program into it.
+fn write_buffer(sender: &Sender, program: &[u8], tps: Option<f64>) -> Result<Pubkey> {
+    let rpc = sender.client.rpc();
+    let authority = sender.payer.pubkey();
+    let buffer = Keypair::new();
+
+    let lamports = rpc.get_minimum_balance_for_rent_exemption(
+        UpgradeableLoaderState::buffer_len(program.len())?,
+    )?;
+    let create = bpf_loader_upgradeable::create_buffer(
+        &authority,
+        &buffer.pubkey(),
+        &authority,
+        lamports,
+        program.len(),
+    )?;
+    sender
+        .send_and_confirm_all(&[create], &[sender.payer, &buffer], None)
+        .remove(0)?;
+    println!("Buffer: {}", buffer.pubkey());
+
+    let writes: Vec<Vec<Instruction>> = program
+        .chunks(BUFFER_WRITE_LEN)
+        .enumerate()
+        .map(|(index, chunk)| {
+            vec![bpf_loader_upgradeable::write(
+                &buffer.pubkey(),
+                &authority,
+                (index * BUFFER_WRITE_LEN) as u32,
+                chunk.to_vec(),
+            )]
+        })
+        .collect();
+    let failed = sender
+        .send_and_confirm_all(&writes, &[sender.payer], tps)
+        .into_iter()
+        .filter(Result::is_err)
+        .count();
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} writes into buffer {} failed, rerun the command, \
+             the buffer can be closed with `solana program close`",
+            failed,
+            writes.len(),
+            buffer.pubkey()
+        ));
+    }
+    println!(
+        "Written {} bytes in {} transactions",
+        program.len(),
+        writes.len()
+    );
+
+    Ok(buffer.pubkey())
+}
+
+/// Program data account of the upgradeable program with its upgrade authority.
+fn program_data(rpc: &RpcClient, program_id: &Pubkey) -> Result<(Pubkey, Option<Pubkey>)> {
+    let program = rpc.get_account(program_id)?;
+    if program.owner != bpf_loader_upgradeable::id() {
+        return Err(anyhow!("{} isn't an upgradeable program", program_id));
+    }
+    let programdata_address = match bincode::deserialize(&program.data)? {
+        UpgradeableLoaderState::Program {
+            programdata_address,
+        } => programdata_address,
+        _ => return Err(anyhow!("{} isn't a program account", program_id)),
+    };
+    let upgrade_authority =
+        match bincode::deserialize(&rpc.get_account(&programdata_address)?.data)? {
+            UpgradeableLoaderState::ProgramData {
+                upgrade_authority_address,
+                ..
+            } => upgrade_authority_address,
+            _ => {
+                return Err(anyhow!(
+                    "{} isn't a program data account",
+                    programdata_address
+                ))
+            }
+        };
+
+    Ok((programdata_address, upgrade_authority))
+}
+
+/// Compares the SHA-256 of the built program to the deployed one, the program data account
+/// is padded with zeros up to its max length.
+fn verify_program_hash(rpc: &RpcClient, program_id: &Pubkey, program: &[u8]) -> Result<()> {
+    let (programdata_address, _upgrade_authority) = program_data(rpc, program_id)?;
+    let data = rpc.get_account(&programdata_address)?.data;
+    let deployed = &data[UpgradeableLoaderState::programdata_data_offset()?..];
+
+    let (deployed, padding) = deployed.split_at(program.len().min(deployed.len()));
+
+    let local_hash = solana_sdk::hash::hash(program);
+    let deployed_hash = solana_sdk::hash::hash(deployed);
+    println!("Built program hash:    {}", local_hash);
+    println!("Deployed program hash: {}", deployed_hash);
+
+    if local_hash != deployed_hash || padding.iter().any(|byte| *byte != 0) {
+        return Err(anyhow!(
+            "deployed program doesn't match {} bytes of the built one",
+            program.len()
+        ));
+    }
+    println!("Deployed program matches the built one");
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::{anyhow, Result};
+
+use crate::{format_duration, format_tge, format_ts, read_schedule, sender::Sender};
+
+pub(crate) fn check_schedule(path: &str, relative: bool) -> Result<()> {
+    let schedule = read_schedule(path)?;
+    let mut warnings = Vec::new();
+
+    let total_percentage: u64 = schedule.iter().map(|period| period.token_percentage).sum();
+    if total_percentage != 10000 {
+        warnings.push(format!(
+            "periods cover {}% of tokens instead of 100%",
+            total_percentage as f64 / 100.0
+        ));
+    }
+
+    for (index, period) in schedule.iter().enumerate() {
+        if period.interval_sec == 0 {
+            warnings.push(format!("period {} has zero interval", index));
+        }
+        if period.token_percentage == 0 {
+            warnings.push(format!("period {} unlocks nothing", index));
+        }
+        if period.times == 0 {
+            warnings.push(format!("period {} has no intervals", index));
+        } else if period.token_percentage % period.times != 0 {
+            warnings.push(format!(
+                "{} bps of period {} can't be split evenly into {} intervals",
+                period.token_percentage, index, period.times
+            ));
+        }
+    }
+
+    for (index, pair) in schedule.windows(2).enumerate() {
+        let end_ts = pair[0].start_ts + pair[0].times * pair[0].interval_sec;
+        let next_start_ts = pair[1].start_ts;
+
+        // the program requires every period to start strictly after the previous one ends
+        if next_start_ts <= end_ts {
+            warnings.push(format!(
+                "period {} starts {} before period {} ends",
+                index + 1,
+                format_duration(end_ts - next_start_ts),
+                index
+            ));
+        } else if next_start_ts > end_ts + pair[1].interval_sec {
+            warnings.push(format!(
+                "gap of {} without unlocks between periods {} and {}",
+                format_duration(next_start_ts - end_ts),
+                index,
+                index + 1
+            ));
+        }
+    }
+
+    if schedule
+        .iter()
+        .filter(|period| period.start_ts == 0)
+        .count()
+        > 1
+        && relative
+    {
+        warnings.push("several periods start right at TGE".to_string());
+    }
+
+    println!(
+        "{:<4} {:<28} {:>10} {:>12}",
+        "#", "Unlock", "Unlocked", "Cumulative"
+    );
+    let mut cumulative = 0.0;
+    for (index, period) in schedule.iter().enumerate() {
+        let unlocked = period.token_percentage as f64 / period.times.max(1) as f64 / 100.0;
+        for interval in 1..=period.times {
+            cumulative += unlocked;
+            println!(
+                "{:<4} {:<28} {:>9.4}% {:>11.4}%{}",
+                index,
+                format_ts(period.start_ts + interval * period.interval_sec, relative),
+                unlocked,
+                cumulative,
+                if period.airdropped {
+                    " (airdropped)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+
+    for warning in &warnings {
+        println!("Warning: {}", warning);
+    }
+
+    match claiming_factory::Vesting::new(schedule, relative) {
+        Ok(_) => println!("Schedule passes the program validation"),
+        Err(err) => return Err(anyhow!("schedule is rejected by the program: {}", err)),
+    }
+
+    Ok(())
+}
+
+pub(crate) fn diff_schedules(sender: &Sender, a: Pubkey, b: Pubkey) -> Result<()> {
+    let client = sender.client;
+    let a: claiming_factory::MerkleDistributor = client.account(a)?;
+    let b: claiming_factory::MerkleDistributor = client.account(b)?;
+
+    let format_period = |distributor: &claiming_factory::MerkleDistributor,
+                         period: Option<&claiming_factory::Period>| {
+        match period {
+            Some(period) => format!(
+                "{}, {} bps, {} x {}{}",
+                // starts become absolute once TGE is activated
+                format_ts(
+                    period.start_ts,
+                    distributor.relative_schedule && distributor.activation_ts == 0
+                ),
+                period.token_percentage,
+                period.times,
+                format_duration(period.interval_sec),
+                if period.airdropped {
+                    ", airdropped"
+                } else {
+                    ""
+                }
+            ),
+            None => "-".to_string(),
+        }
+    };
+
+    let mut differences = 0;
+    let periods = std::cmp::max(a.vesting.schedule.len(), b.vesting.schedule.len());
+    println!("  {:<4} {:<56} {:<56}", "#", "A", "B");
+    for index in 0..periods {
+        let a_period = format_period(&a, a.vesting.schedule.get(index));
+        let b_period = format_period(&b, b.vesting.schedule.get(index));
+        // raw values are compared, the formatted ones hide seconds
+        let same = match (a.vesting.schedule.get(index), b.vesting.schedule.get(index)) {
+            (Some(a_period), Some(b_period)) => {
+                a_period.start_ts == b_period.start_ts
+                    && a_period.token_percentage == b_period.token_percentage
+                    && a_period.interval_sec == b_period.interval_sec
+                    && a_period.times == b_period.times
+                    && a_period.airdropped == b_period.airdropped
+            }
+            _ => false,
+        };
+        if !same {
+            differences += 1;
+        }
+
+        println!(
+            "{} {:<4} {:<56} {:<56}",
+            if same { " " } else { "!" },
+            index,
+            a_period,
+            b_period
+        );
+    }
+
+    if a.relative_schedule != b.relative_schedule || a.activation_ts != b.activation_ts {
+        differences += 1;
+        println!("! TGE: {} vs {}", format_tge(&a), format_tge(&b));
+    }
+    if a.vesting.accelerated_at_ts != b.vesting.accelerated_at_ts {
+        differences += 1;
+        println!(
+            "! Accelerated at: {:?} vs {:?}",
+            a.vesting.accelerated_at_ts, b.vesting.accelerated_at_ts
+        );
+    }
+
+    if differences > 0 {
+        return Err(anyhow!("schedules have {} differences", differences));
+    }
+    println!("Schedules are identical");
+
+    Ok(())
+}
@@ -0,0 +1,386 @@
+use anchor_client::{
+    anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas},
+    solana_sdk::{
+        instruction::Instruction, message::Message, native_token::lamports_to_sol, pubkey::Pubkey,
+    },
+    RequestBuilder,
+};
+use anyhow::{anyhow, Result};
+
+use crate::{
+    fetch_root_and_stats, fetch_user_details, format_ts, read_allocations, sender::Sender,
+    Checkpoint, UserDetailsJson,
+};
+
+pub(crate) fn show_claimable(
+    sender: &Sender,
+    claiming: Pubkey,
+    user: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let client = sender.client;
+    let distributor: claiming_factory::MerkleDistributor = client.account(claiming)?;
+    let (root, _stats) = fetch_root_and_stats(client, &claiming)?;
+    let (user_details, _bump) =
+        claiming_factory::UserDetails::find_address(&claiming, root.merkle_index, &user);
+
+    let user_details = match client
+        .rpc()
+        .get_multiple_accounts(&[user_details])?
+        .remove(0)
+    {
+        Some(account) => {
+            claiming_factory::UserDetails::try_deserialize(&mut account.data.as_ref())?
+        }
+        None => {
+            println!("User details aren't initialized yet");
+            claiming_factory::UserDetails::empty()
+        }
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let claimable = distributor.claimable_amounts(&user_details, amount, now);
+
+    println!("Claimed: {}", user_details.claimed_amount);
+    println!(
+        "Claimable now: {}",
+        claimable.amount + user_details.pending_amount
+    );
+    println!("Airdropped, accounted only: {}", claimable.amount_to_add);
+    if claimable.bonus > 0 {
+        println!("Loyalty bonus (before budget cap): {}", claimable.bonus);
+    }
+    match distributor.next_unlock_ts(now) {
+        Some(next_unlock_ts) => println!(
+            "Next unlock: {} (in {} sec)",
+            next_unlock_ts,
+            next_unlock_ts - now
+        ),
+        None => println!("Next unlock: none"),
+    }
+
+    Ok(())
+}
+
+pub(crate) fn simulate(
+    sender: &Sender,
+    claiming: Pubkey,
+    wallet: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let client = sender.client;
+    let distributor: claiming_factory::MerkleDistributor = client.account(claiming)?;
+    if distributor.relative_schedule && distributor.activation_ts == 0 {
+        return Err(anyhow!(
+            "TGE isn't activated yet, so unlocks have no dates, \
+             see check-schedule --relative for the offsets"
+        ));
+    }
+
+    let (root, _stats) = fetch_root_and_stats(client, &claiming)?;
+    let (user_details, _bump) =
+        claiming_factory::UserDetails::find_address(&claiming, root.merkle_index, &wallet);
+    let user_details = match client
+        .rpc()
+        .get_multiple_accounts(&[user_details])?
+        .remove(0)
+    {
+        Some(account) => {
+            claiming_factory::UserDetails::try_deserialize(&mut account.data.as_ref())?
+        }
+        None => claiming_factory::UserDetails::empty(),
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+
+    // airdropped periods unlock at once at their start
+    let mut unlocks: Vec<u64> = distributor
+        .vesting
+        .schedule
+        .iter()
+        .flat_map(|period| {
+            let first = if period.airdropped { 0 } else { 1 };
+            (first..=period.times)
+                .map(move |interval| period.start_ts + interval * period.interval_sec)
+        })
+        .filter(|ts| *ts > now)
+        .collect();
+    unlocks.sort_unstable();
+    unlocks.dedup();
+
+    println!("Claimed so far: {}", user_details.claimed_amount);
+    println!(
+        "{:<24} {:>20} {:>20} {:>20}",
+        "Date", "Unlocked", "Claimable", "Airdropped"
+    );
+
+    let mut previous = (0, 0);
+    for ts in std::iter::once(now).chain(unlocks) {
+        let claimable = distributor.claimable_amounts(&user_details, amount, ts);
+        let current = (
+            claimable.amount + user_details.pending_amount,
+            claimable.amount_to_add,
+        );
+        if ts != now && current == previous {
+            continue;
+        }
+
+        println!(
+            "{:<24} {:>20} {:>20} {:>20}",
+            if ts == now {
+                "now".to_string()
+            } else {
+                format_ts(ts, false)
+            },
+            (current.0 + current.1) - (previous.0 + previous.1),
+            current.0,
+            current.1
+        );
+        previous = current;
+    }
+
+    Ok(())
+}
+
+pub(crate) struct InitUserDetailsArgs {
+    pub(crate) claiming: Pubkey,
+    pub(crate) allocations: String,
+    pub(crate) batch_size: usize,
+    pub(crate) tps: Option<f64>,
+    pub(crate) concurrency: usize,
+    pub(crate) checkpoint: Option<String>,
+    pub(crate) estimate: bool,
+}
+
+pub(crate) fn init_user_details(sender: &Sender, args: InitUserDetailsArgs) -> Result<()> {
+    let InitUserDetailsArgs {
+        claiming,
+        allocations,
+        batch_size,
+        tps,
+        concurrency,
+        checkpoint,
+        estimate,
+    } = args;
+    let client = sender.client;
+    let authority = sender.fee_payer;
+    let rpc = client.rpc();
+    let (root, _stats) = fetch_root_and_stats(client, &claiming)?;
+
+    let mut checkpoint = Checkpoint::open(checkpoint.as_deref(), &claiming)?;
+    let mut seen = std::collections::HashSet::new();
+    let wallets: Vec<Pubkey> = read_allocations(&allocations)?
+        .into_iter()
+        .map(|(user, _amount)| user)
+        .filter(|user| !checkpoint.contains(user) && seen.insert(*user))
+        .collect();
+    println!(
+        "{} wallets are recorded in the checkpoint",
+        checkpoint.len()
+    );
+
+    // rent is paid only for accounts which don't exist yet
+    let mut missing = Vec::new();
+    for chunk in wallets.chunks(100) {
+        let addresses: Vec<(Pubkey, Pubkey, u8)> = chunk
+            .iter()
+            .map(|user| {
+                let (user_details, bump) =
+                    claiming_factory::UserDetails::find_address(&claiming, root.merkle_index, user);
+                (*user, user_details, bump)
+            })
+            .collect();
+
+        let keys: Vec<Pubkey> = addresses.iter().map(|a| a.1).collect();
+        let existing = rpc.get_multiple_accounts(&keys)?;
+        missing.extend(
+            addresses
+                .into_iter()
+                .zip(existing)
+                .filter(|(_addresses, account)| account.is_none())
+                .map(|(addresses, _account)| addresses),
+        );
+    }
+
+    let requests: Vec<RequestBuilder> = missing
+        .chunks(batch_size)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .fold(client.request(), |request, (user, user_details, bump)| {
+                    request.instruction(Instruction {
+                        program_id: client.id(),
+                        accounts: claiming_factory::accounts::InitUserDetails {
+                            payer: authority,
+                            user: *user,
+                            user_details: *user_details,
+                            distributor: claiming,
+                            root: claiming_factory::MerkleRoot::find_address(&claiming).0,
+                            system_program: solana_sdk::system_program::id(),
+                        }
+                        .to_account_metas(None),
+                        data: claiming_factory::instruction::InitUserDetails { bump: *bump }.data(),
+                    })
+                })
+        })
+        .collect();
+
+    let rent = rpc.get_minimum_balance_for_rent_exemption(claiming_factory::UserDetails::LEN)?
+        * missing.len() as u64;
+    let fee = match requests.first() {
+        Some(request) => {
+            let mut message = Message::new(&request.instructions()?, Some(&authority));
+            message.recent_blockhash = rpc.get_latest_blockhash()?;
+            rpc.get_fee_for_message(&message)? * requests.len() as u64
+        }
+        None => 0,
+    };
+    println!(
+        "{} of {} wallets are missing accounts, {} transactions",
+        missing.len(),
+        wallets.len(),
+        requests.len()
+    );
+    println!(
+        "Estimated cost: {} SOL ({} SOL rent, {} SOL fees)",
+        lamports_to_sol(rent + fee),
+        lamports_to_sol(rent),
+        lamports_to_sol(fee)
+    );
+    let balance = rpc.get_balance(&authority)?;
+    if balance < rent + fee {
+        println!(
+            "Warning: payer balance {} SOL doesn't cover the cost",
+            lamports_to_sol(balance)
+        );
+    }
+    if estimate {
+        return Ok(());
+    }
+
+    let total = missing.len();
+    let mut done = 0;
+    let mut failed = 0;
+    let mut requests = requests.into_iter();
+    let mut batches = missing.chunks(batch_size);
+    loop {
+        let group: Vec<RequestBuilder> = requests.by_ref().take(concurrency.max(1)).collect();
+        if group.is_empty() {
+            break;
+        }
+
+        let results = sender.send_all(group, tps)?;
+        for (result, batch) in results.into_iter().zip(batches.by_ref()) {
+            match result {
+                Ok(Some(signature)) => {
+                    let users: Vec<Pubkey> = batch.iter().map(|(user, _, _)| *user).collect();
+                    checkpoint.record(&users, signature)?;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    println!("Batch failed: {}", err);
+                    failed += 1;
+                }
+            }
+            done += batch.len();
+        }
+        println!("Processed {}/{}", done, total);
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} transactions failed, rerun the command to retry them",
+            failed
+        ));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn export_users(
+    sender: &Sender,
+    distributor_key: Pubkey,
+    allocations: String,
+    output: String,
+) -> Result<()> {
+    let client = sender.client;
+    let rpc = client.rpc();
+    let (root, _stats) = fetch_root_and_stats(client, &distributor_key)?;
+    let allocations = read_allocations(&allocations)?;
+    let wallets: Vec<Pubkey> = allocations.iter().map(|(wallet, _)| *wallet).collect();
+    let user_details = fetch_user_details(&rpc, &distributor_key, &root, &wallets)?;
+
+    let mut writer = csv::Writer::from_path(&output)?;
+    writer.write_record(&[
+        "user",
+        "allocation",
+        "claimed_amount",
+        "pending_amount",
+        "claim_count",
+        "first_claimed_at",
+        "last_claimed_at",
+    ])?;
+
+    let mut exported = 0;
+    for ((wallet, allocation), details) in allocations.iter().zip(&user_details) {
+        let details = match details {
+            Some(details) => details,
+            None => continue,
+        };
+        let date = |ts: u64| {
+            if ts == 0 {
+                String::new()
+            } else {
+                format_ts(ts, false)
+            }
+        };
+
+        writer.write_record(&[
+            wallet.to_string(),
+            allocation.to_string(),
+            details.claimed_amount.to_string(),
+            details.pending_amount.to_string(),
+            details.claim_count.to_string(),
+            date(details.first_claimed_at_ts),
+            date(details.last_claimed_at_ts),
+        ])?;
+        exported += 1;
+    }
+
+    writer.flush()?;
+    println!(
+        "Exported {} of {} users of merkle index {} to {}",
+        exported,
+        allocations.len(),
+        root.merkle_index,
+        output
+    );
+
+    Ok(())
+}
+
+pub(crate) fn show_user_details(
+    sender: &Sender,
+    claiming: Pubkey,
+    user: Pubkey,
+    json: bool,
+) -> Result<()> {
+    let client = sender.client;
+    let (root, _stats) = fetch_root_and_stats(client, &claiming)?;
+    let (address, _bump) =
+        claiming_factory::UserDetails::find_address(&claiming, root.merkle_index, &user);
+
+    let user_details: claiming_factory::UserDetails = client.account(address)?;
+    if json {
+        let json = UserDetailsJson::new(&address, &user, &user_details);
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        println!("{:#?}", user_details);
+    }
+
+    Ok(())
+}
@@ -1,17 +1,45 @@
-use std::rc::Rc;
+mod commands;
+mod sender;
+
+use std::{convert::TryFrom, io::Write, rc::Rc};
 
 use anchor_client::{
+    anchor_lang::{AccountDeserialize, AnchorDeserialize, Discriminator},
+    solana_client::rpc_client::RpcClient,
     solana_sdk::{
-        commitment_config::CommitmentConfig, pubkey::Pubkey, signature::read_keypair_file,
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        native_token::lamports_to_sol,
+        nonce,
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Signature},
     },
-    Client,
+    Client, Program,
 };
 use anyhow::{anyhow, Result};
-
+use calamine::Reader;
+use chrono::{TimeZone, Utc};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use solana_sdk::{program_pack::Pack, signature::Keypair, signer::Signer};
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    signature::Keypair,
+    signer::{
+        keypair::{
+            generate_seed_from_seed_phrase_and_passphrase, keypair_from_seed_and_derivation_path,
+        },
+        Signer,
+    },
+};
 use structopt::StructOpt;
 
+use commands::{
+    airdrop, audit, config, create, create::CreateClaimingArgs, distributor, merkle, metrics,
+    notify, program, schedule, user, user::InitUserDetailsArgs,
+};
+use sender::Sender;
+
 #[derive(Debug)]
 struct CliKeypair<A> {
     path: String,
@@ -66,211 +94,1858 @@ impl DefaultPath for Payer {
     }
 }
 
+#[derive(Debug)]
+struct ProgramKeypair;
+
+impl DefaultPath for ProgramKeypair {
+    fn default_path() -> String {
+        "target/deploy/claiming_factory-keypair.json".to_string()
+    }
+}
+
+/// Reads a keypair from a file, an environment variable or a seed phrase prompt.
+fn read_keypair(source: &str) -> Result<Keypair> {
+    if let Some(var) = source.strip_prefix("env:") {
+        let secret =
+            std::env::var(var).map_err(|err| anyhow!("failed to read {}: {}", var, err))?;
+        let bytes = bs58::decode(secret.trim()).into_vec()?;
+        return Keypair::from_bytes(&bytes)
+            .map_err(|err| anyhow!("invalid keypair in {}: {}", var, err));
+    }
+
+    if let Some(path) = source.strip_prefix("prompt:") {
+        // e.g. prompt:m/44'/501'/0'/0'
+        let derivation_path = if path.is_empty() {
+            None
+        } else {
+            Some(
+                DerivationPath::from_absolute_path_str(path)
+                    .map_err(|err| anyhow!("invalid derivation path {}: {}", path, err))?,
+            )
+        };
+
+        let phrase = rpassword::prompt_password_stderr("Seed phrase: ")?;
+        let passphrase = rpassword::prompt_password_stderr("Passphrase (empty for none): ")?;
+        let seed = generate_seed_from_seed_phrase_and_passphrase(
+            &phrase.split_whitespace().collect::<Vec<_>>().join(" "),
+            &passphrase,
+        );
+
+        return keypair_from_seed_and_derivation_path(&seed, derivation_path)
+            .map_err(|err| anyhow!("failed to derive keypair: {}", err));
+    }
+
+    read_keypair_file(source).map_err(|err| anyhow!("failed to read keypair: {}", err))
+}
+
+#[derive(Debug, StructOpt)]
+struct TxOpts {
+    /// Prints the unsigned transaction message instead of sending it,
+    /// e.g. to import it into a multisig.
+    #[structopt(long, requires = "authority")]
+    print_tx: bool,
+    /// Signer of admin instructions in the printed transactions, e.g. a multisig vault.
+    #[structopt(long, requires = "print-tx")]
+    authority: Option<Pubkey>,
+    /// Simulates every transaction instead of sending it.
+    #[structopt(long, conflicts_with = "print-tx")]
+    dry_run: bool,
+    /// Simulates every transaction instead of sending it and reports compute units
+    /// of every instruction and the fee.
+    #[structopt(long, conflicts_with_all = &["print-tx", "dry-run"])]
+    estimate: bool,
+    /// Priority fee in micro-lamports per compute unit the estimated fee includes.
+    #[structopt(long, default_value = "0")]
+    priority_fee: u64,
+    /// How many times a transaction is resent after transient RPC errors or blockhash expiry.
+    #[structopt(long, default_value = "5")]
+    max_retries: u32,
+    #[structopt(long)]
+    skip_preflight: bool,
+    /// Commitment of reads and of sent transactions: processed, confirmed or finalized.
+    #[structopt(long, default_value = "finalized")]
+    commitment: CommitmentLevel,
+    /// Durable nonce account whose blockhash is used instead of a recent one,
+    /// so printed transactions can be signed offline hours later.
+    #[structopt(long)]
+    nonce_account: Option<Pubkey>,
+    /// Signer advancing the nonce, the fee payer by default.
+    #[structopt(long, requires = "nonce-account")]
+    nonce_authority: Option<Pubkey>,
+}
+
 #[derive(Debug, StructOpt)]
 struct Opts {
+    /// Profile from the config file providing defaults of the options below.
+    #[structopt(long)]
+    profile: Option<String>,
+    #[structopt(long, default_value = "~/.config/claiming-cli/config.toml")]
+    config: String,
+    #[structopt(long)]
+    program_id: Option<Pubkey>,
     #[structopt(long)]
-    program_id: Pubkey,
+    cluster: Option<anchor_client::Cluster>,
+    /// Keypair file, `env:VAR` with a base58 secret key in the variable,
+    /// or `prompt:` with an optional derivation path to enter a seed phrase.
     #[structopt(long)]
-    cluster: anchor_client::Cluster,
-    #[structopt(long, default_value)]
-    payer: CliKeypair<Payer>,
+    payer: Option<CliKeypair<Payer>>,
+    /// Prints accounts as JSON instead of the debug format.
+    #[structopt(long)]
+    json: bool,
+    #[structopt(flatten)]
+    tx: TxOpts,
     #[structopt(subcommand)]
     cmd: Command,
 }
 
+/// Named profiles, e.g.
+///
+/// ```toml
+/// [profiles.devnet]
+/// cluster = "devnet"
+/// program_id = "6cJU4mUJe1fKXzvvbZjz72M3d5aQXMmRV2jeQerkFw5b"
+/// payer = "~/.config/solana/devnet.json"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+struct CliConfig {
+    #[serde(default)]
+    profiles: std::collections::HashMap<String, Profile>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+struct Profile {
+    cluster: Option<String>,
+    program_id: Option<String>,
+    payer: Option<String>,
+}
+
+impl Opts {
+    fn profile(&self) -> Result<Profile> {
+        let name = match &self.profile {
+            Some(name) => name,
+            None => return Ok(Profile::default()),
+        };
+
+        let path = shellexpand::tilde(&self.config).to_string();
+        let file = std::fs::read_to_string(&path)
+            .map_err(|err| anyhow!("failed to read {}: {}", path, err))?;
+        let config: CliConfig = toml::from_str(&file)?;
+
+        config
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("profile {} is not found in {}", name, path))
+    }
+
+    /// Options given on the command line take precedence over the profile.
+    fn connection(&self) -> Result<(anchor_client::Cluster, Pubkey, String)> {
+        let profile = self.profile()?;
+
+        let cluster = match (&self.cluster, profile.cluster) {
+            (Some(cluster), _) => cluster.clone(),
+            (None, Some(cluster)) => cluster.parse()?,
+            (None, None) => return Err(anyhow!("--cluster or a profile is required")),
+        };
+
+        let program_id = match (self.program_id, profile.program_id) {
+            (Some(program_id), _) => program_id,
+            (None, Some(program_id)) => program_id.parse()?,
+            (None, None) => return Err(anyhow!("--program-id or a profile is required")),
+        };
+
+        let payer = match (&self.payer, profile.payer) {
+            (Some(payer), _) => payer.path.clone(),
+            (None, Some(payer)) => shellexpand::tilde(&payer).to_string(),
+            (None, None) => Payer::default_path(),
+        };
+
+        Ok((cluster, program_id, payer))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MerkleData {
     data: [u8; 32],
 }
 
+/// Merkle tree with proofs of every allocation, as written by `getMerkleProof`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleTree {
+    root: [u8; 32],
+    total_tokens: u64,
+    proofs: Vec<MerkleProof>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MerkleProof {
+    /// Base58 wallet address.
+    address: String,
+    amount: u64,
+    proofs: Vec<[u8; 32]>,
+}
+
+impl MerkleTree {
+    /// Builds the tree the same way as `getMerkleProof`: sorted and deduplicated leaves,
+    /// an odd element is carried to the next layer as is.
+    fn new(allocations: &[(Pubkey, u64)]) -> Self {
+        let leaves = allocations
+            .par_iter()
+            .map(|(wallet, amount)| allocation_leaf(wallet, *amount))
+            .collect();
+        let layers = merkle_layers(leaves);
+
+        Self::from_layers(
+            &layers,
+            allocations.iter().map(|(_, amount)| amount).sum(),
+            allocations,
+        )
+    }
+
+    /// Tree with proofs of only the given allocations.
+    fn from_layers(
+        layers: &[Vec<[u8; 32]>],
+        total_tokens: u64,
+        allocations: &[(Pubkey, u64)],
+    ) -> Self {
+        let proofs = allocations
+            .par_iter()
+            .map(|(wallet, amount)| MerkleProof {
+                address: wallet.to_string(),
+                amount: *amount,
+                proofs: merkle_proof(layers, &allocation_leaf(wallet, *amount)),
+            })
+            .collect();
+
+        Self {
+            root: layers.last().unwrap().first().copied().unwrap_or_default(),
+            total_tokens,
+            proofs,
+        }
+    }
+
+    /// Length of the longest proof.
+    fn depth(&self) -> u8 {
+        self.proofs
+            .iter()
+            .map(|proof| proof.proofs.len())
+            .max()
+            .unwrap_or(0) as u8
+    }
+
+    /// Reads the tree, or the shard with the wallet if the file lists shards.
+    fn read(path: &str, wallet: &Pubkey) -> Result<Self> {
+        let file = std::fs::read(path)?;
+        if let Ok(shards) = serde_json::from_slice::<MerkleTreeShards>(&file) {
+            let shard = &shards.shards[shard_of(wallet, shards.shards.len())];
+            let shard = std::path::Path::new(path).with_file_name(shard);
+            return Self::read(&shard.to_string_lossy(), wallet);
+        }
+
+        Ok(serde_json::from_slice(&file)?)
+    }
+
+    fn find(&self, wallet: &Pubkey) -> Option<&MerkleProof> {
+        let wallet = wallet.to_string();
+        self.proofs.iter().find(|proof| proof.address == wallet)
+    }
+}
+
+/// Written instead of the tree when proofs are split into several files, every shard is
+/// a [`MerkleTree`] with the proofs of the wallets for which `shard_of` gives its index.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MerkleTreeShards {
+    root: [u8; 32],
+    total_tokens: u64,
+    /// File names relative to this file.
+    shards: Vec<String>,
+}
+
+fn shard_of(wallet: &Pubkey, shards: usize) -> usize {
+    let bytes = wallet.as_ref();
+    u16::from_be_bytes([bytes[0], bytes[1]]) as usize % shards
+}
+
+/// Layers of the tree from the sorted and deduplicated leaves up to the root,
+/// the same way as `getMerkleProof` builds them: an odd element is carried to the next
+/// layer as is. Layers take about 64 bytes per leaf, so they're kept in memory
+/// even for millions of leaves.
+fn merkle_layers(mut leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    leaves.par_sort_unstable();
+    leaves.dedup();
+
+    let mut layers = vec![leaves];
+    while layers.last().unwrap().len() > 1 {
+        let next = layers
+            .last()
+            .unwrap()
+            .par_chunks(2)
+            .map(|pair| match pair {
+                [first, second] => combined_hash(first, second),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+        layers.push(next);
+    }
+
+    layers
+}
+
+fn merkle_proof(layers: &[Vec<[u8; 32]>], leaf: &[u8; 32]) -> Vec<[u8; 32]> {
+    let mut index = layers[0].binary_search(leaf).unwrap();
+    let mut proof = Vec::new();
+    for layer in &layers[..layers.len() - 1] {
+        if let Some(pair) = layer.get(index ^ 1) {
+            proof.push(*pair);
+        }
+        index /= 2;
+    }
+
+    proof
+}
+
+/// Writes the tree to `output`, or the list of shards to it and every shard next to it,
+/// and the same tree for the EVM contract to `evm_output`. Proofs are streamed into the files
+/// as they are computed, so only the layers of the tree are held in memory.
+fn write_merkle_tree(
+    allocations: &[(Pubkey, u64)],
+    output: &str,
+    evm_output: Option<&str>,
+    shards: usize,
+) -> Result<([u8; 32], u64)> {
+    let leaves = allocations
+        .par_iter()
+        .map(|(wallet, amount)| allocation_leaf(wallet, *amount))
+        .collect();
+    let layers = merkle_layers(leaves);
+    let root = layers.last().unwrap().first().copied().unwrap_or_default();
+    let total_tokens = allocations
+        .iter()
+        .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+        .ok_or_else(|| anyhow!("total of allocations overflows u64"))?;
+
+    if let Some(evm_output) = evm_output {
+        write_evm_merkle_tree(
+            &layers,
+            total_tokens,
+            allocations,
+            std::path::Path::new(evm_output),
+        )?;
+    }
+
+    let output = std::path::Path::new(output);
+    if shards <= 1 {
+        write_merkle_proofs(&layers, total_tokens, allocations, output)?;
+        return Ok((root, total_tokens));
+    }
+
+    let stem = output
+        .file_stem()
+        .ok_or_else(|| anyhow!("output has no file name"))?
+        .to_string_lossy();
+    let names: Vec<String> = (0..shards)
+        .map(|index| format!("{}-{:04}.json", stem, index))
+        .collect();
+
+    // a single pass instead of every shard scanning all allocations
+    let mut buckets = vec![Vec::new(); shards];
+    for (wallet, amount) in allocations {
+        buckets[shard_of(wallet, shards)].push((*wallet, *amount));
+    }
+    for (shard, name) in buckets.iter().zip(&names) {
+        write_merkle_proofs(&layers, total_tokens, shard, &output.with_file_name(name))?;
+    }
+
+    let file = std::io::BufWriter::new(std::fs::File::create(output)?);
+    serde_json::to_writer_pretty(
+        file,
+        &MerkleTreeShards {
+            root,
+            total_tokens,
+            shards: names,
+        },
+    )?;
+
+    Ok((root, total_tokens))
+}
+
+/// Allocations whose proofs are computed at once before being written out.
+const STREAM_CHUNK: usize = 64 * 1024;
+
+/// Writes an entry of every allocation separated by commas. Entries are computed in parallel
+/// a chunk at a time and written right away, so they're never all held in memory.
+fn stream_entries<W: Write, T: Send>(
+    out: &mut W,
+    allocations: &[(Pubkey, u64)],
+    entry: impl Fn(&Pubkey, u64) -> Result<T> + Sync,
+    mut write: impl FnMut(&mut W, T) -> Result<()>,
+) -> Result<()> {
+    let mut first = true;
+    for chunk in allocations.chunks(STREAM_CHUNK) {
+        let entries = chunk
+            .par_iter()
+            .map(|(wallet, amount)| entry(wallet, *amount))
+            .collect::<Result<Vec<_>>>()?;
+        for entry in entries {
+            if !first {
+                out.write_all(b",")?;
+            }
+            first = false;
+            write(out, entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a [`MerkleTree`] with proofs of the given allocations.
+fn write_merkle_proofs(
+    layers: &[Vec<[u8; 32]>],
+    total_tokens: u64,
+    allocations: &[(Pubkey, u64)],
+    path: &std::path::Path,
+) -> Result<()> {
+    let root = layers.last().unwrap().first().copied().unwrap_or_default();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    write!(
+        file,
+        "{{\"root\":{},\"totalTokens\":{},\"proofs\":[",
+        serde_json::to_string(&root)?,
+        total_tokens
+    )?;
+    stream_entries(
+        &mut file,
+        allocations,
+        |wallet, amount| {
+            Ok(MerkleProof {
+                address: wallet.to_string(),
+                amount,
+                proofs: merkle_proof(layers, &allocation_leaf(wallet, amount)),
+            })
+        },
+        |file, proof| Ok(serde_json::to_writer(file, &proof)?),
+    )?;
+    file.write_all(b"]}")?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// The same tree in the format of the EVM claiming contract and frontend: `0x` prefixed hex
+/// and decimal amounts as strings, `{"merkleRoot", "tokenTotal", "claims"}` with claims
+/// keyed by the base58 wallet address. The leaf is exactly
+/// `keccak256(abi.encodePacked(bytes32 wallet, uint64 amount))` and pairs are hashed sorted,
+/// so the root and the proofs verify with OpenZeppelin `MerkleProof` unchanged.
+fn write_evm_merkle_tree(
+    layers: &[Vec<[u8; 32]>],
+    total_tokens: u64,
+    allocations: &[(Pubkey, u64)],
+    path: &std::path::Path,
+) -> Result<()> {
+    let root = layers.last().unwrap().first().copied().unwrap_or_default();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    write!(
+        file,
+        "{{\"merkleRoot\":\"0x{}\",\"tokenTotal\":\"{}\",\"claims\":{{",
+        hex(&root),
+        total_tokens
+    )?;
+    stream_entries(
+        &mut file,
+        allocations,
+        |wallet, amount| {
+            let leaf = allocation_leaf(wallet, amount);
+            let proof = merkle_proof(layers, &leaf);
+            if !verify_proof(wallet, amount, &proof, &root) {
+                return Err(anyhow!("proof of {} doesn't match the tree root", wallet));
+            }
+
+            Ok((
+                wallet.to_string(),
+                EvmClaim {
+                    wallet: format!("0x{}", hex(wallet.as_ref())),
+                    amount: amount.to_string(),
+                    leaf: format!("0x{}", hex(&leaf)),
+                    proof: proof
+                        .iter()
+                        .map(|node| format!("0x{}", hex(node)))
+                        .collect(),
+                },
+            ))
+        },
+        |file, (address, claim)| {
+            write!(file, "{}:", serde_json::to_string(&address)?)?;
+            Ok(serde_json::to_writer(file, &claim)?)
+        },
+    )?;
+    file.write_all(b"}}")?;
+    file.flush()?;
+
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct EvmClaim {
+    /// Wallet public key as `bytes32`.
+    wallet: String,
+    amount: String,
+    leaf: String,
+    proof: Vec<String>,
+}
+
+/// Reads `start_ts,token_percentage,interval_sec,times,airdropped` rows without headers.
+fn read_schedule(path: &str) -> Result<Vec<claiming_factory::Period>> {
+    let file = std::fs::read(path)?;
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(&*file);
+    let mut schedule = Vec::new();
+    for result in rdr.records() {
+        let record = result?;
+
+        let start_ts = parse_schedule_ts(record.get(0).ok_or(anyhow!(
+            "missing period start value (should be unix timestamp, RFC3339 date or offset from TGE)"
+        ))?)?;
+
+        let token_percentage = record
+            .get(1)
+            .ok_or(anyhow!(
+                "missing token percentage value for period (in basis points)"
+            ))?
+            .parse::<u64>()?;
+
+        let interval_sec = parse_duration(
+            record
+                .get(2)
+                .ok_or(anyhow!("missing interval for period"))?,
+        )?;
+
+        let times = record
+            .get(3)
+            .ok_or(anyhow!("missing interval times for periods"))?
+            .parse::<u64>()?;
+
+        let airdropped = record
+            .get(4)
+            .ok_or(anyhow!("missing airdropped flag"))?
+            .parse::<bool>()?;
+
+        schedule.push(claiming_factory::Period {
+            start_ts,
+            token_percentage,
+            interval_sec,
+            times,
+            airdropped,
+        });
+    }
+
+    Ok(schedule)
+}
+
+/// Period start is a unix timestamp or an offset from TGE in seconds,
+/// an RFC3339 date like `2025-03-01T12:00:00Z`, or an offset like `30d`.
+fn parse_schedule_ts(s: &str) -> Result<u64> {
+    let s = s.trim();
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(s) {
+        return u64::try_from(ts.timestamp()).map_err(|_| anyhow!("{} is before 1970", s));
+    }
+
+    parse_duration(s)
+}
+
+/// Seconds as a plain number or with a unit suffix, e.g. `90s`, `15m`, `12h`, `30d`, `1w`.
+fn parse_duration(s: &str) -> Result<u64> {
+    let s = s.trim();
+    if let Ok(sec) = s.parse::<u64>() {
+        return Ok(sec);
+    }
+
+    let unit_start = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("invalid duration {}", s))?;
+    let (value, unit) = s.split_at(unit_start);
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| anyhow!("invalid duration {}", s))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(anyhow!("unknown unit {} in duration {}", unit, s)),
+    };
+
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("duration {} is too long", s))
+}
+
+/// Reads `wallet,amount` rows from CSV, or from the first sheet of an `.xlsx` file.
+/// Amounts are integers in base units, all invalid rows are reported before failing.
+fn read_allocations(path: &str) -> Result<Vec<(Pubkey, u64)>> {
+    let mut validator = AllocationsValidator::default();
+
+    let xlsx = std::path::Path::new(path)
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("xlsx"));
+    if xlsx {
+        let mut workbook = calamine::open_workbook_auto(path)?;
+        let range = workbook
+            .worksheet_range_at(0)
+            .ok_or_else(|| anyhow!("{} has no sheets", path))??;
+        let first_row = range.start().map_or(0, |(row, _column)| row as usize);
+        for (index, cells) in range.rows().enumerate() {
+            let wallet = cells.get(0).map_or(String::new(), |cell| cell.to_string());
+            let amount = cells
+                .get(1)
+                .map_or(Err("missing amount".to_string()), cell_amount);
+            validator.row(first_row + index + 1, &wallet, amount);
+        }
+    } else {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)?;
+        for (index, result) in rdr.records().enumerate() {
+            let record = result?;
+            let amount = record
+                .get(1)
+                .map_or(Err("missing amount".to_string()), parse_amount);
+            validator.row(index + 1, record.get(0).unwrap_or_default(), amount);
+        }
+    }
+
+    validator.finish(path)
+}
+
+/// Integer amount in base units, rejecting what spreadsheets tend to produce instead:
+/// scientific notation, decimal and thousands separators.
+fn parse_amount(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("missing amount".to_string());
+    }
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(format!("amount {:?} isn't an integer in base units", text));
+    }
+
+    text.parse()
+        .map_err(|_| format!("amount {} doesn't fit in u64", text))
+}
+
+fn cell_amount(cell: &calamine::DataType) -> Result<u64, String> {
+    // numbers are stored as doubles, which are exact only up to 2^53
+    const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+    match cell {
+        calamine::DataType::Int(value) => {
+            u64::try_from(*value).map_err(|_| format!("amount {} is negative", value))
+        }
+        calamine::DataType::Float(value)
+            if value.fract() == 0.0 && *value >= 0.0 && *value <= MAX_EXACT =>
+        {
+            Ok(*value as u64)
+        }
+        calamine::DataType::Float(value) => Err(format!(
+            "amount {} isn't an exact integer, big amounts have to be stored as text",
+            value
+        )),
+        calamine::DataType::String(text) => parse_amount(text),
+        calamine::DataType::Empty => Err("missing amount".to_string()),
+        other => Err(format!("amount {} isn't a number", other)),
+    }
+}
+
+/// Collects allocations and everything wrong with them, so a file gets fixed in one go.
+#[derive(Default)]
+struct AllocationsValidator {
+    allocations: Vec<(Pubkey, u64)>,
+    seen: std::collections::HashSet<Pubkey>,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl AllocationsValidator {
+    /// Rows are numbered from 1 as spreadsheets show them.
+    fn row(&mut self, row: usize, wallet: &str, amount: Result<u64, String>) {
+        let wallet = match wallet.trim().parse::<Pubkey>() {
+            Ok(wallet) => wallet,
+            // files exported from spreadsheets usually start with a header
+            Err(_) if row == 1 && amount.is_err() => return,
+            Err(_) => {
+                self.errors
+                    .push(format!("row {}: invalid wallet address {:?}", row, wallet));
+                return;
+            }
+        };
+        let amount = match amount {
+            Ok(amount) => amount,
+            Err(err) => {
+                self.errors.push(format!("row {}: {}", row, err));
+                return;
+            }
+        };
+
+        if amount == 0 {
+            self.warnings
+                .push(format!("row {}: zero allocation of {}", row, wallet));
+        }
+        if !self.seen.insert(wallet) {
+            self.warnings
+                .push(format!("row {}: {} is listed more than once", row, wallet));
+        }
+        self.allocations.push((wallet, amount));
+    }
+
+    fn finish(self, path: &str) -> Result<Vec<(Pubkey, u64)>> {
+        const SHOWN: usize = 20;
+
+        for (label, issues) in [("Warning", &self.warnings), ("Error", &self.errors)] {
+            for issue in issues.iter().take(SHOWN) {
+                println!("{}: {}", label, issue);
+            }
+            if issues.len() > SHOWN {
+                println!("{}: ...and {} more", label, issues.len() - SHOWN);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(anyhow!(
+                "{} of the rows in {} are invalid",
+                self.errors.len(),
+                path
+            ));
+        }
+
+        Ok(self.allocations)
+    }
+}
+
+fn allocation_leaf(wallet: &Pubkey, amount: u64) -> [u8; 32] {
+    solana_sdk::keccak::hashv(&[&wallet.to_bytes()[..], &amount.to_be_bytes()]).0
+}
+
+fn combined_hash(first: &[u8; 32], second: &[u8; 32]) -> [u8; 32] {
+    if first <= second {
+        solana_sdk::keccak::hashv(&[first.as_ref(), second.as_ref()]).0
+    } else {
+        solana_sdk::keccak::hashv(&[second.as_ref(), first.as_ref()]).0
+    }
+}
+
+/// Same leaf and proof verification as the program does.
+fn verify_proof(wallet: &Pubkey, amount: u64, proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    let mut computed_hash = allocation_leaf(wallet, amount);
+    for proof_element in proof {
+        computed_hash = combined_hash(&computed_hash, proof_element);
+    }
+
+    computed_hash == *root
+}
+
+/// Arguments of the `claim` instruction as they are passed by the web3 client.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ClaimArgsJson<'a> {
+    amount: u64,
+    merkle_proof: &'a [[u8; 32]],
+    custodial_destination: bool,
+}
+
 #[derive(Debug, StructOpt)]
 enum Command {
     InitConfig {},
     ShowConfig {},
+    InitTreasury {},
+    /// Records the version of the deployed program in the config.
+    SetVersion {},
+    /// Prints the upgrade authority, last deploy slot and version of the deployed program.
+    ProgramInfo {},
+    SetCreationFee {
+        /// Fee in lamports charged for every new distributor.
+        #[structopt(long)]
+        lamports: u64,
+    },
+    WithdrawFees {
+        /// Lamports to withdraw from the treasury.
+        #[structopt(long)]
+        lamports: u64,
+        /// Receiver of the fees, payer by default.
+        #[structopt(long)]
+        target: Option<Pubkey>,
+    },
     AddAdmin {
         #[structopt(long)]
         admin: Pubkey,
     },
     CreateClaiming {
+        /// Asks for every setting in turn and shows a summary before sending.
+        #[structopt(
+            long,
+            conflicts_with_all = &["merkle", "mint", "schedule", "total-allocation", "clawback-destination"]
+        )]
+        interactive: bool,
+        #[structopt(long, required_unless = "interactive")]
+        merkle: Option<String>,
+        #[structopt(long, required_unless = "interactive")]
+        mint: Option<Pubkey>,
+        #[structopt(long, required_unless = "interactive")]
+        schedule: Option<String>,
         #[structopt(long)]
-        merkle: String,
+        position_nfts: bool,
         #[structopt(long)]
-        mint: Pubkey,
+        max_claim_per_tx: Option<u64>,
+        /// One of: ido, airdrop, team, advisors.
+        #[structopt(long, default_value = "ido", parse(try_from_str = parse_category))]
+        category: claiming_factory::DistributorCategory,
+        /// Sum of all allocations in the merkle tree.
+        #[structopt(long, required_unless = "interactive")]
+        total_allocation: Option<u64>,
+        /// Period starts in the schedule are offsets in seconds from TGE.
         #[structopt(long)]
-        schedule: String,
+        relative_schedule: bool,
+        /// Depth of the merkle tree, longer proofs are rejected.
+        #[structopt(long)]
+        max_proof_len: Option<u8>,
+        /// Project token account, the only one unclaimed tokens can be withdrawn to.
+        #[structopt(long, required_unless = "interactive")]
+        clawback_destination: Option<Pubkey>,
+        /// Creates the vault from a new keypair instead of the associated token account
+        /// of the vault authority.
+        #[structopt(long)]
+        keypair_vault: bool,
     },
     ShowClaiming {
         #[structopt(long)]
         claiming: Pubkey,
     },
+    /// Prints addresses of the accounts of a user in the claiming and whether they exist.
+    Derive {
+        #[structopt(long)]
+        claiming: Pubkey,
+        #[structopt(long)]
+        user: Pubkey,
+    },
+    /// Prints the distributor for humans: dates in UTC, amounts in tokens, timelocks
+    /// left and progress of every period of the schedule.
+    Report {
+        #[structopt(long)]
+        distributor: Pubkey,
+    },
+    /// Creates the same claiming on every cluster of the manifest in order,
+    /// writing the created addresses back into it.
+    DeployClaiming {
+        /// YAML file, see `Manifest`.
+        #[structopt(long)]
+        manifest: String,
+    },
+    /// Replaces the merkle tree, either with the given root or the one built from allocations.
+    UpdateRoot {
+        #[structopt(long)]
+        claiming: Pubkey,
+        /// Root in the same JSON format as for `create-claiming`.
+        #[structopt(long, required_unless = "allocations", conflicts_with = "allocations")]
+        merkle: Option<String>,
+        /// CSV or .xlsx with `wallet,amount` rows to build the tree from.
+        #[structopt(long)]
+        allocations: Option<String>,
+        /// Sum of all allocations, required with `--merkle`.
+        #[structopt(long, required_unless = "allocations")]
+        total_allocation: Option<u64>,
+        /// Depth of the new tree, computed when built from allocations.
+        #[structopt(long)]
+        max_proof_len: Option<u8>,
+        #[structopt(long)]
+        unpause: bool,
+    },
+    /// Rebuilds the tree from allocations and fails if it differs from the one on-chain.
+    VerifyRoot {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// CSV or .xlsx with `wallet,amount` rows.
+        #[structopt(long)]
+        allocations: String,
+    },
+    /// Computes what the user can claim right now without sending anything.
+    ShowClaimable {
+        #[structopt(long)]
+        claiming: Pubkey,
+        #[structopt(long)]
+        user: Pubkey,
+        /// Allocation of the user in the current tree.
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Prints every future unlock of the user, assuming nothing more is claimed meanwhile.
+    Simulate {
+        #[structopt(long)]
+        claiming: Pubkey,
+        #[structopt(long)]
+        wallet: Pubkey,
+        /// Allocation of the user in the current tree.
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Creates user details of every wallet from the allocations CSV in advance.
+    InitUserDetails {
+        #[structopt(long)]
+        claiming: Pubkey,
+        /// CSV or .xlsx with `wallet,amount` rows.
+        #[structopt(long)]
+        allocations: String,
+        /// Instructions packed into a single transaction.
+        #[structopt(long, default_value = "10")]
+        batch_size: usize,
+        /// Transactions sent per second at most.
+        #[structopt(long)]
+        tps: Option<f64>,
+        /// Transactions sent before waiting for their finalization.
+        #[structopt(long, default_value = "1")]
+        concurrency: usize,
+        /// File recording initialized wallets, they're skipped when the command is rerun.
+        #[structopt(long, alias = "progress")]
+        checkpoint: Option<String>,
+        /// Only prints how many accounts are missing and what creating them costs.
+        #[structopt(long)]
+        estimate: bool,
+    },
+    /// Validates the schedule CSV like the program does and prints the unlock timeline.
+    CheckSchedule {
+        schedule: String,
+        /// Period starts are offsets from TGE.
+        #[structopt(long)]
+        relative: bool,
+    },
+    /// Groups `wallet,amount,tier` rows by tier and prints their statistics, doesn't touch the cluster.
+    SummarizeAllocations {
+        allocations: String,
+        /// Intended raise in base units, the grand total has to match it.
+        #[structopt(long)]
+        expected_total: Option<u64>,
+    },
+    /// Writes claims of the distributor found in the transaction history to CSV.
+    ExportClaims {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// Slot number or date (e.g. 2022-05-01 or 2022-05-01T12:00:00Z) to start from.
+        #[structopt(long, parse(try_from_str = parse_since))]
+        since: Since,
+        #[structopt(long, default_value = "claims.csv")]
+        output: String,
+    },
+    /// Prints the schedules of two distributors side by side and fails if they differ.
+    DiffSchedules {
+        #[structopt(long)]
+        a: Pubkey,
+        #[structopt(long)]
+        b: Pubkey,
+    },
+    /// Writes user details of every wallet in the current tree to CSV.
+    ExportUsers {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// CSV or .xlsx with `wallet,amount` rows of the current tree, user details don't store
+        /// the wallet, so it's the only way to tell whose they are.
+        #[structopt(long)]
+        allocations: String,
+        #[structopt(long, default_value = "users.csv")]
+        output: String,
+    },
+    /// Pushes tokens to recipients by claiming on their behalf, the payer has to be a relayer.
+    Airdrop {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// CSV or .xlsx with `wallet,amount` rows of the whole current tree.
+        #[structopt(long)]
+        recipients: String,
+        /// Claims packed into a single transaction.
+        #[structopt(long, default_value = "2")]
+        batch_size: usize,
+        /// CSV with `wallet,status,signature` of every recipient.
+        #[structopt(long, default_value = "airdrop-results.csv")]
+        output: String,
+        /// File recording recipients claimed for, they're skipped when the command is rerun.
+        #[structopt(long)]
+        checkpoint: Option<String>,
+    },
+    /// Transfers tokens from the associated token account of the payer into the vault.
+    Fund {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// Amount in base units of the mint.
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Cross-checks allocations, vault balances and claimed amounts of the distributor.
+    Reconcile {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// CSV or .xlsx with `wallet,amount` rows of the current tree.
+        #[structopt(long)]
+        allocations: String,
+    },
+    /// Computes how many tokens in the vault exceed what users can claim today.
+    Surplus {
+        #[structopt(long)]
+        distributor: Pubkey,
+        /// CSV or .xlsx with `wallet,amount` rows of the current tree.
+        #[structopt(long)]
+        allocations: String,
+    },
+    /// Shows user details of the user in the current tree.
+    ShowUserDetails {
+        #[structopt(long)]
+        claiming: Pubkey,
+        #[structopt(long)]
+        user: Pubkey,
+    },
+    /// Builds the merkle tree with proofs of every allocation, doesn't touch the cluster.
+    GenerateMerkle {
+        /// CSV or .xlsx with `wallet,amount` rows.
+        #[structopt(long)]
+        allocations: String,
+        #[structopt(long, default_value = "tree.json")]
+        output: String,
+        /// Also writes the same tree for the EVM claiming contract, see `write_evm_merkle_tree`.
+        #[structopt(long)]
+        evm_output: Option<String>,
+        /// Splits proofs into this many files, see `MerkleTreeShards`.
+        #[structopt(long, default_value = "1")]
+        shards: usize,
+    },
+    /// Prints the claim arguments of a single wallet, doesn't touch the cluster.
+    GetProof {
+        #[structopt(long)]
+        wallet: Pubkey,
+        /// JSON file with the whole merkle tree or the list of its shards.
+        #[structopt(long)]
+        tree: String,
+    },
+    /// Resolves the relative schedule of the claiming at TGE.
+    ActivateTge {
+        #[structopt(long)]
+        claiming: Pubkey,
+        /// TGE unix timestamp in seconds, now by default.
+        #[structopt(long)]
+        activation_ts: Option<u64>,
+    },
+    Metrics(MetricsCommand),
+    Program(ProgramCommand),
+    Nonce(NonceCommand),
+    /// Watches the distributors and posts their events to a Slack or Discord compatible webhook.
+    Notify {
+        #[structopt(long)]
+        webhook_url: String,
+        #[structopt(long, required = true)]
+        distributor: Vec<Pubkey>,
+        /// Claims of at least this amount are posted, claims aren't posted without it.
+        #[structopt(long)]
+        claim_threshold: Option<u64>,
+        /// Seconds between polls.
+        #[structopt(long, default_value = "10")]
+        interval: u64,
+    },
 }
 
-fn main() -> Result<()> {
-    let opts = Opts::from_args();
+#[derive(Debug, StructOpt)]
+enum MetricsCommand {
+    /// Polls the distributors and exports their state as Prometheus gauges on `/metrics`.
+    Serve {
+        #[structopt(long, required = true)]
+        distributor: Vec<Pubkey>,
+        #[structopt(long, default_value = "9100")]
+        port: u16,
+        /// Seconds between polls.
+        #[structopt(long, default_value = "30")]
+        interval: u64,
+    },
+}
 
-    let payer = read_keypair_file(opts.payer.as_ref())
-        .map_err(|err| anyhow!("failed to read keypair: {}", err))?;
-    let payer = Rc::new(payer);
+#[derive(Debug, StructOpt)]
+enum ProgramCommand {
+    /// Writes the built program into a new buffer and deploys it at the address of the keypair.
+    Deploy {
+        /// Built program, e.g. target/deploy/claiming_factory.so.
+        path: String,
+        #[structopt(long, default_value)]
+        program_keypair: CliKeypair<ProgramKeypair>,
+        /// Bytes reserved for the program including future upgrades, twice its size by default.
+        #[structopt(long)]
+        max_len: Option<usize>,
+        /// Writes into the buffer sent per second at most.
+        #[structopt(long)]
+        tps: Option<f64>,
+        /// Compares the hash of the built program to the deployed one.
+        #[structopt(long)]
+        verify_hash: bool,
+    },
+    /// Writes the built program into a new buffer and upgrades `--program-id` from it.
+    Upgrade {
+        /// Built program, e.g. target/deploy/claiming_factory.so.
+        path: String,
+        /// Hands the buffer over to the upgrade authority instead of upgrading,
+        /// when it isn't the payer, e.g. a multisig.
+        #[structopt(long)]
+        buffer_authority: Option<Pubkey>,
+        /// Writes into the buffer sent per second at most.
+        #[structopt(long)]
+        tps: Option<f64>,
+        /// Compares the hash of the built program to the deployed one.
+        #[structopt(long, conflicts_with = "buffer-authority")]
+        verify_hash: bool,
+    },
+}
 
-    let client =
-        Client::new_with_options(opts.cluster, payer.clone(), CommitmentConfig::processed());
-    let client = client.program(opts.program_id);
+#[derive(Debug, StructOpt)]
+enum NonceCommand {
+    /// Creates a durable nonce account at a new address for `--nonce-account`.
+    Create {
+        /// Signer advancing the nonce, the payer by default.
+        #[structopt(long)]
+        authority: Option<Pubkey>,
+    },
+    /// Prints the blockhash stored in the nonce account and its authority.
+    Show { address: Pubkey },
+    /// Stores a new blockhash, transactions signed against the previous one can't land anymore.
+    Advance { address: Pubkey },
+}
 
-    match opts.cmd {
-        Command::InitConfig {} => {
-            let (config, bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
-            println!("Config address: {}", config);
-
-            let r = client
-                .request()
-                .accounts(claiming_factory::accounts::InitializeConfig {
-                    system_program: solana_sdk::system_program::id(),
-                    owner: payer.pubkey(),
-                    config,
-                })
-                .args(claiming_factory::instruction::InitializeConfig { bump })
-                .signer(payer.as_ref())
-                .send()?;
-
-            println!("Result:\n{}", r);
+#[derive(Debug, Clone, Copy)]
+enum Since {
+    Slot(u64),
+    Timestamp(i64),
+}
+
+fn parse_since(s: &str) -> Result<Since> {
+    if let Ok(slot) = s.parse::<u64>() {
+        return Ok(Since::Slot(slot));
+    }
+
+    let date = match chrono::DateTime::parse_from_rfc3339(s) {
+        Ok(date) => date.timestamp(),
+        Err(_) => {
+            let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")?;
+            Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                .timestamp()
         }
-        Command::ShowConfig {} => {
-            let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
+    };
 
-            let config: claiming_factory::Config = client.account(config)?;
-            println!("{:#?}", config);
+    Ok(Since::Timestamp(date))
+}
+
+impl Since {
+    fn includes(&self, slot: u64, block_time: Option<i64>) -> bool {
+        match *self {
+            Since::Slot(since) => slot >= since,
+            // transactions without the block time are too old to have it recorded
+            Since::Timestamp(since) => block_time.map_or(false, |ts| ts >= since),
         }
-        Command::AddAdmin { admin } => {
-            let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
-            println!("Config address: {}", config);
-
-            let r = client
-                .request()
-                .accounts(claiming_factory::accounts::AddAdmin {
-                    owner: payer.pubkey(),
-                    config,
-                    admin,
-                })
-                .args(claiming_factory::instruction::AddAdmin {})
-                .signer(payer.as_ref())
-                .send()?;
-
-            println!("Result:\n{}", r);
+    }
+}
+
+fn token_balance(rpc: &RpcClient, token_account: &Pubkey) -> Result<u64> {
+    let account = rpc.get_account(token_account)?;
+    let token_account = spl_token::state::Account::unpack(&account.data)?;
+
+    Ok(token_account.amount)
+}
+
+/// State of an initialized durable nonce account.
+fn read_nonce(rpc: &RpcClient, address: &Pubkey) -> Result<nonce::state::Data> {
+    let account = rpc.get_account(address)?;
+    if account.owner != solana_sdk::system_program::id() {
+        return Err(anyhow!("{} isn't a nonce account", address));
+    }
+
+    match bincode::deserialize::<nonce::state::Versions>(&account.data)?.convert_to_current() {
+        nonce::State::Initialized(data) => Ok(data),
+        nonce::State::Uninitialized => Err(anyhow!("nonce account {} isn't initialized", address)),
+    }
+}
+
+/// Active tree and claim counters of the distributor, both kept in their own accounts.
+fn fetch_root_and_stats(
+    client: &Program,
+    distributor_key: &Pubkey,
+) -> Result<(claiming_factory::MerkleRoot, claiming_factory::ClaimStats)> {
+    let root = client.account(claiming_factory::MerkleRoot::find_address(distributor_key).0)?;
+    let stats = client.account(claiming_factory::ClaimStats::find_address(distributor_key).0)?;
+
+    Ok((root, stats))
+}
+
+/// User details of the wallets in the current tree, `None` for users who never claimed.
+fn fetch_user_details(
+    rpc: &RpcClient,
+    distributor_key: &Pubkey,
+    root: &claiming_factory::MerkleRoot,
+    wallets: &[Pubkey],
+) -> Result<Vec<Option<claiming_factory::UserDetails>>> {
+    let mut user_details = Vec::with_capacity(wallets.len());
+
+    // RPC limits the number of accounts per request
+    for chunk in wallets.chunks(100) {
+        let addresses: Vec<Pubkey> = chunk
+            .iter()
+            .map(|wallet| {
+                claiming_factory::UserDetails::find_address(
+                    distributor_key,
+                    root.merkle_index,
+                    wallet,
+                )
+                .0
+            })
+            .collect();
+
+        for account in rpc.get_multiple_accounts(&addresses)? {
+            let details = match account {
+                Some(account) => Some(claiming_factory::UserDetails::try_deserialize(
+                    &mut account.data.as_ref(),
+                )?),
+                None => None,
+            };
+            user_details.push(details);
         }
-        Command::CreateClaiming {
-            merkle,
-            mint,
+    }
+
+    Ok(user_details)
+}
+
+/// Decodes events emitted by the program from transaction logs.
+fn parse_events<T: Discriminator + AnchorDeserialize>(logs: &[String]) -> Vec<T> {
+    logs.iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .filter_map(|data| base64::decode(data).ok())
+        .filter(|data| data.len() >= 8 && data[..8] == T::discriminator())
+        .filter_map(|data| T::deserialize(&mut &data[8..]).ok())
+        .collect()
+}
+
+fn parse_category(s: &str) -> Result<claiming_factory::DistributorCategory> {
+    use claiming_factory::DistributorCategory;
+
+    match s {
+        "ido" => Ok(DistributorCategory::Ido),
+        "airdrop" => Ok(DistributorCategory::Airdrop),
+        "team" => Ok(DistributorCategory::Team),
+        "advisors" => Ok(DistributorCategory::Advisors),
+        _ => Err(anyhow!("unknown distributor category: {}", s)),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn keys(slots: &[Option<Pubkey>]) -> Vec<String> {
+    slots.iter().flatten().map(Pubkey::to_string).collect()
+}
+
+/// JSON layouts of accounts, they are kept stable for scripts and dashboards
+/// regardless of the on-chain layout.
+#[derive(Serialize, Debug)]
+struct ConfigJson {
+    address: String,
+    owner: String,
+    admins: Vec<String>,
+    relayers: Vec<String>,
+    allowed_mints: Vec<String>,
+    creation_fee: u64,
+    version: u32,
+    account_version: u8,
+}
+
+impl ConfigJson {
+    fn new(address: &Pubkey, config: &claiming_factory::Config) -> Self {
+        Self {
+            address: address.to_string(),
+            owner: config.owner.to_string(),
+            admins: keys(&config.admins),
+            relayers: keys(&config.relayers),
+            allowed_mints: keys(&config.allowed_mints),
+            creation_fee: config.creation_fee,
+            version: config.version,
+            account_version: config.account_version,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct DistributorJson {
+    address: String,
+    merkle_index: u64,
+    merkle_root: String,
+    paused: bool,
+    stopped: bool,
+    frozen_at_ts: Option<u64>,
+    category: String,
+    mint: String,
+    mint_decimals: u8,
+    vault: String,
+    clawback_destination: String,
+    total_allocation: u64,
+    total_claimed: u64,
+    tickets_claimed: u64,
+    claimers: u64,
+    max_claim_per_tx: Option<u64>,
+    max_proof_len: Option<u8>,
+    position_nfts: bool,
+    ticket_signer: Option<String>,
+    gate_collection: Option<String>,
+    custodians: Vec<String>,
+    relative_schedule: bool,
+    activation_ts: u64,
+    accelerated_at_ts: Option<u64>,
+    schedule: Vec<PeriodJson>,
+    account_version: u8,
+}
+
+#[derive(Serialize, Debug)]
+struct PeriodJson {
+    start_ts: u64,
+    end_ts: u64,
+    /// Basis points of the whole allocation.
+    token_percentage: u64,
+    interval_sec: u64,
+    times: u64,
+    airdropped: bool,
+    completed: bool,
+    claimed: u64,
+    unlocks: Vec<UnlockJson>,
+}
+
+#[derive(Serialize, Debug)]
+struct UnlockJson {
+    ts: u64,
+    /// Basis points of the whole allocation unlocked at `ts`.
+    bps: f64,
+}
+
+impl DistributorJson {
+    fn new(
+        address: &Pubkey,
+        distributor: &claiming_factory::MerkleDistributor,
+        root: &claiming_factory::MerkleRoot,
+        stats: &claiming_factory::ClaimStats,
+    ) -> Self {
+        let schedule = distributor
+            .vesting
+            .schedule
+            .iter()
+            .enumerate()
+            .map(|(index, period)| PeriodJson {
+                start_ts: period.start_ts,
+                end_ts: period.start_ts + period.times * period.interval_sec,
+                token_percentage: period.token_percentage,
+                interval_sec: period.interval_sec,
+                times: period.times,
+                airdropped: period.airdropped,
+                completed: distributor
+                    .completed_periods
+                    .get(index)
+                    .copied()
+                    .unwrap_or(false),
+                claimed: stats.claimed_per_period.get(index).copied().unwrap_or(0),
+                unlocks: (1..=period.times)
+                    .map(|interval| UnlockJson {
+                        ts: period.start_ts + interval * period.interval_sec,
+                        bps: period.token_percentage as f64 / period.times as f64,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            address: address.to_string(),
+            merkle_index: root.merkle_index,
+            merkle_root: hex(&root.merkle_root),
+            paused: root.paused,
+            stopped: distributor.stopped,
+            frozen_at_ts: distributor.frozen_at_ts,
+            category: format!("{:?}", distributor.category).to_lowercase(),
+            mint: distributor.mint.to_string(),
+            mint_decimals: distributor.mint_decimals,
+            vault: distributor.vault.to_string(),
+            clawback_destination: distributor.clawback_destination.to_string(),
+            total_allocation: root.total_allocation,
+            total_claimed: stats.total_claimed,
+            tickets_claimed: stats.tickets_claimed,
+            claimers: stats.claimers,
+            max_claim_per_tx: distributor.max_claim_per_tx,
+            max_proof_len: root.max_proof_len,
+            position_nfts: distributor.position_nfts,
+            ticket_signer: distributor.ticket_signer.as_ref().map(Pubkey::to_string),
+            gate_collection: distributor.gate_collection.as_ref().map(Pubkey::to_string),
+            custodians: keys(&distributor.custodians),
+            relative_schedule: distributor.relative_schedule,
+            activation_ts: distributor.activation_ts,
+            accelerated_at_ts: distributor.vesting.accelerated_at_ts,
             schedule,
-        } => {
-            let merkle: MerkleData = serde_json::from_str(&merkle)?;
-            println!("{:?}", merkle);
+            account_version: distributor.account_version,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct UserDetailsJson {
+    address: String,
+    user: String,
+    claimed_amount: u64,
+    pending_amount: u64,
+    last_claimed_at_ts: u64,
+    first_claimed_at_ts: u64,
+    claim_count: u64,
+    account_version: u8,
+}
+
+impl UserDetailsJson {
+    fn new(address: &Pubkey, user: &Pubkey, user_details: &claiming_factory::UserDetails) -> Self {
+        Self {
+            address: address.to_string(),
+            user: user.to_string(),
+            claimed_amount: user_details.claimed_amount,
+            pending_amount: user_details.pending_amount,
+            last_claimed_at_ts: user_details.last_claimed_at_ts,
+            first_claimed_at_ts: user_details.first_claimed_at_ts,
+            claim_count: user_details.claim_count,
+            account_version: user_details.account_version,
+        }
+    }
+}
+
+/// Wallets a batch command has processed, appended to a file as `wallet,signature` lines
+/// right after their transaction lands, so a rerun after a crash skips them. The first line
+/// names the distributor and the checkpoint of another one is refused.
+struct Checkpoint {
+    file: Option<std::fs::File>,
+    done: std::collections::HashMap<Pubkey, Option<Signature>>,
+}
+
+impl Checkpoint {
+    /// Without a path nothing is recorded.
+    fn open(path: Option<&str>, distributor: &Pubkey) -> Result<Self> {
+        let mut done = std::collections::HashMap::new();
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Self { file: None, done }),
+        };
 
-            let file = std::fs::read(schedule)?;
-            let mut rdr = csv::ReaderBuilder::new()
-                .has_headers(false)
-                .from_reader(&*file);
-            let mut schedule = Vec::new();
-            for result in rdr.records() {
-                let record = result?;
-
-                let start_ts = record
-                    .get(0)
-                    .ok_or(anyhow!(
-                        "missing period start value (should be unix timestamp in seconds)"
-                    ))?
-                    .parse::<u64>()?;
-
-                let token_percentage = record
-                    .get(1)
-                    .ok_or(anyhow!(
-                        "missing token percentage value for period (in basis points)"
-                    ))?
-                    .parse::<u64>()?;
-
-                let interval_sec = record
-                    .get(2)
-                    .ok_or(anyhow!("missing interval seconds for period"))?
-                    .parse::<u64>()?;
-
-                let times = record
-                    .get(3)
-                    .ok_or(anyhow!("missing interval times for periods"))?
-                    .parse::<u64>()?;
-
-                let airdropped = record
-                    .get(4)
-                    .ok_or(anyhow!("missing airdropped flag"))?
-                    .parse::<bool>()?;
-
-                schedule.push(claiming_factory::Period {
-                    start_ts,
-                    token_percentage,
-                    interval_sec,
-                    times,
-                    airdropped,
-                });
+        let exists = std::path::Path::new(path).exists();
+        if exists {
+            for line in std::fs::read_to_string(path)?.lines().map(str::trim) {
+                if line.is_empty() {
+                    continue;
+                }
+                if let Some(owner) = line.strip_prefix("# ") {
+                    if owner != distributor.to_string() {
+                        return Err(anyhow!(
+                            "checkpoint {} belongs to distributor {}",
+                            path,
+                            owner
+                        ));
+                    }
+                    continue;
+                }
+
+                // files of `--progress` have only wallets
+                let mut fields = line.split(',');
+                let wallet = fields.next().unwrap_or_default().parse()?;
+                let signature = fields
+                    .next()
+                    .filter(|signature| !signature.is_empty())
+                    .map(str::parse)
+                    .transpose()?;
+                done.insert(wallet, signature);
             }
+        }
 
-            let (config, _bump) = Pubkey::find_program_address(&["config".as_ref()], &client.id());
-            println!("Config address: {}", config);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        if !exists {
+            writeln!(file, "# {}", distributor)?;
+        }
 
-            let distributor = Keypair::new();
-            println!("Distributor address: {}", distributor.pubkey());
+        Ok(Self {
+            file: Some(file),
+            done,
+        })
+    }
 
-            let vault = Keypair::new();
+    fn contains(&self, wallet: &Pubkey) -> bool {
+        self.done.contains_key(wallet)
+    }
 
-            let (vault_authority, vault_bump) =
-                Pubkey::find_program_address(&[distributor.pubkey().as_ref()], &client.id());
+    fn signature(&self, wallet: &Pubkey) -> Option<Signature> {
+        self.done.get(wallet).copied().flatten()
+    }
 
-            let rent = client
-                .rpc()
-                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+    fn len(&self) -> usize {
+        self.done.len()
+    }
 
-            let create_token_account_ix = solana_sdk::system_instruction::create_account(
-                &payer.pubkey(),
-                &vault.pubkey(),
-                rent,
-                spl_token::state::Account::LEN as u64,
-                &spl_token::ID,
-            );
+    /// Synced to disk before returning, the next transaction isn't sent until then.
+    fn record(&mut self, wallets: &[Pubkey], signature: Signature) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            for wallet in wallets {
+                writeln!(file, "{},{}", wallet, signature)?;
+            }
+            file.sync_data()?;
+        }
+        for wallet in wallets {
+            self.done.insert(*wallet, Some(signature));
+        }
 
-            let init_token_account_ix = spl_token::instruction::initialize_account(
-                &spl_token::ID,
-                &vault.pubkey(),
-                &mint,
-                &vault_authority,
-            )?;
+        Ok(())
+    }
+}
+
+fn format_tge(distributor: &claiming_factory::MerkleDistributor) -> String {
+    match (distributor.relative_schedule, distributor.activation_ts) {
+        (false, _) => "absolute schedule".to_string(),
+        (true, 0) => "not activated".to_string(),
+        (true, ts) => format!("activated at {}", format_ts(ts, false)),
+    }
+}
+
+fn format_ts(ts: u64, relative: bool) -> String {
+    if relative {
+        return format!("TGE + {}", format_duration(ts));
+    }
+
+    match Utc.timestamp_opt(ts as i64, 0).single() {
+        Some(date) => date.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => ts.to_string(),
+    }
+}
 
-            let r = client
-                .request()
-                .instruction(create_token_account_ix)
-                .instruction(init_token_account_ix)
-                .accounts(claiming_factory::accounts::Initialize {
-                    config,
-                    admin_or_owner: payer.pubkey(),
-                    distributor: distributor.pubkey(),
-                    vault_authority,
-                    vault: vault.pubkey(),
-                    system_program: solana_sdk::system_program::id(),
-                })
-                .args(claiming_factory::instruction::Initialize {
-                    args: claiming_factory::InitializeArgs {
-                        vault_bump,
-                        merkle_root: merkle.data,
-                        schedule,
-                    },
-                })
-                .signer(payer.as_ref())
-                .signer(&distributor)
-                .signer(&vault)
-                .send()?;
-
-            println!("Result:\n{}", r);
+/// Amount in tokens, `amount` is in base units of a mint with `decimals`.
+fn format_ui_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+
+    let unit = 10u128.pow(decimals as u32);
+    let amount = amount as u128;
+    let fraction = format!("{:0width$}", amount % unit, width = decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        (amount / unit).to_string()
+    } else {
+        format!("{}.{}", amount / unit, fraction)
+    }
+}
+
+fn format_percentage(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        return "0%".to_string();
+    }
+    format!("{:.2}%", part as f64 * 100.0 / whole as f64)
+}
+
+/// Time left rounded down to minutes, e.g. `3d 4h 15m`.
+fn format_countdown(sec: u64) -> String {
+    let (days, hours, minutes) = (sec / 86400, sec % 86400 / 3600, sec % 3600 / 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+fn progress_bar(done: u64, total: u64) -> String {
+    const WIDTH: u64 = 20;
+    let filled = if total == 0 { 0 } else { done * WIDTH / total };
+    format!(
+        "[{}{}]",
+        "#".repeat(filled as usize),
+        "-".repeat((WIDTH - filled) as usize)
+    )
+}
+
+fn format_duration(sec: u64) -> String {
+    match sec {
+        0 => "0s".to_string(),
+        _ if sec % 86400 == 0 => format!("{}d", sec / 86400),
+        _ if sec % 3600 == 0 => format!("{}h", sec / 3600),
+        _ => format!("{}s", sec),
+    }
+}
+
+/// Program logs are printed to stdout off-chain, which clutters the output
+/// of commands computing claimable amounts locally.
+struct QuietSyscallStubs;
+
+impl SyscallStubs for QuietSyscallStubs {
+    fn sol_log(&self, _message: &str) {}
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::from_args();
+    set_syscall_stubs(Box::new(QuietSyscallStubs));
+
+    // offline commands don't need the payer
+    if let Command::CheckSchedule { schedule, relative } = &opts.cmd {
+        return schedule::check_schedule(schedule, *relative);
+    }
+
+    if let Command::SummarizeAllocations {
+        allocations,
+        expected_total,
+    } = &opts.cmd
+    {
+        return merkle::summarize_allocations(allocations, *expected_total);
+    }
+
+    // every deployment of the manifest has its own connection
+    if let Command::DeployClaiming { manifest } = &opts.cmd {
+        return create::deploy_claiming(&opts, manifest);
+    }
+
+    if let Command::GenerateMerkle {
+        allocations,
+        output,
+        evm_output,
+        shards,
+    } = &opts.cmd
+    {
+        return merkle::generate_merkle(allocations, output, evm_output.as_deref(), *shards);
+    }
+
+    if let Command::GetProof { wallet, tree } = &opts.cmd {
+        return merkle::get_proof(wallet, tree);
+    }
+
+    let (cluster, program_id, payer_path) = opts.connection()?;
+
+    // nothing is signed by the payer while printing transactions
+    let payer = if opts.tx.print_tx {
+        Keypair::new()
+    } else {
+        read_keypair(&payer_path)?
+    };
+    let payer = Rc::new(payer);
+    let authority = opts.tx.authority.unwrap_or_else(|| payer.pubkey());
+
+    let client = Client::new_with_options(
+        cluster.clone(),
+        payer.clone(),
+        CommitmentConfig {
+            commitment: opts.tx.commitment,
+        },
+    );
+    let client = client.program(program_id);
+    let sender = Sender {
+        client: &client,
+        opts: &opts.tx,
+        payer: payer.as_ref(),
+        fee_payer: authority,
+        cluster: &cluster,
+        estimated: Default::default(),
+    };
+
+    match opts.cmd {
+        Command::ProgramInfo {} => program::program_info(&sender)?,
+        Command::InitConfig {} => config::init_config(&sender)?,
+        Command::ShowConfig {} => config::show_config(&sender, opts.json)?,
+        Command::InitTreasury {} => config::init_treasury(&sender)?,
+        Command::SetVersion {} => config::set_version(&sender)?,
+        Command::SetCreationFee { lamports } => config::set_creation_fee(&sender, lamports)?,
+        Command::WithdrawFees { lamports, target } => {
+            config::withdraw_fees(&sender, lamports, target)?
         }
+        Command::AddAdmin { admin } => config::add_admin(&sender, admin)?,
+        Command::CreateClaiming {
+            interactive: true,
+            keypair_vault,
+            ..
+        } => create::create_claiming_interactive(&sender, keypair_vault)?,
+        Command::CreateClaiming {
+            interactive: false,
+            merkle,
+            mint,
+            schedule,
+            position_nfts,
+            max_claim_per_tx,
+            category,
+            total_allocation,
+            relative_schedule,
+            max_proof_len,
+            clawback_destination,
+            keypair_vault,
+        } => {
+            let merkle: MerkleData = serde_json::from_str(&merkle.unwrap())?;
+            println!("{:?}", merkle);
+
+            create::create_claiming(
+                &sender,
+                CreateClaimingArgs {
+                    merkle_root: merkle.data,
+                    mint: mint.unwrap(),
+                    schedule: read_schedule(&schedule.unwrap())?,
+                    position_nfts,
+                    max_claim_per_tx,
+                    category,
+                    total_allocation: total_allocation.unwrap(),
+                    relative_schedule,
+                    max_proof_len,
+                    clawback_destination: clawback_destination.unwrap(),
+                    keypair_vault,
+                },
+            )?;
+        }
+        Command::UpdateRoot {
+            claiming,
+            merkle,
+            allocations,
+            total_allocation,
+            max_proof_len,
+            unpause,
+        } => distributor::update_root(
+            &sender,
+            claiming,
+            merkle,
+            allocations,
+            total_allocation,
+            max_proof_len,
+            unpause,
+        )?,
+        Command::ShowClaimable {
+            claiming,
+            user,
+            amount,
+        } => user::show_claimable(&sender, claiming, user, amount)?,
+        Command::Simulate {
+            claiming,
+            wallet,
+            amount,
+        } => user::simulate(&sender, claiming, wallet, amount)?,
+        Command::InitUserDetails {
+            claiming,
+            allocations,
+            batch_size,
+            tps,
+            concurrency,
+            checkpoint,
+            estimate,
+        } => user::init_user_details(
+            &sender,
+            InitUserDetailsArgs {
+                claiming,
+                allocations,
+                batch_size,
+                tps,
+                concurrency,
+                checkpoint,
+                estimate,
+            },
+        )?,
+        Command::GetProof { .. }
+        | Command::CheckSchedule { .. }
+        | Command::SummarizeAllocations { .. }
+        | Command::DeployClaiming { .. }
+        | Command::GenerateMerkle { .. } => unreachable!(),
+        Command::VerifyRoot {
+            distributor,
+            allocations,
+        } => merkle::verify_root(&sender, distributor, allocations)?,
         Command::ShowClaiming { claiming } => {
-            let claiming: claiming_factory::MerkleDistributor = client.account(claiming)?;
-            println!("{:#?}", claiming);
+            distributor::show_claiming(&sender, claiming, opts.json)?
+        }
+        Command::Derive { claiming, user } => distributor::derive(&sender, claiming, user)?,
+        Command::Report { distributor } => distributor::report(&sender, distributor)?,
+        Command::ExportClaims {
+            distributor,
+            since,
+            output,
+        } => audit::export_claims(&sender, distributor, since, output)?,
+        Command::DiffSchedules { a, b } => schedule::diff_schedules(&sender, a, b)?,
+        Command::ExportUsers {
+            distributor,
+            allocations,
+            output,
+        } => user::export_users(&sender, distributor, allocations, output)?,
+        Command::Airdrop {
+            distributor,
+            recipients,
+            batch_size,
+            output,
+            checkpoint,
+        } => airdrop::airdrop(
+            &sender,
+            distributor,
+            recipients,
+            batch_size,
+            output,
+            checkpoint,
+        )?,
+        Command::Fund {
+            distributor,
+            amount,
+        } => distributor::fund(&sender, distributor, amount)?,
+        Command::Notify {
+            webhook_url,
+            distributor,
+            claim_threshold,
+            interval,
+        } => notify::notify(&sender, webhook_url, distributor, claim_threshold, interval)?,
+        Command::Nonce(NonceCommand::Create { authority }) => {
+            commands::nonce::create(&sender, authority)?
+        }
+        Command::Nonce(NonceCommand::Show { address }) => commands::nonce::show(&sender, address)?,
+        Command::Nonce(NonceCommand::Advance { address }) => {
+            commands::nonce::advance(&sender, address)?
         }
+        Command::Program(ProgramCommand::Deploy {
+            path,
+            program_keypair,
+            max_len,
+            tps,
+            verify_hash,
+        }) => program::deploy(&sender, path, program_keypair, max_len, tps, verify_hash)?,
+        Command::Program(ProgramCommand::Upgrade {
+            path,
+            buffer_authority,
+            tps,
+            verify_hash,
+        }) => program::upgrade(&sender, path, buffer_authority, tps, verify_hash)?,
+        Command::Metrics(MetricsCommand::Serve {
+            distributor,
+            port,
+            interval,
+        }) => metrics::serve(&sender, distributor, port, interval)?,
+        Command::Surplus {
+            distributor,
+            allocations,
+        } => audit::surplus(&sender, distributor, allocations)?,
+        Command::Reconcile {
+            distributor,
+            allocations,
+        } => audit::reconcile(&sender, distributor, allocations)?,
+        Command::ShowUserDetails { claiming, user } => {
+            user::show_user_details(&sender, claiming, user, opts.json)?
+        }
+        Command::ActivateTge {
+            claiming,
+            activation_ts,
+        } => distributor::activate_tge(&sender, claiming, activation_ts)?,
+    }
+
+    if opts.tx.estimate {
+        let (transactions, compute_units, fee) = sender.estimated.get();
+        println!(
+            "Estimated {} transactions: {} compute units, {} SOL of fees",
+            transactions,
+            compute_units,
+            lamports_to_sol(fee)
+        );
     }
 
     Ok(())
@@ -0,0 +1,569 @@
+use anchor_client::{
+    solana_client::rpc_config::{
+        RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig,
+        RpcSimulateTransactionConfig,
+    },
+    solana_sdk::{
+        account::Account,
+        clock::MAX_PROCESSING_AGE,
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        hash::Hash,
+        instruction::Instruction,
+        message::Message,
+        native_token::lamports_to_sol,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{Transaction, TransactionError},
+    },
+    Program, RequestBuilder,
+};
+use anyhow::{anyhow, Result};
+use solana_account_decoder::UiAccountEncoding;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::{read_nonce, TxOpts};
+
+/// Common path of all transactions sent by the CLI.
+pub(crate) struct Sender<'a> {
+    pub(crate) client: &'a Program,
+    pub(crate) opts: &'a TxOpts,
+    pub(crate) payer: &'a Keypair,
+    pub(crate) fee_payer: Pubkey,
+    pub(crate) cluster: &'a anchor_client::Cluster,
+    /// Transactions, compute units and lamports of fees estimated so far.
+    pub(crate) estimated: std::cell::Cell<(u64, u64, u64)>,
+}
+
+impl Sender<'_> {
+    /// Sends the transaction or only prints or simulates it, depending on the options.
+    /// The signature is returned only for sent transactions.
+    pub(crate) fn send(&self, request: RequestBuilder) -> Result<Option<Signature>> {
+        self.send_with_signers(request, &[])
+    }
+
+    /// Same as `send`, the transaction is signed by the payer and the extra signers.
+    pub(crate) fn send_with_signers(
+        &self,
+        request: RequestBuilder,
+        signers: &[&dyn Signer],
+    ) -> Result<Option<Signature>> {
+        if self.opts.print_tx {
+            let instructions = request.instructions()?;
+            // without a nonce the signer sets a recent blockhash
+            let blockhash = match self.opts.nonce_account {
+                Some(_) => self.blockhash()?.0,
+                None => Hash::default(),
+            };
+            let message = self.message(&instructions, blockhash).serialize();
+
+            println!("Base64 message:\n{}", base64::encode(&message));
+            println!("Base58 message:\n{}", bs58::encode(&message).into_string());
+
+            return Ok(None);
+        }
+
+        if self.opts.dry_run {
+            self.simulate(&request.instructions()?)?;
+            return Ok(None);
+        }
+
+        if self.opts.estimate {
+            self.estimate(&request.instructions()?)?;
+            return Ok(None);
+        }
+
+        let mut all_signers: Vec<&dyn Signer> = vec![self.payer];
+        all_signers.extend_from_slice(signers);
+
+        let r = self
+            .send_and_confirm_all(&[request.instructions()?], &all_signers, None)
+            .remove(0)?;
+        println!("Result:\n{}", r);
+
+        Ok(Some(r))
+    }
+
+    /// Blockhash stored in the nonce account if it's set, otherwise a recent one,
+    /// with the block height after which a transaction which didn't land is re-sent.
+    fn blockhash(&self) -> Result<(Hash, u64)> {
+        let rpc = self.client.rpc();
+        match &self.opts.nonce_account {
+            // the nonce doesn't expire, but only one of the re-sent transactions can land
+            Some(nonce_account) => Ok((
+                read_nonce(&rpc, nonce_account)?.blockhash,
+                rpc.get_block_height()? + MAX_PROCESSING_AGE as u64,
+            )),
+            None => Ok(rpc.get_latest_blockhash_with_commitment(CommitmentConfig::finalized())?),
+        }
+    }
+
+    /// Message paid by the fee payer, advancing the nonce first if it's set.
+    fn message(&self, instructions: &[Instruction], blockhash: Hash) -> Message {
+        let mut message = match &self.opts.nonce_account {
+            Some(nonce_account) => Message::new_with_nonce(
+                instructions.to_vec(),
+                Some(&self.fee_payer),
+                nonce_account,
+                &self.opts.nonce_authority.unwrap_or(self.fee_payer),
+            ),
+            None => Message::new(instructions, Some(&self.fee_payer)),
+        };
+        message.recent_blockhash = blockhash;
+        message
+    }
+
+    pub(crate) fn print_tx_links(&self, signature: &Signature) {
+        print_explorer_links(
+            self.cluster,
+            &format!("tx/{}", signature),
+            &format!("tx/{}", signature),
+        );
+    }
+
+    pub(crate) fn print_address_links(&self, address: &Pubkey) {
+        print_explorer_links(
+            self.cluster,
+            &format!("address/{}", address),
+            &format!("account/{}", address),
+        );
+    }
+
+    /// Sends the transactions to the cluster, at most `tps` per second if given, signed by the
+    /// payer. Results are in the order of the requests.
+    pub(crate) fn send_all(
+        &self,
+        requests: Vec<RequestBuilder>,
+        tps: Option<f64>,
+    ) -> Result<Vec<Result<Option<Signature>>>> {
+        if self.opts.print_tx || self.opts.dry_run || self.opts.estimate {
+            return Ok(requests
+                .into_iter()
+                .map(|request| self.send(request))
+                .collect());
+        }
+
+        let transactions = requests
+            .iter()
+            .map(RequestBuilder::instructions)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self
+            .send_and_confirm_all(&transactions, &[self.payer], tps)
+            .into_iter()
+            .map(|result| result.map(Some))
+            .collect())
+    }
+
+    /// Sends all transactions with a fresh blockhash and polls their statuses until they're
+    /// finalized. Transient RPC errors are retried with exponential backoff. A sent transaction
+    /// is rebroadcast until it lands or its blockhash expires, only then it's signed again with
+    /// a new blockhash, so the same instructions can't land twice.
+    pub(crate) fn send_and_confirm_all(
+        &self,
+        transactions: &[Vec<Instruction>],
+        signers: &[&dyn Signer],
+        tps: Option<f64>,
+    ) -> Vec<Result<Signature>> {
+        let rpc = self.client.rpc();
+        let send_config = RpcSendTransactionConfig {
+            skip_preflight: self.opts.skip_preflight,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        };
+        // rebroadcasts don't need another simulation, the status tells whether they landed
+        let rebroadcast_config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..send_config
+        };
+        let max_retries = self.opts.max_retries;
+
+        // every transaction advances the nonce, so the next one would be rejected
+        if self.opts.nonce_account.is_some() && transactions.len() > 1 {
+            return transactions
+                .iter()
+                .map(|_| {
+                    Err(anyhow!(
+                        "a durable nonce signs one transaction at a time, \
+                         send batches without --nonce-account"
+                    ))
+                })
+                .collect();
+        }
+
+        let mut results: Vec<Option<Result<Signature>>> =
+            transactions.iter().map(|_| None).collect();
+        let mut attempts = vec![0; transactions.len()];
+        let mut retry =
+            |results: &mut [Option<Result<Signature>>], index: usize, err: anyhow::Error| {
+                attempts[index] += 1;
+                if attempts[index] > max_retries {
+                    results[index] = Some(Err(err));
+                }
+            };
+
+        let mut backoff = 0;
+        let mut last_sent: Option<std::time::Instant> = None;
+        loop {
+            let pending: Vec<usize> = (0..transactions.len())
+                .filter(|index| results[*index].is_none())
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+
+            let mut transient = false;
+            match self.blockhash() {
+                Err(err) => {
+                    println!("Failed to get blockhash: {}", err);
+                    transient = true;
+                    for index in pending {
+                        retry(&mut results, index, anyhow!("{}", err));
+                    }
+                }
+                Ok((blockhash, last_valid_block_height)) => {
+                    let mut in_flight = Vec::new();
+                    for index in pending {
+                        if let (Some(tps), Some(sent)) = (tps, last_sent) {
+                            let interval = std::time::Duration::from_secs_f64(1.0 / tps);
+                            if let Some(wait) = interval.checked_sub(sent.elapsed()) {
+                                std::thread::sleep(wait);
+                            }
+                        }
+                        last_sent = Some(std::time::Instant::now());
+
+                        let mut tx = Transaction::new_unsigned(
+                            self.message(&transactions[index], blockhash),
+                        );
+                        if let Err(err) = tx.try_sign(&signers.to_vec(), blockhash) {
+                            results[index] = Some(Err(err.into()));
+                            continue;
+                        }
+
+                        match rpc.send_transaction_with_config(&tx, send_config) {
+                            Ok(signature) => {
+                                println!(
+                                    "Sent {}, waiting for {:?} commitment",
+                                    signature, self.opts.commitment
+                                );
+                                in_flight.push((index, tx));
+                            }
+                            // the program rejected it, resending won't help
+                            Err(err) => match err.get_transaction_error() {
+                                Some(tx_err) => {
+                                    results[index] = Some(Err(SendError::Failed(tx_err).into()))
+                                }
+                                // it may have reached the cluster anyway, so it's not signed
+                                // again before its blockhash expires
+                                None => {
+                                    println!(
+                                        "Send of {} failed: {}, rebroadcasting it",
+                                        tx.signatures[0], err
+                                    );
+                                    transient = true;
+                                    in_flight.push((index, tx));
+                                }
+                            },
+                        }
+                    }
+
+                    let mut poll_failures = 0;
+                    while !in_flight.is_empty() {
+                        std::thread::sleep(std::time::Duration::from_secs(1));
+
+                        // the height is read first, so a missing status after it means expired
+                        let statuses = rpc.get_block_height().and_then(|block_height| {
+                            let signatures: Vec<Signature> = in_flight
+                                .iter()
+                                .map(|(_index, tx)| tx.signatures[0])
+                                .collect();
+                            let mut statuses = Vec::with_capacity(signatures.len());
+                            // RPC limits the number of signatures per request
+                            for chunk in signatures.chunks(256) {
+                                statuses.extend(rpc.get_signature_statuses(chunk)?.value);
+                            }
+                            Ok((block_height, statuses))
+                        });
+                        let (block_height, statuses) = match statuses {
+                            Ok(statuses) => statuses,
+                            // signing them again could land them twice, so they're given up on
+                            Err(err) => {
+                                poll_failures += 1;
+                                if poll_failures > max_retries {
+                                    for (index, tx) in in_flight.drain(..) {
+                                        results[index] = Some(Err(anyhow!(
+                                            "status of {} is unknown, it may still land: {}",
+                                            tx.signatures[0],
+                                            err
+                                        )));
+                                    }
+                                }
+                                continue;
+                            }
+                        };
+
+                        let mut still_in_flight = Vec::new();
+                        for ((index, tx), status) in in_flight.into_iter().zip(statuses) {
+                            let signature = tx.signatures[0];
+                            let status = match status {
+                                // recent statuses only, the whole history is checked before
+                                // the transaction is taken as expired
+                                None if block_height > last_valid_block_height => {
+                                    match rpc.get_signature_statuses_with_history(&[signature]) {
+                                        Ok(response) => response.value.into_iter().next().flatten(),
+                                        Err(_) => {
+                                            still_in_flight.push((index, tx));
+                                            continue;
+                                        }
+                                    }
+                                }
+                                status => status,
+                            };
+                            match status {
+                                Some(status) => {
+                                    if let Some(err) = &status.err {
+                                        results[index] =
+                                            Some(Err(SendError::Failed(err.clone()).into()));
+                                    } else if status.satisfies_commitment(CommitmentConfig {
+                                        commitment: self.opts.commitment,
+                                    }) {
+                                        self.print_tx_links(&signature);
+                                        results[index] = Some(Ok(signature));
+                                    } else {
+                                        still_in_flight.push((index, tx));
+                                    }
+                                }
+                                None if block_height > last_valid_block_height => {
+                                    println!("Blockhash of {} expired", signature);
+                                    retry(
+                                        &mut results,
+                                        index,
+                                        anyhow!("transaction didn't land in time"),
+                                    );
+                                }
+                                // the same transaction again, until it lands or expires
+                                None => {
+                                    let _ =
+                                        rpc.send_transaction_with_config(&tx, rebroadcast_config);
+                                    still_in_flight.push((index, tx));
+                                }
+                            }
+                        }
+                        in_flight = still_in_flight;
+                    }
+                }
+            }
+
+            if transient {
+                let delay = std::time::Duration::from_millis(500 << backoff.min(6));
+                println!("Retrying in {:?}", delay);
+                std::thread::sleep(delay);
+                backoff += 1;
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every transaction has a result"))
+            .collect()
+    }
+
+    /// Prints compute units consumed by every instruction and the fee of the transaction.
+    fn estimate(&self, instructions: &[Instruction]) -> Result<()> {
+        let rpc = self.client.rpc();
+
+        let mut message = Message::new(instructions, Some(&self.fee_payer));
+        message.recent_blockhash = rpc.get_latest_blockhash()?;
+        let result = rpc
+            .simulate_transaction_with_config(
+                &Transaction::new_unsigned(message.clone()),
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )?
+            .value;
+        let logs = result.logs.unwrap_or_default();
+        if let Some(err) = result.err {
+            for log in &logs {
+                println!("  {}", log);
+            }
+            return Err(anyhow!("simulation failed: {}", err));
+        }
+
+        // consumed units of inner instructions are included in the top level ones,
+        // e.g. "Program <id> invoke [1]", "Program <id> consumed 12345 of 200000 compute units"
+        let mut consumed = vec![(0, 0); instructions.len()];
+        let mut depth = 0;
+        let mut index = 0;
+        for log in &logs {
+            let log = match log.strip_prefix("Program ") {
+                Some(log) => log,
+                None => continue,
+            };
+            if log.contains(" invoke [") {
+                depth += 1;
+                if depth == 1 {
+                    index += 1;
+                }
+            } else if log.ends_with(" success") || log.contains(" failed: ") {
+                depth -= 1;
+            } else if let Some(units) = log.split(" consumed ").nth(1) {
+                let mut units = units.split(' ');
+                if let (1, Some(used), Some("of"), Some(limit)) =
+                    (depth, units.next(), units.next(), units.next())
+                {
+                    consumed[index - 1] = (used.parse::<u64>()?, limit.parse::<u64>()?);
+                }
+            }
+        }
+
+        for (instruction, (used, limit)) in instructions.iter().zip(&consumed) {
+            println!(
+                "  {}: {} CU{}",
+                instruction.program_id,
+                used,
+                // builtin programs don't report units, so there is no limit for them
+                if *limit > 0 && used * 10 >= limit * 8 {
+                    format!(", approaching the limit of {}", limit)
+                } else {
+                    String::new()
+                }
+            );
+        }
+
+        let compute_units: u64 = consumed.iter().map(|(used, _limit)| used).sum();
+        let fee =
+            rpc.get_fee_for_message(&message)? + compute_units * self.opts.priority_fee / 1_000_000;
+        println!(
+            "Compute units: {}, fee: {} SOL",
+            compute_units,
+            lamports_to_sol(fee)
+        );
+
+        let (transactions, total_units, total_fee) = self.estimated.get();
+        self.estimated.set((
+            transactions + 1,
+            total_units + compute_units,
+            total_fee + fee,
+        ));
+
+        Ok(())
+    }
+
+    /// Prints logs, consumed compute units and changes of writable accounts.
+    fn simulate(&self, instructions: &[Instruction]) -> Result<()> {
+        let rpc = self.client.rpc();
+
+        let mut message = Message::new(instructions, Some(&self.fee_payer));
+        message.recent_blockhash = rpc.get_latest_blockhash()?;
+        let writable: Vec<Pubkey> = message
+            .account_keys
+            .iter()
+            .enumerate()
+            .filter(|(index, _key)| message.is_writable(*index))
+            .map(|(_index, key)| *key)
+            .collect();
+        let before = rpc.get_multiple_accounts(&writable)?;
+
+        let result = rpc
+            .simulate_transaction_with_config(
+                &Transaction::new_unsigned(message),
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: writable.iter().map(Pubkey::to_string).collect(),
+                    }),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )?
+            .value;
+
+        println!(
+            "Simulation: {}",
+            match &result.err {
+                Some(err) => format!("failed, {}", err),
+                None => "succeeded".to_string(),
+            }
+        );
+
+        let logs = result.logs.unwrap_or_default();
+        for log in &logs {
+            println!("  {}", log);
+        }
+        // e.g. "Program <id> consumed 12345 of 200000 compute units"
+        let compute_units: u64 = logs
+            .iter()
+            .filter_map(|log| log.split(" consumed ").nth(1))
+            .filter_map(|consumed| consumed.split(' ').next()?.parse::<u64>().ok())
+            .sum();
+        println!("Compute units: {}", compute_units);
+
+        let after = result.accounts.unwrap_or_default();
+        for ((key, before), after) in writable.iter().zip(before).zip(after) {
+            let after = after.and_then(|account| account.decode::<Account>());
+            match (before, after) {
+                (Some(before), Some(after)) => println!(
+                    "{}: lamports {} -> {}, data {}",
+                    key,
+                    before.lamports,
+                    after.lamports,
+                    if before.data == after.data {
+                        "unchanged"
+                    } else {
+                        "changed"
+                    }
+                ),
+                (None, Some(after)) => println!(
+                    "{}: created with {} lamports and {} bytes",
+                    key,
+                    after.lamports,
+                    after.data.len()
+                ),
+                (Some(_before), None) => println!("{}: closed", key),
+                (None, None) => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints links to the Solana explorer and Solscan, which knows only public clusters.
+fn print_explorer_links(cluster: &anchor_client::Cluster, explorer_path: &str, solscan_path: &str) {
+    use anchor_client::Cluster;
+
+    let query = match cluster {
+        Cluster::Mainnet => String::new(),
+        Cluster::Devnet => "?cluster=devnet".to_string(),
+        Cluster::Testnet => "?cluster=testnet".to_string(),
+        cluster => format!(
+            "?cluster=custom&customUrl={}",
+            cluster.url().replace(':', "%3A").replace('/', "%2F")
+        ),
+    };
+
+    println!("  https://explorer.solana.com/{}{}", explorer_path, query);
+    if matches!(
+        cluster,
+        Cluster::Mainnet | Cluster::Devnet | Cluster::Testnet
+    ) {
+        println!("  https://solscan.io/{}{}", solscan_path, query);
+    }
+}
+
+/// Error which isn't worth retrying, the transaction itself failed.
+#[derive(Debug)]
+enum SendError {
+    Failed(TransactionError),
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SendError::Failed(err) => write!(f, "transaction failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
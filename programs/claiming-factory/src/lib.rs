@@ -3,11 +3,18 @@ use std::ops::DerefMut;
 use anchor_lang::{
     prelude::*,
     solana_program::{
-        keccak,
+        ed25519_program, keccak,
         log::{sol_log, sol_log_64},
+        program::{invoke, set_return_data},
+        system_instruction,
+        sysvar::{
+            self,
+            instructions::{load_current_index_checked, load_instruction_at_checked},
+        },
     },
+    Discriminator,
 };
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
 use rust_decimal::{
     prelude::{FromPrimitive, ToPrimitive},
     Decimal,
@@ -15,6 +22,28 @@ use rust_decimal::{
 
 declare_id!("6cJU4mUJe1fKXzvvbZjz72M3d5aQXMmRV2jeQerkFw5b");
 
+/// Layout version written into every account of the program, bumped whenever an account
+/// layout changes. Accounts created before versioning don't have it until they are grown
+/// by a migration, which reads it as zero.
+pub const ACCOUNT_VERSION: u8 = 1;
+
+/// Rejects accounts written by a newer layout than this program understands.
+fn check_account_version(account_version: u8) -> Result<()> {
+    require!(
+        account_version <= ACCOUNT_VERSION,
+        UnsupportedAccountVersion
+    );
+
+    Ok(())
+}
+
+/// Metaplex token metadata program.
+mod mpl_token_metadata {
+    use super::*;
+
+    declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
 #[error_code]
 pub enum ErrorCode {
     MaxAdmins,
@@ -33,28 +62,260 @@ pub enum ErrorCode {
     IntegerOverflow,
     VestingAlreadyStarted,
     NothingToClaim,
+    PositionNftsDisabled,
+    PositionAlreadyMinted,
+    PositionNftRequired,
+    InvalidPosition,
+    NotPositionHolder,
+    InvalidMint,
+    ForeignTargetWallet,
+    TicketsDisabled,
+    TicketExpired,
+    InvalidTicketSignature,
+    MaxRelayers,
+    RelayerNotFound,
+    NotRelayer,
+    InvalidHoldback,
+    HoldbackVaultMissing,
+    InvalidHoldbackVault,
+    HoldbackLocked,
+    NothingToRelease,
+    VestingAlreadyAccelerated,
+    MaxAllowedMints,
+    AllowedMintNotFound,
+    MintNotAllowed,
+    InsufficientTreasuryBalance,
+    InvalidVault,
+    AllocationExceedsVault,
+    TotalAllocationExceeded,
+    VaultFrozen,
+    VaultAlreadyFrozen,
+    VaultNotFrozen,
+    FreezeTimelockActive,
+    CannotRecoverDistributionMint,
+    MaxCustodians,
+    CustodianNotFound,
+    WithdrawalScheduleSet,
+    WithdrawalLocked,
+    InvalidPeriodIndex,
+    PeriodNotEnded,
+    NotRelativeSchedule,
+    TgeNotActivated,
+    TgeAlreadyActivated,
+    InvalidUserDetails,
+    ProofTooLong,
+    GateNftMissing,
+    InvalidGateNft,
+    InvalidLoyaltyBonus,
+    InvalidClawbackDestination,
+    NoPendingClawbackDestination,
+    ClawbackTimelockActive,
+    DistributorStopped,
+    UnsupportedAccountVersion,
+    HoldbackRecordMissing,
+    InvalidHoldbackRecord,
+    LoyaltyBonusReserved,
+    SplitAmountMismatch,
+    TreasuryMissing,
+    InvalidTreasury,
+    InvalidLegacyDistributor,
+    InvalidConfig,
+}
+
+/// This event is triggered whenever a new distributor is initialized.
+#[event]
+pub struct DistributorInitialized {
+    distributor: Pubkey,
+    mint: Pubkey,
+    category: DistributorCategory,
 }
 
 /// This event is triggered whenever a call to claim succeeds.
 #[event]
 pub struct Claimed {
+    pub merkle_index: u64,
+    pub account: Pubkey,
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub category: DistributorCategory,
+}
+
+/// This event is triggered whenever the merkle root gets updated.
+#[event]
+pub struct MerkleRootUpdated {
+    pub merkle_index: u64,
+    pub merkle_root: [u8; 32],
+    pub total_allocation: u64,
+}
+
+/// This event is triggered whenever a vesting position NFT is minted for a user.
+#[event]
+pub struct PositionMinted {
+    distributor: Pubkey,
+    account: Pubkey,
+    position_mint: Pubkey,
+}
+
+/// This event is triggered whenever a vesting position NFT is burned after full claim.
+#[event]
+pub struct PositionBurned {
+    distributor: Pubkey,
+    account: Pubkey,
+    position_mint: Pubkey,
+}
+
+/// This event is triggered whenever a relayer pushes a claim on behalf of a user.
+#[event]
+pub struct ClaimRelayed {
+    merkle_index: u64,
+    relayer: Pubkey,
+    account: Pubkey,
+}
+
+/// This event is triggered whenever part of a claim is moved to the holdback vault.
+#[event]
+pub struct TokensHeldBack {
     merkle_index: u64,
     account: Pubkey,
+    amount: u64,
+    release_ts: u64,
+}
+
+/// This event is triggered whenever held back tokens are paid out to the user.
+#[event]
+pub struct HoldbackReleased {
+    distributor: Pubkey,
+    account: Pubkey,
     token_account: Pubkey,
     amount: u64,
 }
 
-/// This event is triggered whenever the merkle root gets updated.
+/// This event is triggered whenever not started periods get postponed.
 #[event]
-pub struct MerkleRootUpdated {
+pub struct ScheduleShifted {
+    distributor: Pubkey,
+    shift_sec: u64,
+    periods_shifted: u64,
+}
+
+/// This event is triggered once for every period of the schedule after it ends.
+#[event]
+pub struct PeriodCompleted {
+    distributor: Pubkey,
+    index: u64,
+    end_ts: u64,
+}
+
+/// This event is triggered whenever a relative schedule gets resolved at TGE.
+#[event]
+pub struct TgeActivated {
+    distributor: Pubkey,
+    activation_ts: u64,
+}
+
+/// This event is triggered whenever the whole schedule gets unlocked early.
+#[event]
+pub struct VestingAccelerated {
+    distributor: Pubkey,
+    accelerated_at_ts: u64,
+}
+
+/// This event is triggered whenever the owner freezes the vault.
+#[event]
+pub struct VaultFrozen {
+    pub distributor: Pubkey,
+    pub frozen_at_ts: u64,
+}
+
+/// This event is triggered whenever the vault freeze gets lifted.
+#[event]
+pub struct VaultUnfrozen {
+    pub distributor: Pubkey,
+}
+
+/// This event is triggered whenever the owner proposes a new clawback destination.
+#[event]
+pub struct ClawbackDestinationProposed {
+    distributor: Pubkey,
+    destination: Pubkey,
+    proposed_at_ts: u64,
+}
+
+/// This event is triggered whenever a proposed clawback destination takes effect.
+#[event]
+pub struct ClawbackDestinationChanged {
+    distributor: Pubkey,
+    destination: Pubkey,
+}
+
+/// This event is triggered whenever a part of the vault is moved into a new distributor.
+#[event]
+pub struct DistributorSplit {
+    from: Pubkey,
+    into: Pubkey,
+    amount: u64,
+}
+
+/// This event is triggered whenever the owner repairs user details.
+#[event]
+pub struct UserDetailsRepaired {
+    distributor: Pubkey,
+    account: Pubkey,
+    repair_id: u64,
+    old_claimed_amount: u64,
+    claimed_amount: u64,
+    old_last_claimed_at_ts: u64,
+    last_claimed_at_ts: u64,
+}
+
+/// This event is triggered whenever a user gets a bonus for claiming late.
+#[event]
+pub struct LoyaltyBonusPaid {
     merkle_index: u64,
-    merkle_root: [u8; 32],
+    account: Pubkey,
+    amount: u64,
+}
+
+/// This event is triggered whenever a claim ticket is redeemed.
+#[event]
+pub struct TicketClaimed {
+    distributor: Pubkey,
+    ticket_id: u64,
+    account: Pubkey,
+    token_account: Pubkey,
+    amount: u64,
+}
+
+/// This event is triggered whenever a distributor creation fee is paid into the treasury.
+#[event]
+pub struct CreationFeePaid {
+    distributor: Pubkey,
+    payer: Pubkey,
+    amount: u64,
+}
+
+/// This event is triggered whenever the owner withdraws collected fees,
+/// `token` is empty for lamports.
+#[event]
+pub struct FeesWithdrawn {
+    token: Option<Pubkey>,
+    target: Pubkey,
+    amount: u64,
 }
 
 /// This event is triggered whenever a call to withdraw by owner succeeds.
 #[event]
 pub struct TokensWithdrawn {
+    pub token: Pubkey,
+    pub amount: u64,
+}
+
+/// This event is triggered whenever stray tokens get recovered from the vault authority.
+#[event]
+pub struct TokensRecovered {
+    distributor: Pubkey,
     token: Pubkey,
+    target: Pubkey,
     amount: u64,
 }
 
@@ -69,444 +330,3590 @@ pub mod claiming_factory {
             owner: ctx.accounts.owner.key(),
             admins: [None; 10],
             bump,
+            relayers: [None; 10],
+            allowed_mints: [None; 10],
+            creation_fee: 0,
+            version: Config::PROGRAM_VERSION,
+            account_version: ACCOUNT_VERSION,
         };
 
         Ok(())
     }
 
-    pub fn initialize(ctx: Context<Initialize>, args: InitializeArgs) -> Result<()> {
-        let distributor = ctx.accounts.distributor.deref_mut();
-
-        *distributor = MerkleDistributor {
-            merkle_index: 0,
-            merkle_root: args.merkle_root,
-            paused: false,
-            vault_bump: args.vault_bump,
-            vault: ctx.accounts.vault.key(),
-            // schedule should pass validation first
-            vesting: Vesting::new(args.schedule)?,
-        };
-
-        Ok(())
-    }
-
-    pub fn init_user_details(ctx: Context<InitUserDetails>, bump: u8) -> Result<()> {
-        let user_details = ctx.accounts.user_details.deref_mut();
+    pub fn set_version(ctx: Context<SetVersion>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
 
-        *user_details = UserDetails {
-            last_claimed_at_ts: 0,
-            claimed_amount: 0,
-            bump,
-        };
+        config.version = Config::PROGRAM_VERSION;
 
         Ok(())
     }
 
-    pub fn update_schedule(ctx: Context<UpdateSchedule>, args: UpdateScheduleArgs) -> Result<()> {
-        let distributor = &mut ctx.accounts.distributor;
+    /// Grows the config up to the current layout, the added fields are filled
+    /// depending on the layout the config was written with.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config = &ctx.accounts.config;
 
         require!(
-            !distributor.vesting.has_started(&ctx.accounts.clock),
-            VestingAlreadyStarted
+            config.try_borrow_data()?.get(..8) == Some(&Config::discriminator()[..]),
+            InvalidConfig
         );
 
-        for change in args.changes {
-            distributor.vesting.apply_change(change);
-        }
-
-        distributor.vesting.validate()?;
-
-        Ok(())
-    }
-
-    pub fn update_root(ctx: Context<UpdateRoot>, args: UpdateRootArgs) -> Result<()> {
-        let distributor = &mut ctx.accounts.distributor;
-
-        distributor.merkle_root = args.merkle_root;
-        distributor.merkle_index += 1;
+        grow_account(
+            config,
+            Config::LEN,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
 
-        emit!(MerkleRootUpdated {
-            merkle_index: distributor.merkle_index,
-            merkle_root: distributor.merkle_root
-        });
+        let mut migrated = Account::<Config>::try_from(config)?;
+        require!(migrated.owner == ctx.accounts.owner.key(), NotOwner);
 
-        if args.unpause {
-            distributor.paused = false;
+        match migrated.account_version {
+            // created before versioning, relayers, allowed mints and the creation fee
+            // are read from the zeroed tail as empty, so only the version is missing
+            0 => migrated.version = Config::PROGRAM_VERSION,
+            ACCOUNT_VERSION => {}
+            _ => return Err(ErrorCode::UnsupportedAccountVersion.into()),
         }
+        migrated.account_version = ACCOUNT_VERSION;
+        migrated.exit(&crate::ID)?;
 
         Ok(())
     }
 
-    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
-        let distributor = &mut ctx.accounts.distributor;
-
-        require!(distributor.paused != paused, ChangingPauseValueToTheSame);
+    /// Returns [`VersionInfo`] via return data.
+    pub fn get_version(ctx: Context<GetVersion>) -> Result<()> {
+        let version = VersionInfo {
+            program: Config::PROGRAM_VERSION,
+            config: ctx.accounts.config.version,
+        };
 
-        distributor.paused = paused;
+        set_return_data(&version.try_to_vec()?);
 
         Ok(())
     }
 
-    pub fn add_admin(ctx: Context<AddAdmin>) -> Result<()> {
-        let config = &mut ctx.accounts.config;
-        let admin = &ctx.accounts.admin;
+    pub fn initialize(ctx: Context<Initialize>, args: InitializeArgs) -> Result<()> {
+        let root = ctx.accounts.root.deref_mut();
+        *root = MerkleRoot::new(&args, *ctx.bumps.get("root").unwrap());
 
-        for admin_slot in config.admins.iter_mut() {
-            match admin_slot {
-                // this admin have been already added
-                Some(admin_key) if *admin_key == admin.key() => {
-                    return Ok(());
-                }
-                _ => {}
-            }
-        }
+        let stats = ctx.accounts.stats.deref_mut();
+        *stats = ClaimStats::new(&args.schedule, *ctx.bumps.get("stats").unwrap());
 
-        for admin_slot in config.admins.iter_mut() {
-            if let None = admin_slot {
-                *admin_slot = Some(admin.key());
-                return Ok(());
-            }
-        }
-        // fails if available admin slot is not found
-        Err(ErrorCode::MaxAdmins.into())
-    }
+        let distributor = ctx.accounts.distributor.deref_mut();
 
-    pub fn remove_admin(ctx: Context<RemoveAdmin>) -> Result<()> {
-        let config = &mut ctx.accounts.config;
-        let admin = &ctx.accounts.admin;
+        *distributor = MerkleDistributor::new(
+            args,
+            &ctx.accounts.vault,
+            &ctx.accounts.mint,
+            ctx.accounts.clawback_destination.key(),
+        )?;
 
-        for admin_slot in config.admins.iter_mut() {
-            if let Some(admin_key) = admin_slot {
-                if *admin_key == admin.key() {
-                    *admin_slot = None;
-                    return Ok(());
-                }
-            }
+        emit!(DistributorInitialized {
+            distributor: ctx.accounts.distributor.key(),
+            mint: ctx.accounts.mint.key(),
+            category: ctx.accounts.distributor.category,
+        });
+
+        CreationFee {
+            amount: ctx.accounts.config.creation_fee,
+            distributor: ctx.accounts.distributor.key(),
+            payer: &ctx.accounts.admin_or_owner,
+            remaining_accounts: ctx.remaining_accounts,
+            system_program: &ctx.accounts.system_program,
         }
+        .charge()?;
 
-        // fails if admin is not found
-        Err(ErrorCode::AdminNotFound.into())
+        Ok(())
     }
 
-    pub fn withdraw_tokens(ctx: Context<WithdrawTokens>, amount: u64) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
-        let distributor = &ctx.accounts.distributor;
+    /// Moves `amount` from the vault into a new distributor with its own schedule,
+    /// the old distributor gets stopped. The new distributor pays the creation fee
+    /// like in `initialize`, with the treasury in remaining accounts.
+    pub fn split_distributor(
+        ctx: Context<SplitDistributor>,
+        args: SplitDistributorArgs,
+    ) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        require!(distributor.frozen_at_ts.is_none(), VaultFrozen);
+        require!(!distributor.stopped, DistributorStopped);
+        // the new tree has to be fully funded, and nothing else may leave with it
+        require!(
+            args.amount == args.distributor.total_allocation,
+            SplitAmountMismatch
+        );
 
         let distributor_key = distributor.key();
+        let new_distributor_key = ctx.accounts.new_distributor.key();
         let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
         let signers = &[&seeds[..]];
 
         TokenTransfer {
-            amount,
-            from: vault,
-            to: &ctx.accounts.target_wallet,
+            amount: args.amount,
+            from: &mut ctx.accounts.vault,
+            to: &ctx.accounts.new_vault,
             authority: &ctx.accounts.vault_authority,
             token_program: &ctx.accounts.token_program,
             signers: Some(signers),
         }
         .make()?;
 
-        emit!(TokensWithdrawn {
-            token: vault.mint,
-            amount
+        distributor.stopped = true;
+        distributor.split_into = Some(new_distributor_key);
+
+        let new_root = ctx.accounts.new_root.deref_mut();
+        *new_root = MerkleRoot::new(&args.distributor, *ctx.bumps.get("new_root").unwrap());
+
+        let new_stats = ctx.accounts.new_stats.deref_mut();
+        *new_stats = ClaimStats::new(
+            &args.distributor.schedule,
+            *ctx.bumps.get("new_stats").unwrap(),
+        );
+
+        let new_distributor = ctx.accounts.new_distributor.deref_mut();
+        *new_distributor = MerkleDistributor::new(
+            args.distributor,
+            &ctx.accounts.new_vault,
+            &ctx.accounts.mint,
+            distributor.clawback_destination,
+        )?;
+        new_distributor.split_from = Some(distributor_key);
+
+        emit!(DistributorInitialized {
+            distributor: new_distributor_key,
+            mint: ctx.accounts.mint.key(),
+            category: new_distributor.category,
         });
+        emit!(DistributorSplit {
+            from: distributor_key,
+            into: new_distributor_key,
+            amount: args.amount,
+        });
+
+        CreationFee {
+            amount: ctx.accounts.config.creation_fee,
+            distributor: new_distributor_key,
+            payer: &ctx.accounts.owner,
+            remaining_accounts: ctx.remaining_accounts,
+            system_program: &ctx.accounts.system_program,
+        }
+        .charge()?;
 
         Ok(())
     }
 
-    pub fn claim(ctx: Context<Claim>, args: ClaimArgs) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
-        let distributor = &ctx.accounts.distributor;
-        let user_details = &mut ctx.accounts.user_details;
+    pub fn init_treasury(ctx: Context<InitTreasury>) -> Result<()> {
+        let treasury = ctx.accounts.treasury.deref_mut();
 
-        require!(!distributor.paused, Paused);
-        require!(user_details.claimed_amount < args.amount, AlreadyClaimed);
+        *treasury = Treasury {
+            total_collected: 0,
+            total_withdrawn: 0,
+            bump: *ctx.bumps.get("treasury").unwrap(),
+            account_version: ACCOUNT_VERSION,
+        };
 
-        let leaf = [
-            &ctx.accounts.user.key().to_bytes()[..],
-            &args.amount.to_be_bytes(),
-        ];
-        let leaf = keccak::hashv(&leaf).0;
+        Ok(())
+    }
 
-        let mut computed_hash = leaf;
-        for proof_element in args.merkle_proof {
-            if computed_hash <= proof_element {
-                computed_hash = keccak::hashv(&[computed_hash.as_ref(), proof_element.as_ref()]).0;
-            } else {
-                computed_hash = keccak::hashv(&[proof_element.as_ref(), computed_hash.as_ref()]).0;
-            }
-        }
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+        let target = &ctx.accounts.target;
 
-        require!(computed_hash == distributor.merkle_root, InvalidProof);
+        // the treasury should stay rent exempt
+        let treasury_info = treasury.to_account_info();
+        let rent_exempt_balance = Rent::get()?.minimum_balance(treasury_info.data_len());
+        let available = treasury_info.lamports().saturating_sub(rent_exempt_balance);
+        require!(amount <= available, InsufficientTreasuryBalance);
 
-        let (bps_to_claim, bps_to_add) = distributor
-            .vesting
-            .bps_available_to_claim(ctx.accounts.clock.unix_timestamp as u64, &user_details);
-        let amount = (Decimal::from_u64(args.amount).unwrap() * bps_to_claim)
-            .ceil()
-            .to_u64()
-            .unwrap();
-        // this amount is from airdropped periods
-        let amount_to_add = (Decimal::from_u64(args.amount).unwrap() * bps_to_add)
-            .ceil()
-            .to_u64()
-            .unwrap();
-        require!(amount > 0, NothingToClaim);
+        **treasury_info.try_borrow_mut_lamports()? -= amount;
+        **target.try_borrow_mut_lamports()? += amount;
 
-        let distributor_key = distributor.key();
-        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        treasury.total_withdrawn = treasury
+            .total_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::IntegerOverflow)?;
+
+        emit!(FeesWithdrawn {
+            token: None,
+            target: target.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_fee_tokens(ctx: Context<WithdrawFeeTokens>, amount: u64) -> Result<()> {
+        let treasury = &ctx.accounts.treasury;
+
+        require!(
+            amount <= ctx.accounts.fee_wallet.amount,
+            InsufficientTreasuryBalance
+        );
+
+        let seeds = &["treasury".as_ref(), &[treasury.bump]];
         let signers = &[&seeds[..]];
 
         TokenTransfer {
             amount,
-            from: vault,
+            from: &mut ctx.accounts.fee_wallet,
             to: &ctx.accounts.target_wallet,
-            authority: &ctx.accounts.vault_authority,
+            authority: &treasury.to_account_info(),
             token_program: &ctx.accounts.token_program,
             signers: Some(signers),
         }
         .make()?;
 
-        user_details.claimed_amount += amount;
-        user_details.claimed_amount += amount_to_add;
-
-        user_details.last_claimed_at_ts = ctx.accounts.clock.unix_timestamp as u64;
+        // created by the first withdrawal of the mint
+        let stats = &mut ctx.accounts.fee_token_stats;
+        if stats.bump == 0 {
+            stats.mint = ctx.accounts.fee_wallet.mint;
+            stats.bump = *ctx.bumps.get("fee_token_stats").unwrap();
+            stats.account_version = ACCOUNT_VERSION;
+        }
+        check_account_version(stats.account_version)?;
+        stats.total_withdrawn = stats
+            .total_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::IntegerOverflow)?;
 
-        emit!(Claimed {
-            merkle_index: distributor.merkle_index,
-            account: ctx.accounts.user.key(),
-            token_account: ctx.accounts.target_wallet.key(),
+        emit!(FeesWithdrawn {
+            token: Some(ctx.accounts.fee_wallet.mint),
+            target: ctx.accounts.target_wallet.key(),
             amount,
         });
 
         Ok(())
     }
-}
 
-#[account]
-#[derive(Debug)]
-pub struct Config {
-    owner: Pubkey,
-    admins: [Option<Pubkey>; 10],
-    bump: u8,
-}
+    pub fn set_creation_fee(ctx: Context<SetCreationFee>, creation_fee: u64) -> Result<()> {
+        let config = &mut ctx.accounts.config;
 
-impl Config {
-    pub const LEN: usize = std::mem::size_of::<Self>() + 8;
-}
+        config.creation_fee = creation_fee;
 
-#[account]
-pub struct UserDetails {
-    last_claimed_at_ts: u64,
-    claimed_amount: u64,
-    bump: u8,
-}
+        Ok(())
+    }
 
-impl UserDetails {
-    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
-}
+    pub fn init_user_details(ctx: Context<InitUserDetails>, bump: u8) -> Result<()> {
+        let user_details = ctx.accounts.user_details.deref_mut();
 
-#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
-pub struct Period {
-    /// Percentage in Basis Points (BPS). 1% = 100 BPS.
-    /// NOTE: Percentage is for the whole period.
-    pub token_percentage: u64,
-    pub start_ts: u64,
-    pub interval_sec: u64,
-    pub times: u64,
-    /// We should skip this in claim amount calculation
-    /// because it has been claimed outside of this vesting scope.
-    pub airdropped: bool,
-}
+        *user_details = UserDetails {
+            last_claimed_at_ts: 0,
+            claimed_amount: 0,
+            bump,
+            verified_allocation: None,
+            pending_amount: 0,
+            claim_count: 0,
+            first_claimed_at_ts: 0,
+            account_version: ACCOUNT_VERSION,
+        };
 
-#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
-pub struct Vesting {
-    schedule: Vec<Period>,
-}
+        Ok(())
+    }
 
-impl Vesting {
-    fn new(schedule: Vec<Period>) -> Result<Self> {
-        let s = Self { schedule };
+    /// Grows user details created before new fields were added, the new fields start zeroed,
+    /// and stamps the current [`ACCOUNT_VERSION`].
+    pub fn migrate_user_details(ctx: Context<MigrateUserDetails>) -> Result<()> {
+        let user_details = &ctx.accounts.user_details;
 
-        s.validate()?;
+        require!(
+            user_details.try_borrow_data()?.get(..8) == Some(&UserDetails::discriminator()[..]),
+            InvalidUserDetails
+        );
 
-        Ok(s)
+        grow_account(
+            user_details,
+            UserDetails::LEN,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        // accounts grown above start at version zero
+        let mut migrated = Account::<UserDetails>::try_from(user_details)?;
+        check_account_version(migrated.account_version)?;
+        migrated.account_version = ACCOUNT_VERSION;
+        migrated.exit(&crate::ID)?;
+
+        Ok(())
     }
 
-    fn validate(&self) -> Result<()> {
-        require!(self.schedule.len() > 0, EmptySchedule);
+    /// Moves a distributor created before the merkle root and the claim counters got their own
+    /// accounts into the current layout. Claims made before the migration aren't counted,
+    /// so `total_allocation` should be what is left unclaimed in the current tree.
+    pub fn migrate_distributor(
+        ctx: Context<MigrateDistributor>,
+        args: MigrateDistributorArgs,
+    ) -> Result<()> {
+        let distributor_info = &ctx.accounts.distributor;
 
-        let mut last_start_ts = 0;
-        let mut total_percentage = 0;
+        let legacy = {
+            let data = distributor_info.try_borrow_data()?;
+            require!(
+                data.get(..8) == Some(&MerkleDistributor::discriminator()[..]),
+                InvalidLegacyDistributor
+            );
+            LegacyMerkleDistributor::deserialize(&mut &data[8..])
+                .map_err(|_| ErrorCode::InvalidLegacyDistributor)?
+        };
+        require!(legacy.vault == ctx.accounts.vault.key(), InvalidVault);
+        require!(
+            args.total_allocation <= ctx.accounts.vault.amount,
+            AllocationExceedsVault
+        );
 
-        for entry in &self.schedule {
-            require!(entry.times > 0, EmptyPeriod);
-            require!(last_start_ts < entry.start_ts, InvalidScheduleOrder);
+        let root = ctx.accounts.root.deref_mut();
+        *root = MerkleRoot {
+            // user details are derived from the index, so it has to stay the same
+            merkle_index: legacy.merkle_index,
+            merkle_root: legacy.merkle_root,
+            paused: legacy.paused,
+            total_allocation: args.total_allocation,
+            max_proof_len: None,
+            round_history: [None; 8],
+            bump: *ctx.bumps.get("root").unwrap(),
+            account_version: ACCOUNT_VERSION,
+        };
 
-            // start_ts + (times * interval_sec)
-            last_start_ts = entry
-                .times
-                .checked_mul(entry.interval_sec)
-                .ok_or(ErrorCode::IntegerOverflow)?
-                .checked_add(entry.start_ts)
-                .ok_or(ErrorCode::IntegerOverflow)?;
+        // the stats are created empty, the number of periods is only known here
+        grow_account(
+            &ctx.accounts.stats.to_account_info(),
+            ClaimStats::space_required(&legacy.vesting.schedule),
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+        let stats = ctx.accounts.stats.deref_mut();
+        *stats = ClaimStats::new(&legacy.vesting.schedule, *ctx.bumps.get("stats").unwrap());
 
-            total_percentage += entry.token_percentage;
+        let distributor = MerkleDistributor {
+            vault_bump: legacy.vault_bump,
+            vault: legacy.vault,
+            completed_periods: vec![false; legacy.vesting.schedule.len()],
+            vesting: Vesting {
+                schedule: legacy.vesting.schedule,
+                accelerated_at_ts: None,
+                loyalty_bonus: None,
+            },
+            position_nfts: false,
+            mint: ctx.accounts.mint.key(),
+            mint_decimals: ctx.accounts.mint.decimals,
+            max_claim_per_tx: None,
+            ticket_signer: None,
+            holdback: None,
+            category: args.category,
+            frozen_at_ts: None,
+            custodians: [None; 10],
+            withdrawal_schedule: false,
+            relative_schedule: false,
+            activation_ts: 0,
+            gate_collection: None,
+            clawback_destination: args.clawback_destination,
+            pending_clawback_destination: None,
+            stopped: false,
+            split_from: None,
+            split_into: None,
+            account_version: ACCOUNT_VERSION,
+        };
+
+        grow_account(
+            distributor_info,
+            MerkleDistributor::space_required(&distributor.vesting.schedule),
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+        let mut data = distributor_info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        distributor.try_serialize(&mut writer)?;
+
+        Ok(())
+    }
+
+    pub fn update_schedule(ctx: Context<UpdateSchedule>, args: UpdateScheduleArgs) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        require!(
+            distributor.tge_pending()
+                || !distributor
+                    .vesting
+                    .has_started(Clock::get()?.unix_timestamp as u64),
+            VestingAlreadyStarted
+        );
+
+        for change in args.changes {
+            distributor.vesting.apply_change(change);
         }
 
-        // 100% == 10000 basis points
-        require!(total_percentage == 10000, PercentageDoesntCoverAllTokens);
+        distributor.vesting.validate(distributor.tge_pending())?;
+
+        // vesting hasn't started yet, so there is no claimed tokens to keep
+        ctx.accounts.stats.claimed_per_period = vec![0; distributor.vesting.schedule.len()];
+        distributor.completed_periods = vec![false; distributor.vesting.schedule.len()];
 
         Ok(())
     }
 
-    fn has_started(&self, clock: &Sysvar<Clock>) -> bool {
-        let first_period = self.schedule.first().unwrap();
-        let now = clock.unix_timestamp as u64;
+    pub fn shift_schedule(ctx: Context<ShiftSchedule>, shift_sec: u64) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
 
-        first_period.start_ts <= now
+        require!(!distributor.tge_pending(), TgeNotActivated);
+
+        let periods_shifted = distributor.vesting.shift_not_started(now, shift_sec)?;
+        distributor.vesting.validate(false)?;
+
+        emit!(ScheduleShifted {
+            distributor: distributor.key(),
+            shift_sec,
+            periods_shifted,
+        });
+
+        Ok(())
     }
 
-    fn apply_change(&mut self, change: Change) {
-        match change {
-            Change::Update { index, period } => {
-                self.schedule[index as usize] = period;
-            }
-            Change::Remove { index } => {
-                self.schedule.remove(index as usize);
-            }
-            Change::Push { period } => {
-                self.schedule.push(period);
-            }
+    /// Permissionless crank which marks an ended period as completed,
+    /// calls for already completed periods are no-op.
+    pub fn complete_period(ctx: Context<CompletePeriod>, index: u64) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        require!(!distributor.tge_pending(), TgeNotActivated);
+
+        let period = distributor
+            .vesting
+            .schedule
+            .get(index as usize)
+            .ok_or(ErrorCode::InvalidPeriodIndex)?;
+        let end_ts = period.start_ts + period.times * period.interval_sec;
+        require!(
+            end_ts <= now || distributor.vesting.accelerated_at_ts.is_some(),
+            PeriodNotEnded
+        );
+
+        if distributor.completed_periods[index as usize] {
+            return Ok(());
         }
+        distributor.completed_periods[index as usize] = true;
+
+        emit!(PeriodCompleted {
+            distributor: distributor.key(),
+            index,
+            end_ts,
+        });
+
+        Ok(())
     }
 
-    fn bps_available_to_claim(&self, now: u64, user_details: &UserDetails) -> (Decimal, Decimal) {
-        let mut total_percentage_to_claim = Decimal::ZERO;
-        let mut total_percentage_to_add = Decimal::ZERO;
+    /// Resolves period offsets of a relative schedule into timestamps,
+    /// TGE happens now if `activation_ts` is not provided.
+    pub fn activate_tge(ctx: Context<ActivateTge>, activation_ts: Option<u64>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
 
-        for period in self.schedule.iter() {
-            sol_log_64(now, period.start_ts, user_details.last_claimed_at_ts, 0, 0);
+        require!(distributor.relative_schedule, NotRelativeSchedule);
+        require!(distributor.activation_ts == 0, TgeAlreadyActivated);
 
-            if now < period.start_ts {
-                sol_log("too early to claim period");
-                break;
-            }
+        let activation_ts = match activation_ts {
+            Some(activation_ts) => activation_ts,
+            None => Clock::get()?.unix_timestamp as u64,
+        };
 
-            let period_end_ts = period.start_ts + period.times * period.interval_sec;
-            if period_end_ts <= user_details.last_claimed_at_ts {
-                sol_log("skip since we've already claimed");
-                continue;
+        for period in distributor.vesting.schedule.iter_mut() {
+            period.start_ts = period
+                .start_ts
+                .checked_add(activation_ts)
+                .ok_or(ErrorCode::IntegerOverflow)?;
+        }
+        distributor.vesting.validate(false)?;
+        distributor.activation_ts = activation_ts;
+
+        emit!(TgeActivated {
+            distributor: distributor.key(),
+            activation_ts,
+        });
+
+        Ok(())
+    }
+
+    /// Logs the unlock table of the stored schedule, doesn't change anything.
+    pub fn preview_schedule(ctx: Context<PreviewSchedule>) -> Result<()> {
+        let vesting = &ctx.accounts.distributor.vesting;
+        let mut cumulative_bps = 0;
+
+        msg!("index | start | end | unlock | cumulative");
+        for (index, period) in vesting.schedule.iter().enumerate() {
+            let end_ts = period.start_ts + period.times * period.interval_sec;
+            let airdropped = if period.airdropped {
+                " (airdropped)"
+            } else {
+                ""
+            };
+            cumulative_bps += period.token_percentage;
+
+            msg!(
+                "{} | {} | {} | {}.{:02}% in {} x {}s{} | {}.{:02}%",
+                index,
+                period.start_ts,
+                end_ts,
+                period.token_percentage / 100,
+                period.token_percentage % 100,
+                period.times,
+                period.interval_sec,
+                airdropped,
+                cumulative_bps / 100,
+                cumulative_bps % 100
+            );
+        }
+
+        if let Some(accelerated_at_ts) = vesting.accelerated_at_ts {
+            msg!("fully unlocked at {}", accelerated_at_ts);
+        }
+
+        Ok(())
+    }
+
+    /// Checks sample allocations against the current root, fails if any of them is invalid.
+    pub fn verify_proofs(ctx: Context<VerifyProofs>, args: VerifyProofsArgs) -> Result<()> {
+        let root = &ctx.accounts.root;
+        let mut invalid = 0;
+
+        for (index, allocation) in args.allocations.iter().enumerate() {
+            let leaf = allocation_leaf(&allocation.wallet, allocation.amount);
+            let valid = check_proof(leaf, &allocation.merkle_proof, &root.merkle_root).is_ok();
+
+            msg!(
+                "allocation {}: wallet {}, amount {}, valid {}",
+                index,
+                allocation.wallet,
+                allocation.amount,
+                valid
+            );
+
+            if !valid {
+                invalid += 1;
             }
+        }
 
-            if period.airdropped {
-                sol_log("this period was airdropped");
-                total_percentage_to_add += Decimal::new(period.token_percentage as i64, 4);
-                continue;
+        require!(invalid == 0, InvalidProof);
+
+        Ok(())
+    }
+
+    pub fn update_root(ctx: Context<UpdateRoot>, args: UpdateRootArgs) -> Result<()> {
+        let root = &mut ctx.accounts.root;
+        let stats = &mut ctx.accounts.stats;
+
+        // the new tree should be fully covered by tokens left in the vault,
+        // apart from those reserved for loyalty bonuses
+        let available = ctx
+            .accounts
+            .vault
+            .amount
+            .saturating_sub(ctx.accounts.distributor.bonus_reserve(stats));
+        require!(args.total_allocation <= available, AllocationExceedsVault);
+
+        root.archive_round(stats);
+
+        root.merkle_root = args.merkle_root;
+        root.merkle_index += 1;
+        root.total_allocation = args.total_allocation;
+        root.max_proof_len = args.max_proof_len;
+        stats.total_claimed = 0;
+        stats.tickets_claimed = 0;
+        stats.claimers = 0;
+
+        emit!(MerkleRootUpdated {
+            merkle_index: root.merkle_index,
+            merkle_root: root.merkle_root,
+            total_allocation: root.total_allocation,
+        });
+
+        if args.unpause {
+            root.paused = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let root = &mut ctx.accounts.root;
+
+        require!(root.paused != paused, ChangingPauseValueToTheSame);
+
+        root.paused = paused;
+
+        Ok(())
+    }
+
+    pub fn freeze_vault(ctx: Context<FreezeVault>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        require!(distributor.frozen_at_ts.is_none(), VaultAlreadyFrozen);
+
+        distributor.frozen_at_ts = Some(now);
+
+        emit!(VaultFrozen {
+            distributor: distributor.key(),
+            frozen_at_ts: now,
+        });
+
+        Ok(())
+    }
+
+    pub fn unfreeze_vault(ctx: Context<UnfreezeVault>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        let frozen_at_ts = distributor.frozen_at_ts.ok_or(ErrorCode::VaultNotFrozen)?;
+        require!(
+            now >= frozen_at_ts + MerkleDistributor::FREEZE_TIMELOCK_SEC,
+            FreezeTimelockActive
+        );
+
+        distributor.frozen_at_ts = None;
+
+        emit!(VaultUnfrozen {
+            distributor: distributor.key(),
+        });
+
+        Ok(())
+    }
+
+    /// The new destination takes effect only after [`MerkleDistributor::CLAWBACK_TIMELOCK_SEC`].
+    pub fn propose_clawback_destination(ctx: Context<ProposeClawbackDestination>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        let destination = ctx.accounts.clawback_destination.key();
+        distributor.pending_clawback_destination = Some(PendingClawbackDestination {
+            destination,
+            proposed_at_ts: now,
+        });
+
+        emit!(ClawbackDestinationProposed {
+            distributor: distributor.key(),
+            destination,
+            proposed_at_ts: now,
+        });
+
+        Ok(())
+    }
+
+    pub fn apply_clawback_destination(ctx: Context<ApplyClawbackDestination>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        let pending = distributor
+            .pending_clawback_destination
+            .ok_or(ErrorCode::NoPendingClawbackDestination)?;
+        require!(
+            now >= pending.proposed_at_ts + MerkleDistributor::CLAWBACK_TIMELOCK_SEC,
+            ClawbackTimelockActive
+        );
+
+        distributor.clawback_destination = pending.destination;
+        distributor.pending_clawback_destination = None;
+
+        emit!(ClawbackDestinationChanged {
+            distributor: distributor.key(),
+            destination: pending.destination,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_max_claim_per_tx(
+        ctx: Context<SetMaxClaimPerTx>,
+        max_claim_per_tx: Option<u64>,
+    ) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        distributor.max_claim_per_tx = max_claim_per_tx;
+
+        Ok(())
+    }
+
+    /// Sets the loyalty bonus curve, the budget has to be funded on top of unclaimed allocations.
+    pub fn set_loyalty_bonus(
+        ctx: Context<SetLoyaltyBonus>,
+        args: Option<SetLoyaltyBonusArgs>,
+    ) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        let args = match args {
+            Some(args) => args,
+            None => {
+                distributor.vesting.loyalty_bonus = None;
+                return Ok(());
             }
+        };
 
-            let last_claimed_at_ts_aligned_by_interval = user_details.last_claimed_at_ts
-                - user_details.last_claimed_at_ts % period.interval_sec;
-            let seconds_passed =
-                now - std::cmp::max(period.start_ts, last_claimed_at_ts_aligned_by_interval);
-            let intervals_passed = seconds_passed / period.interval_sec;
-            let intervals_passed = std::cmp::min(intervals_passed, period.times);
+        let paid = ctx.accounts.stats.bonus_paid;
+        require!(
+            args.bonus_bps <= 10000 && args.budget >= paid,
+            InvalidLoyaltyBonus
+        );
 
-            sol_log_64(
-                user_details.last_claimed_at_ts,
-                last_claimed_at_ts_aligned_by_interval,
-                seconds_passed,
-                now,
-                intervals_passed,
-            );
+        let unclaimed = ctx
+            .accounts
+            .root
+            .total_allocation
+            .saturating_sub(ctx.accounts.stats.claimed_with_tickets());
+        let required = unclaimed
+            .checked_add(args.budget - paid)
+            .ok_or(ErrorCode::IntegerOverflow)?;
+        require!(
+            required <= ctx.accounts.vault.amount,
+            AllocationExceedsVault
+        );
 
-            let percentage_for_intervals = (Decimal::new(period.token_percentage as i64, 4)
-                / Decimal::from_u64(period.times).unwrap())
-                * Decimal::from_u64(intervals_passed).unwrap();
+        distributor.vesting.loyalty_bonus = Some(LoyaltyBonus {
+            delay_sec: args.delay_sec,
+            bonus_bps: args.bonus_bps,
+            budget: args.budget,
+        });
 
-            total_percentage_to_claim += percentage_for_intervals;
+        Ok(())
+    }
+
+    pub fn set_gate_collection(
+        ctx: Context<SetGateCollection>,
+        gate_collection: Option<Pubkey>,
+    ) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        distributor.gate_collection = gate_collection;
+
+        Ok(())
+    }
+
+    pub fn set_ticket_signer(
+        ctx: Context<SetTicketSigner>,
+        ticket_signer: Option<Pubkey>,
+    ) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        distributor.ticket_signer = ticket_signer;
+
+        Ok(())
+    }
+
+    /// Claims a ticket signed by the distributor's ticket signer. The remaining accounts
+    /// are the same as for `claim`, besides the position mint.
+    pub fn claim_ticket(ctx: Context<ClaimTicket>, args: ClaimTicketArgs) -> Result<()> {
+        let distributor = &ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        require!(now <= args.expires_at_ts, TicketExpired);
+
+        let ticket_signer = distributor
+            .ticket_signer
+            .ok_or(ErrorCode::TicketsDisabled)?;
+
+        // the ticket signature is checked by the ed25519 program in the previous instruction
+        let instructions = &ctx.accounts.instructions;
+        let current_index = load_current_index_checked(instructions)?;
+        require!(current_index > 0, InvalidTicketSignature);
+        let signature_ix = load_instruction_at_checked(current_index as usize - 1, instructions)?;
+        require!(
+            signature_ix.program_id == ed25519_program::ID,
+            InvalidTicketSignature
+        );
+
+        let message = args.message(&distributor.key(), &ctx.accounts.user.key());
+        check_ticket_signature(&signature_ix.data, &ticket_signer, &message)?;
+
+        let ticket_usage = ctx.accounts.ticket_usage.deref_mut();
+        *ticket_usage = TicketUsage {
+            user: ctx.accounts.user.key(),
+            amount: args.amount,
+            claimed_at_ts: now,
+            bump: *ctx.bumps.get("ticket_usage").unwrap(),
+            account_version: ACCOUNT_VERSION,
+        };
+
+        ClaimTokens {
+            distributor: &ctx.accounts.distributor,
+            root: &ctx.accounts.root,
+            stats: &mut ctx.accounts.stats,
+            user: ctx.accounts.user.key(),
+            vault_authority: &ctx.accounts.vault_authority,
+            vault: &mut ctx.accounts.vault,
+            target_wallet: &ctx.accounts.target_wallet,
+            token_program: &ctx.accounts.token_program,
+            remaining_accounts: ctx.remaining_accounts,
         }
+        .make(ClaimSource::Ticket {
+            amount: args.amount,
+        })?;
 
-        (total_percentage_to_claim, total_percentage_to_add)
+        emit!(TicketClaimed {
+            distributor: ctx.accounts.distributor.key(),
+            ticket_id: args.ticket_id,
+            account: ctx.accounts.user.key(),
+            token_account: ctx.accounts.target_wallet.key(),
+            amount: args.amount,
+        });
+
+        Ok(())
     }
-}
 
-#[account]
-#[derive(Debug)]
-pub struct MerkleDistributor {
-    merkle_index: u64,
-    merkle_root: [u8; 32],
-    paused: bool,
-    vault_bump: u8,
-    vault: Pubkey,
-    vesting: Vesting,
-}
+    pub fn add_admin(ctx: Context<AddAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let admin = &ctx.accounts.admin;
+
+        for admin_slot in config.admins.iter_mut() {
+            match admin_slot {
+                // this admin have been already added
+                Some(admin_key) if *admin_key == admin.key() => {
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        for admin_slot in config.admins.iter_mut() {
+            if let None = admin_slot {
+                *admin_slot = Some(admin.key());
+                return Ok(());
+            }
+        }
+        // fails if available admin slot is not found
+        Err(ErrorCode::MaxAdmins.into())
+    }
+
+    pub fn remove_admin(ctx: Context<RemoveAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let admin = &ctx.accounts.admin;
+
+        for admin_slot in config.admins.iter_mut() {
+            if let Some(admin_key) = admin_slot {
+                if *admin_key == admin.key() {
+                    *admin_slot = None;
+                    return Ok(());
+                }
+            }
+        }
+
+        // fails if admin is not found
+        Err(ErrorCode::AdminNotFound.into())
+    }
+
+    pub fn add_relayer(ctx: Context<AddRelayer>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let relayer = &ctx.accounts.relayer;
+
+        if config.relayers.contains(&Some(relayer.key())) {
+            return Ok(());
+        }
+
+        for relayer_slot in config.relayers.iter_mut() {
+            if let None = relayer_slot {
+                *relayer_slot = Some(relayer.key());
+                return Ok(());
+            }
+        }
+        // fails if available relayer slot is not found
+        Err(ErrorCode::MaxRelayers.into())
+    }
+
+    pub fn remove_relayer(ctx: Context<RemoveRelayer>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let relayer = &ctx.accounts.relayer;
+
+        for relayer_slot in config.relayers.iter_mut() {
+            if *relayer_slot == Some(relayer.key()) {
+                *relayer_slot = None;
+                return Ok(());
+            }
+        }
+
+        // fails if relayer is not found
+        Err(ErrorCode::RelayerNotFound.into())
+    }
+
+    pub fn add_custodian(ctx: Context<AddCustodian>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let custodian = &ctx.accounts.custodian;
+
+        if distributor.custodians.contains(&Some(custodian.key())) {
+            return Ok(());
+        }
+
+        for custodian_slot in distributor.custodians.iter_mut() {
+            if let None = custodian_slot {
+                *custodian_slot = Some(custodian.key());
+                return Ok(());
+            }
+        }
+        // fails if available custodian slot is not found
+        Err(ErrorCode::MaxCustodians.into())
+    }
+
+    pub fn remove_custodian(ctx: Context<RemoveCustodian>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let custodian = &ctx.accounts.custodian;
+
+        for custodian_slot in distributor.custodians.iter_mut() {
+            if *custodian_slot == Some(custodian.key()) {
+                *custodian_slot = None;
+                return Ok(());
+            }
+        }
+
+        // fails if custodian is not found
+        Err(ErrorCode::CustodianNotFound.into())
+    }
+
+    pub fn add_allowed_mint(ctx: Context<AddAllowedMint>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let mint = &ctx.accounts.mint;
+
+        if config.allowed_mints.contains(&Some(mint.key())) {
+            return Ok(());
+        }
+
+        for mint_slot in config.allowed_mints.iter_mut() {
+            if let None = mint_slot {
+                *mint_slot = Some(mint.key());
+                return Ok(());
+            }
+        }
+        // fails if available mint slot is not found
+        Err(ErrorCode::MaxAllowedMints.into())
+    }
+
+    pub fn remove_allowed_mint(ctx: Context<RemoveAllowedMint>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let mint = &ctx.accounts.mint;
+
+        for mint_slot in config.allowed_mints.iter_mut() {
+            if *mint_slot == Some(mint.key()) {
+                *mint_slot = None;
+                return Ok(());
+            }
+        }
+
+        // fails if mint is not found
+        Err(ErrorCode::AllowedMintNotFound.into())
+    }
+
+    pub fn withdraw_tokens(ctx: Context<WithdrawTokens>, amount: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let distributor = &ctx.accounts.distributor;
+
+        require!(distributor.frozen_at_ts.is_none(), VaultFrozen);
+        require!(!distributor.withdrawal_schedule, WithdrawalScheduleSet);
+        // bonuses promised to users aren't withdrawable
+        let bonus_reserve = distributor.bonus_reserve(&ctx.accounts.stats);
+        require!(
+            amount <= vault.amount.saturating_sub(bonus_reserve),
+            LoyaltyBonusReserved
+        );
+
+        let distributor_key = distributor.key();
+        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        let signers = &[&seeds[..]];
+
+        TokenTransfer {
+            amount,
+            from: vault,
+            to: &ctx.accounts.target_wallet,
+            authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            signers: Some(signers),
+        }
+        .make()?;
+
+        emit!(TokensWithdrawn {
+            token: vault.mint,
+            amount
+        });
+
+        Ok(())
+    }
+
+    pub fn set_withdrawal_schedule(
+        ctx: Context<SetWithdrawalSchedule>,
+        args: SetWithdrawalScheduleArgs,
+    ) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let withdrawal_schedule = ctx.accounts.withdrawal_schedule.deref_mut();
+
+        *withdrawal_schedule = WithdrawalSchedule {
+            vesting: Vesting::new(args.schedule, false)?,
+            total_amount: args.total_amount,
+            withdrawn_amount: 0,
+            bump: *ctx.bumps.get("withdrawal_schedule").unwrap(),
+            account_version: ACCOUNT_VERSION,
+        };
+
+        distributor.withdrawal_schedule = true;
+
+        Ok(())
+    }
+
+    pub fn withdraw_vested_tokens(ctx: Context<WithdrawVestedTokens>, amount: u64) -> Result<()> {
+        let distributor = &ctx.accounts.distributor;
+        let withdrawal_schedule = &mut ctx.accounts.withdrawal_schedule;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        require!(distributor.frozen_at_ts.is_none(), VaultFrozen);
+
+        let available = withdrawal_schedule.available_to_withdraw(now);
+        require!(amount <= available, WithdrawalLocked);
+        let bonus_reserve = distributor.bonus_reserve(&ctx.accounts.stats);
+        require!(
+            amount <= ctx.accounts.vault.amount.saturating_sub(bonus_reserve),
+            LoyaltyBonusReserved
+        );
+
+        let distributor_key = distributor.key();
+        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        let signers = &[&seeds[..]];
+
+        TokenTransfer {
+            amount,
+            from: &mut ctx.accounts.vault,
+            to: &ctx.accounts.target_wallet,
+            authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            signers: Some(signers),
+        }
+        .make()?;
+
+        withdrawal_schedule.withdrawn_amount += amount;
+
+        emit!(TokensWithdrawn {
+            token: ctx.accounts.vault.mint,
+            amount
+        });
+
+        Ok(())
+    }
+
+    /// Overwrites claim progress of the user, every repair is kept in a [`RepairRecord`].
+    pub fn repair_user_details(
+        ctx: Context<RepairUserDetails>,
+        args: RepairUserDetailsArgs,
+    ) -> Result<()> {
+        let user_details = &mut ctx.accounts.user_details;
+        let repair_record = ctx.accounts.repair_record.deref_mut();
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        // nobody can have claimed more than the tree holds or claimed in the future
+        require!(
+            args.claimed_amount <= ctx.accounts.root.total_allocation
+                && args.last_claimed_at_ts <= now,
+            InvalidUserDetails
+        );
+
+        *repair_record = RepairRecord {
+            user_details: user_details.key(),
+            repaired_by: ctx.accounts.owner.key(),
+            old_claimed_amount: user_details.claimed_amount,
+            claimed_amount: args.claimed_amount,
+            old_last_claimed_at_ts: user_details.last_claimed_at_ts,
+            last_claimed_at_ts: args.last_claimed_at_ts,
+            repaired_at_ts: now,
+            bump: *ctx.bumps.get("repair_record").unwrap(),
+            account_version: ACCOUNT_VERSION,
+        };
+
+        emit!(UserDetailsRepaired {
+            distributor: ctx.accounts.distributor.key(),
+            account: ctx.accounts.user.key(),
+            repair_id: args.repair_id,
+            old_claimed_amount: user_details.claimed_amount,
+            claimed_amount: args.claimed_amount,
+            old_last_claimed_at_ts: user_details.last_claimed_at_ts,
+            last_claimed_at_ts: args.last_claimed_at_ts,
+        });
+
+        user_details.claimed_amount = args.claimed_amount;
+        user_details.last_claimed_at_ts = args.last_claimed_at_ts;
+
+        Ok(())
+    }
+
+    pub fn recover_token(ctx: Context<RecoverToken>, amount: u64) -> Result<()> {
+        let distributor = &ctx.accounts.distributor;
+
+        let distributor_key = distributor.key();
+        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        let signers = &[&seeds[..]];
+
+        TokenTransfer {
+            amount,
+            from: &mut ctx.accounts.source,
+            to: &ctx.accounts.target_wallet,
+            authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            signers: Some(signers),
+        }
+        .make()?;
+
+        emit!(TokensRecovered {
+            distributor: distributor_key,
+            token: ctx.accounts.source.mint,
+            target: ctx.accounts.target_wallet.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim(ctx: Context<Claim>, args: ClaimArgs) -> Result<()> {
+        let remaining_accounts = check_no_position(
+            &ctx.accounts.distributor,
+            &ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+        )?;
+
+        ClaimTokens {
+            distributor: &ctx.accounts.distributor,
+            root: &ctx.accounts.root,
+            stats: &mut ctx.accounts.stats,
+            user: ctx.accounts.user.key(),
+            vault_authority: &ctx.accounts.vault_authority,
+            vault: &mut ctx.accounts.vault,
+            target_wallet: &ctx.accounts.target_wallet,
+            token_program: &ctx.accounts.token_program,
+            remaining_accounts: remaining_accounts,
+        }
+        .make(ClaimSource::Allocation {
+            user_details: &mut ctx.accounts.user_details,
+            args,
+        })?;
+
+        Ok(())
+    }
+
+    pub fn accelerate_vesting(ctx: Context<AccelerateVesting>) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        require!(
+            distributor.vesting.accelerated_at_ts.is_none(),
+            VestingAlreadyAccelerated
+        );
+
+        distributor.vesting.validate(distributor.tge_pending())?;
+        distributor.vesting.accelerated_at_ts = Some(now);
+
+        emit!(VestingAccelerated {
+            distributor: distributor.key(),
+            accelerated_at_ts: now,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_holdback(ctx: Context<InitHoldback>, args: InitHoldbackArgs) -> Result<()> {
+        let distributor = &mut ctx.accounts.distributor;
+
+        require!(args.bps > 0 && args.bps <= 10000, InvalidHoldback);
+
+        distributor.holdback = Some(Holdback {
+            vault: ctx.accounts.holdback_vault.key(),
+            bps: args.bps,
+            window_sec: args.window_sec,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_holdback_record(ctx: Context<InitHoldbackRecord>) -> Result<()> {
+        let holdback_record = ctx.accounts.holdback_record.deref_mut();
+
+        *holdback_record = HoldbackRecord {
+            deposits: [None; 8],
+            bump: *ctx.bumps.get("holdback_record").unwrap(),
+            account_version: ACCOUNT_VERSION,
+        };
+
+        Ok(())
+    }
+
+    pub fn release_holdback(ctx: Context<ReleaseHoldback>) -> Result<()> {
+        let distributor = &ctx.accounts.distributor;
+        let holdback_record = &mut ctx.accounts.holdback_record;
+
+        check_account_version(holdback_record.account_version)?;
+        require!(distributor.frozen_at_ts.is_none(), VaultFrozen);
+
+        let amount = holdback_record.take_released(Clock::get()?.unix_timestamp as u64)?;
+
+        let distributor_key = distributor.key();
+        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        let signers = &[&seeds[..]];
+
+        TokenTransfer {
+            amount,
+            from: &mut ctx.accounts.holdback_vault,
+            to: &ctx.accounts.target_wallet,
+            authority: &ctx.accounts.vault_authority,
+            token_program: &ctx.accounts.token_program,
+            signers: Some(signers),
+        }
+        .make()?;
+
+        emit!(HoldbackReleased {
+            distributor: distributor_key,
+            account: ctx.accounts.user.key(),
+            token_account: ctx.accounts.target_wallet.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_on_behalf(ctx: Context<ClaimOnBehalf>, args: ClaimArgs) -> Result<()> {
+        let remaining_accounts = check_no_position(
+            &ctx.accounts.distributor,
+            &ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+        )?;
+
+        ClaimTokens {
+            distributor: &ctx.accounts.distributor,
+            root: &ctx.accounts.root,
+            stats: &mut ctx.accounts.stats,
+            user: ctx.accounts.user.key(),
+            vault_authority: &ctx.accounts.vault_authority,
+            vault: &mut ctx.accounts.vault,
+            target_wallet: &ctx.accounts.target_wallet,
+            token_program: &ctx.accounts.token_program,
+            remaining_accounts: remaining_accounts,
+        }
+        .make(ClaimSource::Allocation {
+            user_details: &mut ctx.accounts.user_details,
+            args,
+        })?;
+
+        emit!(ClaimRelayed {
+            merkle_index: ctx.accounts.root.merkle_index,
+            relayer: ctx.accounts.relayer.key(),
+            account: ctx.accounts.user.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn mint_position(ctx: Context<MintPosition>, args: MintPositionArgs) -> Result<()> {
+        let distributor = &ctx.accounts.distributor;
+        let user_details = &mut ctx.accounts.user_details;
+
+        require!(distributor.position_nfts, PositionNftsDisabled);
+        require!(user_details.claimed_amount < args.amount, AlreadyClaimed);
+        ctx.accounts.root.check_proof_len(&args.merkle_proof)?;
+
+        let leaf = allocation_leaf(&ctx.accounts.user.key(), args.amount);
+        user_details.verify_allocation(
+            &ctx.accounts.root,
+            leaf,
+            args.amount,
+            &args.merkle_proof,
+        )?;
+
+        let distributor_key = distributor.key();
+        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        let signers = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.position_mint.to_account_info(),
+                to: ctx.accounts.position_token_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            signers,
+        );
+        token::mint_to(cpi_ctx, 1)?;
+
+        emit!(PositionMinted {
+            distributor: distributor_key,
+            account: ctx.accounts.user.key(),
+            position_mint: ctx.accounts.position_mint.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_position(ctx: Context<ClaimPosition>, args: ClaimArgs) -> Result<()> {
+        let total_amount = args.amount;
+
+        ClaimTokens {
+            distributor: &ctx.accounts.distributor,
+            root: &ctx.accounts.root,
+            stats: &mut ctx.accounts.stats,
+            user: ctx.accounts.user.key(),
+            vault_authority: &ctx.accounts.vault_authority,
+            vault: &mut ctx.accounts.vault,
+            target_wallet: &ctx.accounts.target_wallet,
+            token_program: &ctx.accounts.token_program,
+            remaining_accounts: ctx.remaining_accounts,
+        }
+        .make(ClaimSource::Allocation {
+            user_details: &mut ctx.accounts.user_details,
+            args,
+        })?;
+
+        // the whole allocation is claimed, so the position doesn't represent anything anymore
+        if ctx.accounts.user_details.claimed_amount >= total_amount {
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.position_mint.to_account_info(),
+                    to: ctx.accounts.position_token_account.to_account_info(),
+                    authority: ctx.accounts.holder.to_account_info(),
+                },
+            );
+            token::burn(cpi_ctx, 1)?;
+
+            emit!(PositionBurned {
+                distributor: ctx.accounts.distributor.key(),
+                account: ctx.accounts.user.key(),
+                position_mint: ctx.accounts.position_mint.key(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct Config {
+    pub owner: Pubkey,
+    pub admins: [Option<Pubkey>; 10],
+    pub bump: u8,
+    /// Accounts allowed to execute claims on behalf of users.
+    pub relayers: [Option<Pubkey>; 10],
+    /// Mints which can be distributed, any mint is allowed while the list is empty.
+    pub allowed_mints: [Option<Pubkey>; 10],
+    /// Lamports charged for every new distributor, paid into the treasury.
+    pub creation_fee: u64,
+    /// Version of the program which initialized or last updated this config.
+    pub version: u32,
+    /// Layout of this account, unlike `version` it's not tied to releases.
+    pub account_version: u8,
+}
+
+impl Config {
+    pub const LEN: usize = std::mem::size_of::<Self>() + 8;
+    /// Bumped with every release that changes instructions or accounts.
+    pub const PROGRAM_VERSION: u32 = 1;
+
+    fn is_mint_allowed(&self, mint: &Pubkey) -> bool {
+        self.allowed_mints.iter().all(Option::is_none) || self.allowed_mints.contains(&Some(*mint))
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct UserDetails {
+    pub last_claimed_at_ts: u64,
+    pub claimed_amount: u64,
+    pub bump: u8,
+    /// Allocation which proof has been already checked, so next claims can skip it.
+    pub verified_allocation: Option<VerifiedAllocation>,
+    /// Vested tokens which haven't been transferred due to the per transaction limit.
+    pub pending_amount: u64,
+    /// Number of successful claims, counted since migration for older accounts.
+    pub claim_count: u64,
+    pub first_claimed_at_ts: u64,
+    pub account_version: u8,
+}
+
+impl UserDetails {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
+
+    /// State of a user who has never claimed, for simulations without an account.
+    pub fn empty() -> Self {
+        Self {
+            last_claimed_at_ts: 0,
+            claimed_amount: 0,
+            bump: 0,
+            verified_allocation: None,
+            pending_amount: 0,
+            claim_count: 0,
+            first_claimed_at_ts: 0,
+            account_version: ACCOUNT_VERSION,
+        }
+    }
+
+    /// User details are derived per tree, so they are reset by every root update.
+    pub fn find_address(distributor: &Pubkey, merkle_index: u64, user: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                distributor.as_ref(),
+                merkle_index.to_be_bytes().as_ref(),
+                user.as_ref(),
+            ],
+            &crate::ID,
+        )
+    }
+
+    /// Checks the merkle proof unless the same allocation has been already verified
+    /// against the current root. Successful verification is cached.
+    fn verify_allocation(
+        &mut self,
+        root: &MerkleRoot,
+        leaf: [u8; 32],
+        amount: u64,
+        merkle_proof: &[[u8; 32]],
+    ) -> Result<()> {
+        match self.verified_allocation {
+            Some(verified)
+                if verified.merkle_index == root.merkle_index && verified.leaf == leaf =>
+            {
+                sol_log("allocation has been already verified");
+            }
+            _ => {
+                check_proof(leaf, merkle_proof, &root.merkle_root)?;
+
+                self.verified_allocation = Some(VerifiedAllocation {
+                    merkle_index: root.merkle_index,
+                    amount,
+                    leaf,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Claim statistics of a replaced merkle tree.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy)]
+pub struct RoundStats {
+    pub merkle_index: u64,
+    pub merkle_root: [u8; 32],
+    pub total_allocation: u64,
+    pub total_claimed: u64,
+    pub claimers: u64,
+}
+
+/// Escrows a fraction of every claim for a dispute window.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy)]
+pub struct Holdback {
+    pub vault: Pubkey,
+    /// Part of every claim held back, in basis points.
+    pub bps: u64,
+    pub window_sec: u64,
+}
+
+/// Tokens held back from a single claim.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy)]
+pub struct HoldbackDeposit {
+    pub amount: u64,
+    pub release_ts: u64,
+}
+
+/// Held back tokens of a user. It isn't tied to a tree, so deposits
+/// survive root updates, and every deposit is released on its own.
+#[account]
+#[derive(Debug)]
+pub struct HoldbackRecord {
+    pub deposits: [Option<HoldbackDeposit>; 8],
+    pub bump: u8,
+    pub account_version: u8,
+}
+
+impl HoldbackRecord {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
+
+    pub fn find_address(distributor: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[distributor.as_ref(), "holdback".as_ref(), user.as_ref()],
+            &crate::ID,
+        )
+    }
+
+    /// Adds a deposit, returns the release time of the slot it ends up in.
+    fn deposit(&mut self, amount: u64, release_ts: u64) -> u64 {
+        match self.deposits.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(HoldbackDeposit { amount, release_ts }),
+            None => {
+                // once all slots are taken, the latest deposit is the one delayed the least
+                let latest = self
+                    .deposits
+                    .iter_mut()
+                    .flatten()
+                    .max_by_key(|deposit| deposit.release_ts)
+                    .unwrap();
+                latest.amount += amount;
+                latest.release_ts = release_ts;
+            }
+        }
+
+        release_ts
+    }
+
+    /// Removes deposits which release time has come, returns their sum.
+    fn take_released(&mut self, now: u64) -> Result<u64> {
+        require!(self.deposits.iter().any(Option::is_some), NothingToRelease);
+
+        let mut amount = 0;
+        for slot in self.deposits.iter_mut() {
+            if let Some(deposit) = slot {
+                if deposit.release_ts <= now {
+                    amount += deposit.amount;
+                    *slot = None;
+                }
+            }
+        }
+        require!(amount > 0, HoldbackLocked);
+
+        Ok(amount)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy)]
+pub struct VerifiedAllocation {
+    pub merkle_index: u64,
+    pub amount: u64,
+    pub leaf: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+pub struct Period {
+    /// Percentage in Basis Points (BPS). 1% = 100 BPS.
+    /// NOTE: Percentage is for the whole period.
+    pub token_percentage: u64,
+    pub start_ts: u64,
+    pub interval_sec: u64,
+    pub times: u64,
+    /// We should skip this in claim amount calculation
+    /// because it has been claimed outside of this vesting scope.
+    pub airdropped: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+pub struct Vesting {
+    pub schedule: Vec<Period>,
+    /// Once set all periods are considered fully unlocked.
+    pub accelerated_at_ts: Option<u64>,
+    pub loyalty_bonus: Option<LoyaltyBonus>,
+}
+
+/// Rewards users who leave unlocked tokens unclaimed for a while.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy)]
+pub struct LoyaltyBonus {
+    /// How long an unlocked interval has to stay unclaimed.
+    pub delay_sec: u64,
+    /// Bonus on top of the delayed intervals, in basis points.
+    pub bonus_bps: u64,
+    /// Tokens reserved in the vault for bonuses, paid ones are counted in [`ClaimStats`].
+    pub budget: u64,
+}
+
+impl Vesting {
+    /// Fails the same way as `initialize` would for an invalid schedule,
+    /// period starts of a `relative` schedule are offsets from TGE.
+    pub fn new(schedule: Vec<Period>, relative: bool) -> Result<Self> {
+        let s = Self {
+            schedule,
+            accelerated_at_ts: None,
+            loyalty_bonus: None,
+        };
+
+        s.validate(relative)?;
+
+        Ok(s)
+    }
+
+    fn validate(&self, relative: bool) -> Result<()> {
+        require!(self.schedule.len() > 0, EmptySchedule);
+
+        // only the first period of a relative schedule may start right at TGE,
+        // a zero timestamp is never a real start
+        let mut last_start_ts = if relative { None } else { Some(0) };
+        let mut total_percentage = 0;
+
+        for entry in &self.schedule {
+            require!(entry.times > 0, EmptyPeriod);
+            require!(
+                last_start_ts.map_or(true, |ts| ts < entry.start_ts),
+                InvalidScheduleOrder
+            );
+
+            // start_ts + (times * interval_sec)
+            last_start_ts = Some(
+                entry
+                    .times
+                    .checked_mul(entry.interval_sec)
+                    .ok_or(ErrorCode::IntegerOverflow)?
+                    .checked_add(entry.start_ts)
+                    .ok_or(ErrorCode::IntegerOverflow)?,
+            );
+
+            total_percentage += entry.token_percentage;
+        }
+
+        // 100% == 10000 basis points
+        require!(total_percentage == 10000, PercentageDoesntCoverAllTokens);
+
+        Ok(())
+    }
+
+    fn has_started(&self, now: u64) -> bool {
+        let first_period = self.schedule.first().unwrap();
+
+        first_period.start_ts <= now
+    }
+
+    /// Moves all periods which haven't started yet forward by `shift_sec`,
+    /// returns the number of shifted periods.
+    fn shift_not_started(&mut self, now: u64, shift_sec: u64) -> Result<u64> {
+        let mut periods_shifted = 0;
+
+        for period in self.schedule.iter_mut().filter(|p| p.start_ts > now) {
+            period.start_ts = period
+                .start_ts
+                .checked_add(shift_sec)
+                .ok_or(ErrorCode::IntegerOverflow)?;
+            periods_shifted += 1;
+        }
+
+        Ok(periods_shifted)
+    }
+
+    fn apply_change(&mut self, change: Change) {
+        match change {
+            Change::Update { index, period } => {
+                self.schedule[index as usize] = period;
+            }
+            Change::Remove { index } => {
+                self.schedule.remove(index as usize);
+            }
+            Change::Push { period } => {
+                self.schedule.push(period);
+            }
+        }
+    }
+
+    fn bps_available_to_claim(&self, now: u64, last_claimed_at_ts: u64) -> AvailableBps {
+        let mut total_percentage_to_claim = Decimal::ZERO;
+        let mut total_percentage_to_add = Decimal::ZERO;
+        let mut total_percentage_bonus = Decimal::ZERO;
+        let mut percentage_per_period = vec![Decimal::ZERO; self.schedule.len()];
+        let mut last_started = None;
+
+        for (index, period) in self.schedule.iter().enumerate() {
+            let period_end_ts = period.start_ts + period.times * period.interval_sec;
+            // accelerated vesting collapses every period to now
+            let now = match self.accelerated_at_ts {
+                Some(_) => std::cmp::max(now, period_end_ts),
+                None => now,
+            };
+
+            sol_log_64(now, period.start_ts, last_claimed_at_ts, 0, 0);
+
+            if now < period.start_ts {
+                sol_log("too early to claim period");
+                break;
+            }
+            last_started = Some(index);
+
+            if period_end_ts <= last_claimed_at_ts {
+                sol_log("skip since we've already claimed");
+                continue;
+            }
+
+            if period.airdropped {
+                sol_log("this period was airdropped");
+                total_percentage_to_add += Decimal::new(period.token_percentage as i64, 4);
+                percentage_per_period[index] = Decimal::new(period.token_percentage as i64, 4);
+                continue;
+            }
+
+            let last_claimed_at_ts_aligned_by_interval =
+                last_claimed_at_ts - last_claimed_at_ts % period.interval_sec;
+            // intervals after the end of the period don't exist,
+            // so don't count them for users who claimed in the middle of the period
+            let seconds_passed = std::cmp::min(now, period_end_ts)
+                - std::cmp::max(period.start_ts, last_claimed_at_ts_aligned_by_interval);
+            let intervals_passed = seconds_passed / period.interval_sec;
+            let intervals_passed = std::cmp::min(intervals_passed, period.times);
+
+            sol_log_64(
+                last_claimed_at_ts,
+                last_claimed_at_ts_aligned_by_interval,
+                seconds_passed,
+                now,
+                intervals_passed,
+            );
+
+            let percentage_for_intervals = (Decimal::new(period.token_percentage as i64, 4)
+                / Decimal::from_u64(period.times).unwrap())
+                * Decimal::from_u64(intervals_passed).unwrap();
+
+            total_percentage_to_claim += percentage_for_intervals;
+            percentage_per_period[index] = percentage_for_intervals;
+
+            if let Some(loyalty_bonus) = self.loyalty_bonus {
+                // intervals which have stayed unclaimed for at least `delay_sec`
+                let loyal_now = now.saturating_sub(loyalty_bonus.delay_sec);
+                let loyal_from_ts =
+                    std::cmp::max(period.start_ts, last_claimed_at_ts_aligned_by_interval);
+
+                if loyal_now > loyal_from_ts {
+                    let loyal_intervals = (std::cmp::min(loyal_now, period_end_ts) - loyal_from_ts)
+                        / period.interval_sec;
+                    let loyal_intervals = std::cmp::min(loyal_intervals, intervals_passed);
+
+                    total_percentage_bonus += (Decimal::new(period.token_percentage as i64, 4)
+                        / Decimal::from_u64(period.times).unwrap())
+                        * Decimal::from_u64(loyal_intervals).unwrap()
+                        * Decimal::new(loyalty_bonus.bonus_bps as i64, 4);
+                }
+            }
+        }
+
+        AvailableBps {
+            to_claim: total_percentage_to_claim,
+            to_add: total_percentage_to_add,
+            bonus: total_percentage_bonus,
+            per_period: percentage_per_period,
+            last_started,
+        }
+    }
+}
+
+/// Vested percentages (as fractions) available for the user at the moment.
+#[derive(Debug, Clone)]
+struct AvailableBps {
+    to_claim: Decimal,
+    /// Percentage of airdropped periods which should be only accounted.
+    to_add: Decimal,
+    /// Loyalty bonus paid on top of `to_claim`.
+    bonus: Decimal,
+    /// Part of `to_claim` and `to_add` coming from every period of the schedule.
+    per_period: Vec<Decimal>,
+    /// Index of the latest period which has started.
+    last_started: Option<usize>,
+}
+
+impl AvailableBps {
+    /// Splits `claimed` tokens between periods proportionally, the amounts always sum up
+    /// to `claimed`. The rounding remainder (or pending tokens vested by earlier claims)
+    /// goes to the latest period with something to claim.
+    fn split_by_period(&self, allocation: u64, claimed: u64) -> Vec<u64> {
+        let mut amounts = vec![0; self.per_period.len()];
+        let mut distributed = 0;
+        let mut last_index = self.last_started;
+
+        for (index, bps) in self.per_period.iter().enumerate() {
+            if bps.is_zero() {
+                continue;
+            }
+
+            // claims limited by `max_claim_per_tx` take less than vested
+            let amount = (Decimal::from_u64(allocation).unwrap() * bps)
+                .floor()
+                .to_u64()
+                .unwrap();
+            let amount = std::cmp::min(amount, claimed - distributed);
+            amounts[index] = amount;
+            distributed += amount;
+            last_index = Some(index);
+        }
+
+        if let Some(index) = last_index {
+            amounts[index] += claimed - distributed;
+        }
+
+        amounts
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct MerkleDistributor {
+    pub vault_bump: u8,
+    pub vault: Pubkey,
+    pub vesting: Vesting,
+    /// Allows users to mint transferable position NFTs for their allocations.
+    pub position_nfts: bool,
+    pub mint: Pubkey,
+    pub mint_decimals: u8,
+    /// Limits amount of tokens transferred by a single claim,
+    /// the rest stays available for the next claims.
+    pub max_claim_per_tx: Option<u64>,
+    /// Backend key which signs claim tickets, tickets are disabled if it's not set.
+    pub ticket_signer: Option<Pubkey>,
+    pub holdback: Option<Holdback>,
+    pub category: DistributorCategory,
+    /// Set by the owner during incidents, blocks both claims and withdrawals.
+    pub frozen_at_ts: Option<u64>,
+    /// Owners of custodial accounts (e.g. exchange omnibus wallets)
+    /// which are allowed to receive claims on behalf of users.
+    pub custodians: [Option<Pubkey>; 10],
+    /// Set once unclaimed tokens can be withdrawn only by the [`WithdrawalSchedule`].
+    pub withdrawal_schedule: bool,
+    /// Periods of the schedule which have been marked as completed by the crank.
+    pub completed_periods: Vec<bool>,
+    /// Period start times are offsets from `activation_ts` until TGE gets activated.
+    pub relative_schedule: bool,
+    /// Zero until `activate_tge` is called for a relative schedule.
+    pub activation_ts: u64,
+    /// Only holders of an NFT verified in this collection are able to claim.
+    pub gate_collection: Option<Pubkey>,
+    /// Project treasury, the only token account unclaimed tokens can be withdrawn to.
+    pub clawback_destination: Pubkey,
+    pub pending_clawback_destination: Option<PendingClawbackDestination>,
+    /// Set once the remaining allocation moved into `split_into`, claims are blocked.
+    pub stopped: bool,
+    /// Distributor this one has been split from.
+    pub split_from: Option<Pubkey>,
+    pub split_into: Option<Pubkey>,
+    pub account_version: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy)]
+pub struct PendingClawbackDestination {
+    pub destination: Pubkey,
+    pub proposed_at_ts: u64,
+}
+
+/// Kind of the distribution, used by analytics and the claiming portal to group distributors.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistributorCategory {
+    Ido,
+    Airdrop,
+    Team,
+    Advisors,
+}
+
+impl MerkleDistributor {
+    fn new(
+        args: InitializeArgs,
+        vault: &Account<TokenAccount>,
+        mint: &Account<Mint>,
+        clawback_destination: Pubkey,
+    ) -> Result<Self> {
+        Ok(Self {
+            vault_bump: args.vault_bump,
+            vault: vault.key(),
+            mint: mint.key(),
+            mint_decimals: mint.decimals,
+            max_claim_per_tx: args.max_claim_per_tx,
+            ticket_signer: None,
+            holdback: None,
+            completed_periods: vec![false; args.schedule.len()],
+            // schedule should pass validation first
+            vesting: Vesting::new(args.schedule, args.relative_schedule)?,
+            position_nfts: args.position_nfts,
+            category: args.category,
+            frozen_at_ts: None,
+            custodians: [None; 10],
+            withdrawal_schedule: false,
+            relative_schedule: args.relative_schedule,
+            activation_ts: 0,
+            gate_collection: None,
+            clawback_destination,
+            pending_clawback_destination: None,
+            stopped: false,
+            split_from: None,
+            split_into: None,
+            account_version: ACCOUNT_VERSION,
+        })
+    }
+
+    /// Bonus tokens which haven't been paid out of the budget yet, they have to stay in the vault.
+    pub fn bonus_reserve(&self, stats: &ClaimStats) -> u64 {
+        self.vesting.loyalty_bonus.map_or(0, |loyalty_bonus| {
+            loyalty_bonus.budget.saturating_sub(stats.bonus_paid)
+        })
+    }
+
+    /// Period start times of a relative schedule are still unknown.
+    fn tge_pending(&self) -> bool {
+        self.relative_schedule && self.activation_ts == 0
+    }
+
+    /// The freeze can't be lifted earlier, so a compromised key can't freeze and drain at once.
+    pub const FREEZE_TIMELOCK_SEC: u64 = 2 * 24 * 60 * 60;
+    /// Gives users time to notice a changed treasury before it can receive withdrawals.
+    pub const CLAWBACK_TIMELOCK_SEC: u64 = 7 * 24 * 60 * 60;
+
+    /// Amounts unlocked for the user with the `allocation` since the last claim,
+    /// excluding tokens left pending by the per transaction limit.
+    pub fn claimable_amounts(
+        &self,
+        user_details: &UserDetails,
+        allocation: u64,
+        now: u64,
+    ) -> ClaimableAmounts {
+        let available = self
+            .vesting
+            .bps_available_to_claim(now, user_details.last_claimed_at_ts);
+        let amount = (Decimal::from_u64(allocation).unwrap() * available.to_claim)
+            .ceil()
+            .to_u64()
+            .unwrap();
+        // this amount is from airdropped periods
+        let amount_to_add = (Decimal::from_u64(allocation).unwrap() * available.to_add)
+            .ceil()
+            .to_u64()
+            .unwrap();
+        // amounts are rounded up in base units, which is noticeable for mints with few decimals,
+        // so never let rounding accumulate above the allocation
+        let remaining = allocation
+            .saturating_sub(user_details.claimed_amount)
+            .saturating_sub(user_details.pending_amount);
+        let amount_to_add = std::cmp::min(amount_to_add, remaining);
+        let amount = std::cmp::min(amount, remaining - amount_to_add);
+
+        let bonus = (Decimal::from_u64(allocation).unwrap() * available.bonus)
+            .floor()
+            .to_u64()
+            .unwrap();
+
+        ClaimableAmounts {
+            amount,
+            amount_to_add,
+            bonus,
+            allocation,
+            available,
+        }
+    }
+
+    /// Time of the next interval unlock after `now`, if any is left.
+    pub fn next_unlock_ts(&self, now: u64) -> Option<u64> {
+        if self.tge_pending() || self.vesting.accelerated_at_ts.is_some() {
+            return None;
+        }
+
+        self.vesting
+            .schedule
+            .iter()
+            .filter(|period| !period.airdropped)
+            .filter_map(|period| {
+                let intervals_passed = now.saturating_sub(period.start_ts) / period.interval_sec;
+                let next_interval = if now < period.start_ts {
+                    1
+                } else {
+                    intervals_passed + 1
+                };
+
+                if next_interval > period.times {
+                    None
+                } else {
+                    Some(period.start_ts + next_interval * period.interval_sec)
+                }
+            })
+            .min()
+    }
+
+    pub fn space_required(periods: &[Period]) -> usize {
+        8 + std::mem::size_of::<Self>()
+            + periods.len() * (std::mem::size_of::<Period>() + std::mem::size_of::<bool>())
+    }
+}
+
+/// Active merkle tree of a distributor. It's kept apart from the distributor,
+/// so replacing the tree doesn't write the account every claim reads.
+#[account]
+#[derive(Debug)]
+pub struct MerkleRoot {
+    pub merkle_index: u64,
+    pub merkle_root: [u8; 32],
+    pub paused: bool,
+    /// Sum of all allocations in the current tree.
+    pub total_allocation: u64,
+    /// Depth of the current tree, longer proofs are rejected before verification.
+    pub max_proof_len: Option<u8>,
+    /// Statistics of the latest replaced trees, indexed by `merkle_index % 8`.
+    pub round_history: [Option<RoundStats>; 8],
+    pub bump: u8,
+    pub account_version: u8,
+}
+
+impl MerkleRoot {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
+
+    fn new(args: &InitializeArgs, bump: u8) -> Self {
+        Self {
+            merkle_index: 0,
+            merkle_root: args.merkle_root,
+            paused: false,
+            total_allocation: args.total_allocation,
+            max_proof_len: args.max_proof_len,
+            round_history: [None; 8],
+            bump,
+            account_version: ACCOUNT_VERSION,
+        }
+    }
+
+    pub fn find_address(distributor: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[distributor.as_ref(), "root".as_ref()], &crate::ID)
+    }
+
+    fn check_proof_len(&self, merkle_proof: &[[u8; 32]]) -> Result<()> {
+        if let Some(max_proof_len) = self.max_proof_len {
+            require!(merkle_proof.len() <= max_proof_len as usize, ProofTooLong);
+        }
+
+        Ok(())
+    }
+
+    /// Keeps statistics of the current tree before it gets replaced.
+    fn archive_round(&mut self, stats: &ClaimStats) {
+        let slot = (self.merkle_index % self.round_history.len() as u64) as usize;
+
+        self.round_history[slot] = Some(RoundStats {
+            merkle_index: self.merkle_index,
+            merkle_root: self.merkle_root,
+            total_allocation: self.total_allocation,
+            total_claimed: stats.total_claimed,
+            claimers: stats.claimers,
+        });
+    }
+}
+
+/// Counters updated by every claim. They are kept apart from the distributor,
+/// so claims only read it and don't contend with each other on it.
+#[account]
+#[derive(Debug)]
+pub struct ClaimStats {
+    /// Tokens claimed from the current tree, never exceeds `total_allocation` of the root.
+    pub total_claimed: u64,
+    /// Number of users who claimed from the current tree.
+    pub claimers: u64,
+    /// Amount of tokens claimed against every period of the schedule.
+    pub claimed_per_period: Vec<u64>,
+    /// Loyalty bonuses paid out of the budget.
+    pub bonus_paid: u64,
+    /// Tokens claimed with tickets, they count towards `total_allocation` too.
+    pub tickets_claimed: u64,
+    pub bump: u8,
+    pub account_version: u8,
+}
+
+impl ClaimStats {
+    fn new(periods: &[Period], bump: u8) -> Self {
+        Self {
+            total_claimed: 0,
+            claimers: 0,
+            claimed_per_period: vec![0; periods.len()],
+            bonus_paid: 0,
+            tickets_claimed: 0,
+            bump,
+            account_version: ACCOUNT_VERSION,
+        }
+    }
+
+    pub fn space_required(periods: &[Period]) -> usize {
+        8 + std::mem::size_of::<Self>() + periods.len() * std::mem::size_of::<u64>()
+    }
+
+    pub fn find_address(distributor: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[distributor.as_ref(), "stats".as_ref()], &crate::ID)
+    }
+
+    /// Tokens claimed against `total_allocation`, both from the tree and with tickets.
+    pub fn claimed_with_tickets(&self) -> u64 {
+        self.total_claimed + self.tickets_claimed
+    }
+}
+
+/// Result of [`MerkleDistributor::claimable_amounts`].
+#[derive(Debug, Clone)]
+pub struct ClaimableAmounts {
+    /// Vested tokens to be transferred.
+    pub amount: u64,
+    /// Tokens of airdropped periods, only accounted as claimed.
+    pub amount_to_add: u64,
+    /// Loyalty bonus before capping by the remaining budget.
+    pub bonus: u64,
+    allocation: u64,
+    available: AvailableBps,
+}
+
+impl ClaimableAmounts {
+    /// Part of `claimed` tokens coming from every period of the schedule.
+    pub fn split_by_period(&self, claimed: u64) -> Vec<u64> {
+        self.available.split_by_period(self.allocation, claimed)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct InitUserDetails<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK:
+    user: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = UserDetails::LEN,
+        seeds = [
+            distributor.key().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump,
+    )]
+    user_details: Account<'info, UserDetails>,
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateUserDetails<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK:
+    user: AccountInfo<'info>,
+    /// CHECK: can't be deserialized until it grows, the discriminator is checked instead
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [
+            distributor.key().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump,
+    )]
+    user_details: AccountInfo<'info>,
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+
+    system_program: Program<'info, System>,
+}
+
+/// Layout of distributors created before the merkle root moved into [`MerkleRoot`].
+#[derive(AnchorDeserialize)]
+struct LegacyMerkleDistributor {
+    merkle_index: u64,
+    merkle_root: [u8; 32],
+    paused: bool,
+    vault_bump: u8,
+    vault: Pubkey,
+    vesting: LegacyVesting,
+}
+
+#[derive(AnchorDeserialize)]
+struct LegacyVesting {
+    schedule: Vec<Period>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct MigrateDistributorArgs {
+    /// Tokens left unclaimed in the current tree.
+    pub total_allocation: u64,
+    pub clawback_destination: Pubkey,
+    pub category: DistributorCategory,
+}
+
+#[derive(Accounts)]
+#[instruction(args: MigrateDistributorArgs)]
+pub struct MigrateDistributor<'info> {
+    /// CHECK: can't be deserialized until it's rewritten, the discriminator is checked instead
+    #[account(mut, owner = crate::ID)]
+    distributor: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = owner,
+        space = MerkleRoot::LEN,
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump,
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        init,
+        payer = owner,
+        space = ClaimStats::space_required(&[]),
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump,
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    vault: Account<'info, TokenAccount>,
+    #[account(constraint = mint.key() == vault.mint @ ErrorCode::InvalidMint)]
+    mint: Account<'info, Mint>,
+    #[account(
+        address = args.clawback_destination,
+        constraint = clawback_destination.mint == mint.key() @ ErrorCode::InvalidMint
+    )]
+    clawback_destination: Account<'info, TokenAccount>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = Config::LEN,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump,
+    )]
+    config: Account<'info, Config>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize)]
+pub struct VersionInfo {
+    /// Version of the deployed build.
+    pub program: u32,
+    /// Version recorded in the config.
+    pub config: u32,
+}
+
+#[derive(Accounts)]
+pub struct SetVersion<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: can't be deserialized until it grows, the discriminator is checked instead
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump
+    )]
+    config: AccountInfo<'info>,
+    #[account(mut)]
+    owner: Signer<'info>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetVersion<'info> {
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize)]
+pub struct InitializeArgs {
+    pub vault_bump: u8,
+    pub merkle_root: [u8; 32],
+    pub schedule: Vec<Period>,
+    pub position_nfts: bool,
+    pub max_claim_per_tx: Option<u64>,
+    pub category: DistributorCategory,
+    pub total_allocation: u64,
+    /// Period start times are offsets from TGE set later by `activate_tge`.
+    pub relative_schedule: bool,
+    /// Depth of the merkle tree, longer proofs are rejected.
+    pub max_proof_len: Option<u8>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: InitializeArgs)]
+pub struct Initialize<'info> {
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin_or_owner,
+        space = MerkleDistributor::space_required(&args.schedule),
+    )]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        init,
+        payer = admin_or_owner,
+        space = MerkleRoot::LEN,
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump,
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        init,
+        payer = admin_or_owner,
+        space = ClaimStats::space_required(&args.schedule),
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump,
+    )]
+    stats: Account<'info, ClaimStats>,
+
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = args.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == mint.key() @ ErrorCode::InvalidMint
+    )]
+    vault: Account<'info, TokenAccount>,
+    #[account(constraint = config.is_mint_allowed(&mint.key()) @ ErrorCode::MintNotAllowed)]
+    mint: Account<'info, Mint>,
+    #[account(constraint = clawback_destination.mint == mint.key() @ ErrorCode::InvalidMint)]
+    clawback_destination: Account<'info, TokenAccount>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize)]
+pub struct SplitDistributorArgs {
+    /// Tokens moved into the new vault.
+    amount: u64,
+    /// Merkle tree and schedule of the remaining allocations.
+    distributor: InitializeArgs,
+}
+
+#[derive(Accounts)]
+#[instruction(args: SplitDistributorArgs)]
+pub struct SplitDistributor<'info> {
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
+    )]
+    vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = MerkleDistributor::space_required(&args.distributor.schedule),
+    )]
+    new_distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        init,
+        payer = owner,
+        space = MerkleRoot::LEN,
+        seeds = [
+            new_distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump,
+    )]
+    new_root: Account<'info, MerkleRoot>,
+    #[account(
+        init,
+        payer = owner,
+        space = ClaimStats::space_required(&args.distributor.schedule),
+        seeds = [
+            new_distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump,
+    )]
+    new_stats: Account<'info, ClaimStats>,
+    /// CHECK:
+    #[account(
+        seeds = [
+            new_distributor.key().as_ref()
+        ],
+        bump = args.distributor.vault_bump
+    )]
+    new_vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = new_vault.owner == new_vault_authority.key(),
+        constraint = new_vault.mint == distributor.mint @ ErrorCode::InvalidMint
+    )]
+    new_vault: Account<'info, TokenAccount>,
+    #[account(constraint = mint.key() == distributor.mint @ ErrorCode::InvalidMint)]
+    mint: Account<'info, Mint>,
+
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+/// Collects factory fees.
+#[account]
+pub struct Treasury {
+    /// Lamports collected as fees.
+    total_collected: u64,
+    bump: u8,
+    /// Lamports withdrawn by the owner.
+    total_withdrawn: u64,
+    account_version: u8,
+}
+
+impl Treasury {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
+}
+
+/// Fee tokens of one mint withdrawn from the treasury.
+#[account]
+pub struct FeeTokenStats {
+    mint: Pubkey,
+    /// Tokens withdrawn by the owner.
+    total_withdrawn: u64,
+    bump: u8,
+    account_version: u8,
+}
+
+impl FeeTokenStats {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
+}
+
+#[derive(Accounts)]
+pub struct InitTreasury<'info> {
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(
+        init,
+        payer = owner,
+        space = Treasury::LEN,
+        seeds = [
+            "treasury".as_ref()
+        ],
+        bump,
+    )]
+    treasury: Account<'info, Treasury>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            "treasury".as_ref()
+        ],
+        bump = treasury.bump
+    )]
+    treasury: Account<'info, Treasury>,
+    /// CHECK: any account could receive lamports
+    #[account(mut)]
+    target: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFeeTokens<'info> {
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(
+        seeds = [
+            "treasury".as_ref()
+        ],
+        bump = treasury.bump
+    )]
+    treasury: Account<'info, Treasury>,
+    #[account(
+        mut,
+        constraint = fee_wallet.owner == treasury.key()
+    )]
+    fee_wallet: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_wallet.mint == target_wallet.mint
+    )]
+    target_wallet: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = FeeTokenStats::LEN,
+        seeds = [
+            "treasury".as_ref(),
+            fee_wallet.mint.as_ref(),
+        ],
+        bump,
+    )]
+    fee_token_stats: Account<'info, FeeTokenStats>,
+
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetCreationFee<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewSchedule<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AllocationProof {
+    wallet: Pubkey,
+    amount: u64,
+    merkle_proof: Vec<[u8; 32]>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct VerifyProofsArgs {
+    allocations: Vec<AllocationProof>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyProofs<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateRootArgs {
+    pub merkle_root: [u8; 32],
+    pub unpause: bool,
+    /// Sum of all allocations in the new tree.
+    pub total_allocation: u64,
+    /// Depth of the new tree.
+    pub max_proof_len: Option<u8>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRoot<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+    #[account(
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault,
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint
+    )]
+    vault: Account<'info, TokenAccount>,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize)]
+pub enum Change {
+    Update { index: u64, period: Period },
+    Remove { index: u64 },
+    Push { period: Period },
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize)]
+pub struct UpdateScheduleArgs {
+    changes: Vec<Change>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSchedule<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ActivateTge<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CompletePeriod<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+}
+
+#[derive(Accounts)]
+pub struct ShiftSchedule<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeVault<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeClawbackDestination<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(constraint = clawback_destination.mint == distributor.mint @ ErrorCode::InvalidMint)]
+    clawback_destination: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyClawbackDestination<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnfreezeVault<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxClaimPerTx<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetLoyaltyBonusArgs {
+    delay_sec: u64,
+    bonus_bps: u64,
+    /// Total bonus tokens which can be paid, including already paid ones.
+    budget: u64,
+}
+
+#[derive(Accounts)]
+pub struct SetLoyaltyBonus<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault,
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint
+    )]
+    vault: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetGateCollection<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTicketSigner<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = admin_or_owner.key() == config.owner ||
+            config.admins.contains(&Some(admin_or_owner.key()))
+            @ ErrorCode::NotAdminOrOwner
+    )]
+    admin_or_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    admin: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    admin: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddRelayer<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    relayer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveRelayer<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    relayer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddCustodian<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    custodian: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveCustodian<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    custodian: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddAllowedMint<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    mint: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAllowedMint<'info> {
+    #[account(
+        mut,
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    /// CHECK:
+    mint: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTokens<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
+    )]
+    vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = target_wallet.key() == distributor.clawback_destination
+            @ ErrorCode::InvalidClawbackDestination
+    )]
+    target_wallet: Account<'info, TokenAccount>,
+
+    token_program: Program<'info, Token>,
+}
+
+/// Releases unclaimed tokens to the project gradually instead of in one shot.
+#[account]
+pub struct WithdrawalSchedule {
+    vesting: Vesting,
+    /// Amount of tokens released by the whole schedule.
+    total_amount: u64,
+    withdrawn_amount: u64,
+    bump: u8,
+    account_version: u8,
+}
+
+impl WithdrawalSchedule {
+    pub fn space_required(periods: &[Period]) -> usize {
+        8 + std::mem::size_of::<Self>() + periods.len() * std::mem::size_of::<Period>()
+    }
+
+    fn available_to_withdraw(&self, now: u64) -> u64 {
+        let available = self.vesting.bps_available_to_claim(now, 0);
+        let unlocked = (Decimal::from_u64(self.total_amount).unwrap()
+            * (available.to_claim + available.to_add))
+            .floor()
+            .to_u64()
+            .unwrap();
+
+        unlocked.saturating_sub(self.withdrawn_amount)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetWithdrawalScheduleArgs {
+    schedule: Vec<Period>,
+    total_amount: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(args: SetWithdrawalScheduleArgs)]
+pub struct SetWithdrawalSchedule<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(
+        init,
+        payer = owner,
+        space = WithdrawalSchedule::space_required(&args.schedule),
+        seeds = [
+            distributor.key().as_ref(),
+            "withdrawal_schedule".as_ref(),
+        ],
+        bump,
+    )]
+    withdrawal_schedule: Account<'info, WithdrawalSchedule>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawVestedTokens<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "withdrawal_schedule".as_ref(),
+        ],
+        bump = withdrawal_schedule.bump
+    )]
+    withdrawal_schedule: Account<'info, WithdrawalSchedule>,
+
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
+    )]
+    vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = target_wallet.key() == distributor.clawback_destination
+            @ ErrorCode::InvalidClawbackDestination
+    )]
+    target_wallet: Account<'info, TokenAccount>,
+
+    token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverToken<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
+
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    /// Token account of the vault authority holding stray tokens,
+    /// the real vaults can't be drained this way.
+    #[account(
+        mut,
+        constraint = source.owner == vault_authority.key(),
+        constraint = source.mint != distributor.mint
+            @ ErrorCode::CannotRecoverDistributionMint
+    )]
+    source: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = source.mint == target_wallet.mint
+    )]
+    target_wallet: Account<'info, TokenAccount>,
+
+    token_program: Program<'info, Token>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClaimArgs {
+    pub amount: u64,
+    pub merkle_proof: Vec<[u8; 32]>,
+    /// Explicitly allows to claim into a token account not owned by the claimer,
+    /// the account has to be owned by one of the approved custodians.
+    pub custodial_destination: bool,
+}
+
+#[derive(Accounts)]
+#[instruction(args: ClaimArgs)]
+pub struct Claim<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump = user_details.bump
+    )]
+    user_details: Account<'info, UserDetails>,
+
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
+    )]
+    vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = target_wallet.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = target_wallet.owner == user.key() || (args.custodial_destination
+            && distributor.custodians.contains(&Some(target_wallet.owner)))
+            @ ErrorCode::ForeignTargetWallet
+    )]
+    target_wallet: Account<'info, TokenAccount>,
 
-impl MerkleDistributor {
-    pub fn space_required(periods: &[Period]) -> usize {
-        8 + std::mem::size_of::<Self>() + periods.len() * std::mem::size_of::<Period>()
-    }
+    token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(bump: u8)]
-pub struct InitUserDetails<'info> {
-    #[account(mut)]
-    payer: Signer<'info>,
-    /// CHECK:
+#[instruction(args: ClaimArgs)]
+pub struct ClaimOnBehalf<'info> {
+    distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    #[account(
+        seeds = [
+            "config".as_ref()
+        ],
+        bump = config.bump
+    )]
+    config: Account<'info, Config>,
+    #[account(
+        constraint = config.relayers.contains(&Some(relayer.key()))
+            @ ErrorCode::NotRelayer
+    )]
+    relayer: Signer<'info>,
+    /// CHECK: the owner of the allocation, used only for derivation and proof
     user: AccountInfo<'info>,
     #[account(
-        init,
-        payer = payer,
-        space = UserDetails::LEN,
+        mut,
         seeds = [
             distributor.key().as_ref(),
-            distributor.merkle_index.to_be_bytes().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
             user.key().as_ref(),
         ],
-        bump,
+        bump = user_details.bump
     )]
     user_details: Account<'info, UserDetails>,
-    distributor: Account<'info, MerkleDistributor>,
 
-    system_program: Program<'info, System>,
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
+    )]
+    vault: Account<'info, TokenAccount>,
+    // relayers can't choose custodial destinations, tokens always go to the user
+    #[account(
+        mut,
+        constraint = target_wallet.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = target_wallet.owner == user.key() @ ErrorCode::ForeignTargetWallet
+    )]
+    target_wallet: Account<'info, TokenAccount>,
+
+    token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(bump: u8)]
-pub struct InitializeConfig<'info> {
+pub struct AccelerateVesting<'info> {
     #[account(mut)]
-    owner: Signer<'info>,
-
+    distributor: Account<'info, MerkleDistributor>,
     #[account(
-        init,
-        payer = owner,
-        space = Config::LEN,
         seeds = [
             "config".as_ref()
         ],
-        bump,
+        bump = config.bump
     )]
     config: Account<'info, Config>,
-
-    system_program: Program<'info, System>,
+    #[account(
+        constraint = owner.key() == config.owner
+            @ ErrorCode::NotOwner
+    )]
+    owner: Signer<'info>,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize)]
-pub struct InitializeArgs {
-    pub vault_bump: u8,
-    pub merkle_root: [u8; 32],
-    pub schedule: Vec<Period>,
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct InitHoldbackArgs {
+    bps: u64,
+    window_sec: u64,
 }
 
 #[derive(Accounts)]
-#[instruction(args: InitializeArgs)]
-pub struct Initialize<'info> {
+pub struct InitHoldback<'info> {
+    #[account(mut)]
+    distributor: Account<'info, MerkleDistributor>,
     #[account(
         seeds = [
             "config".as_ref()
         ],
-        bump
+        bump = config.bump
     )]
     config: Account<'info, Config>,
     #[account(
@@ -517,147 +3924,280 @@ pub struct Initialize<'info> {
     )]
     admin_or_owner: Signer<'info>,
 
-    #[account(
-        init,
-        payer = admin_or_owner,
-        space = MerkleDistributor::space_required(&args.schedule),
-    )]
-    distributor: Account<'info, MerkleDistributor>,
-
     /// CHECK:
     #[account(
         seeds = [
             distributor.key().as_ref()
         ],
-        bump = args.vault_bump
+        bump = distributor.vault_bump
     )]
     vault_authority: AccountInfo<'info>,
-    #[account(constraint = vault.owner == vault_authority.key())]
-    vault: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = admin_or_owner,
+        seeds = [
+            distributor.key().as_ref(),
+            "holdback".as_ref(),
+        ],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority,
+    )]
+    holdback_vault: Account<'info, TokenAccount>,
+    #[account(constraint = mint.key() == distributor.mint @ ErrorCode::InvalidMint)]
+    mint: Account<'info, Mint>,
 
     system_program: Program<'info, System>,
+    token_program: Program<'info, Token>,
+    rent: Sysvar<'info, Rent>,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct UpdateRootArgs {
-    merkle_root: [u8; 32],
-    unpause: bool,
+#[derive(Accounts)]
+pub struct InitHoldbackRecord<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK:
+    user: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = HoldbackRecord::LEN,
+        seeds = [
+            distributor.key().as_ref(),
+            "holdback".as_ref(),
+            user.key().as_ref(),
+        ],
+        bump,
+    )]
+    holdback_record: Account<'info, HoldbackRecord>,
+    distributor: Account<'info, MerkleDistributor>,
+    system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateRoot<'info> {
-    #[account(mut)]
+pub struct ReleaseHoldback<'info> {
     distributor: Account<'info, MerkleDistributor>,
+    user: Signer<'info>,
     #[account(
+        mut,
         seeds = [
-            "config".as_ref()
+            distributor.key().as_ref(),
+            "holdback".as_ref(),
+            user.key().as_ref(),
         ],
-        bump = config.bump
+        bump = holdback_record.bump
     )]
-    config: Account<'info, Config>,
+    holdback_record: Account<'info, HoldbackRecord>,
+
+    /// CHECK:
     #[account(
-        constraint = admin_or_owner.key() == config.owner ||
-            config.admins.contains(&Some(admin_or_owner.key()))
-            @ ErrorCode::NotAdminOrOwner
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
     )]
-    admin_or_owner: Signer<'info>,
-
-    clock: Sysvar<'info, Clock>,
-}
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "holdback".as_ref(),
+        ],
+        bump,
+    )]
+    holdback_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = target_wallet.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = target_wallet.owner == user.key() @ ErrorCode::ForeignTargetWallet
+    )]
+    target_wallet: Account<'info, TokenAccount>,
 
-#[derive(AnchorDeserialize, AnchorSerialize)]
-pub enum Change {
-    Update { index: u64, period: Period },
-    Remove { index: u64 },
-    Push { period: Period },
+    token_program: Program<'info, Token>,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize)]
-pub struct UpdateScheduleArgs {
-    changes: Vec<Change>,
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct MintPositionArgs {
+    amount: u64,
+    merkle_proof: Vec<[u8; 32]>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateSchedule<'info> {
-    #[account(mut)]
+pub struct MintPosition<'info> {
     distributor: Account<'info, MerkleDistributor>,
     #[account(
         seeds = [
-            "config".as_ref()
+            distributor.key().as_ref(),
+            "root".as_ref(),
         ],
-        bump = config.bump
+        bump = root.bump
     )]
-    config: Account<'info, Config>,
+    root: Account<'info, MerkleRoot>,
+    #[account(mut)]
+    user: Signer<'info>,
     #[account(
-        constraint = admin_or_owner.key() == config.owner ||
-            config.admins.contains(&Some(admin_or_owner.key()))
-            @ ErrorCode::NotAdminOrOwner
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump = user_details.bump
     )]
-    admin_or_owner: Signer<'info>,
+    user_details: Account<'info, UserDetails>,
+
+    /// CHECK:
+    #[account(
+        seeds = [
+            distributor.key().as_ref()
+        ],
+        bump = distributor.vault_bump
+    )]
+    vault_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            distributor.key().as_ref(),
+            "position".as_ref(),
+            user.key().as_ref(),
+        ],
+        bump,
+        mint::decimals = 0,
+        mint::authority = vault_authority,
+    )]
+    position_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = user,
+        token::mint = position_mint,
+        token::authority = user,
+    )]
+    position_token_account: Account<'info, TokenAccount>,
 
-    clock: Sysvar<'info, Clock>,
+    system_program: Program<'info, System>,
+    token_program: Program<'info, Token>,
+    rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct SetPaused<'info> {
-    #[account(mut)]
+#[instruction(args: ClaimArgs)]
+pub struct ClaimPosition<'info> {
     distributor: Account<'info, MerkleDistributor>,
     #[account(
         seeds = [
-            "config".as_ref()
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            "stats".as_ref(),
+        ],
+        bump = stats.bump
+    )]
+    stats: Account<'info, ClaimStats>,
+    holder: Signer<'info>,
+    /// CHECK: the original owner of the allocation, used only for derivation and proof
+    user: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [
+            distributor.key().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
+            user.key().as_ref(),
         ],
-        bump = config.bump
-    )]
-    config: Account<'info, Config>,
-    #[account(
-        constraint = admin_or_owner.key() == config.owner ||
-            config.admins.contains(&Some(admin_or_owner.key()))
-            @ ErrorCode::NotAdminOrOwner
+        bump = user_details.bump
     )]
-    admin_or_owner: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct AddAdmin<'info> {
+    user_details: Account<'info, UserDetails>,
     #[account(
         mut,
         seeds = [
-            "config".as_ref()
+            distributor.key().as_ref(),
+            "position".as_ref(),
+            user.key().as_ref(),
         ],
-        bump = config.bump
+        bump
     )]
-    config: Account<'info, Config>,
+    position_mint: Account<'info, Mint>,
     #[account(
-        constraint = owner.key() == config.owner
-            @ ErrorCode::NotOwner
+        mut,
+        constraint = position_token_account.mint == position_mint.key() &&
+            position_token_account.owner == holder.key() &&
+            position_token_account.amount == 1
+            @ ErrorCode::NotPositionHolder
     )]
-    owner: Signer<'info>,
-    /// CHECK:
-    admin: AccountInfo<'info>,
-}
+    position_token_account: Account<'info, TokenAccount>,
 
-#[derive(Accounts)]
-pub struct RemoveAdmin<'info> {
+    /// CHECK:
     #[account(
-        mut,
         seeds = [
-            "config".as_ref()
+            distributor.key().as_ref()
         ],
-        bump = config.bump
+        bump = distributor.vault_bump
     )]
-    config: Account<'info, Config>,
+    vault_authority: AccountInfo<'info>,
     #[account(
-        constraint = owner.key() == config.owner
-            @ ErrorCode::NotOwner
+        mut,
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
     )]
-    owner: Signer<'info>,
-    /// CHECK:
-    admin: AccountInfo<'info>,
+    vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = target_wallet.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = target_wallet.owner == holder.key() || (args.custodial_destination
+            && distributor.custodians.contains(&Some(target_wallet.owner)))
+            @ ErrorCode::ForeignTargetWallet
+    )]
+    target_wallet: Account<'info, TokenAccount>,
+
+    token_program: Program<'info, Token>,
+}
+
+/// Audit log entry of a single `repair_user_details` call.
+#[account]
+pub struct RepairRecord {
+    user_details: Pubkey,
+    repaired_by: Pubkey,
+    old_claimed_amount: u64,
+    claimed_amount: u64,
+    old_last_claimed_at_ts: u64,
+    last_claimed_at_ts: u64,
+    repaired_at_ts: u64,
+    bump: u8,
+    account_version: u8,
+}
+
+impl RepairRecord {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RepairUserDetailsArgs {
+    /// Support ticket number or any other unique id of the repair.
+    repair_id: u64,
+    claimed_amount: u64,
+    last_claimed_at_ts: u64,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawTokens<'info> {
+#[instruction(args: RepairUserDetailsArgs)]
+pub struct RepairUserDetails<'info> {
     distributor: Account<'info, MerkleDistributor>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
     #[account(
         seeds = [
             "config".as_ref()
@@ -666,54 +4206,110 @@ pub struct WithdrawTokens<'info> {
     )]
     config: Account<'info, Config>,
     #[account(
+        mut,
         constraint = owner.key() == config.owner
             @ ErrorCode::NotOwner
     )]
     owner: Signer<'info>,
-
-    /// CHECK:
+    /// CHECK: the owner of the repaired details, used only for derivation
+    user: AccountInfo<'info>,
     #[account(
+        mut,
         seeds = [
-            distributor.key().as_ref()
+            distributor.key().as_ref(),
+            root.merkle_index.to_be_bytes().as_ref(),
+            user.key().as_ref(),
         ],
-        bump = distributor.vault_bump
-    )]
-    vault_authority: AccountInfo<'info>,
-    #[account(
-        mut,
-        constraint = vault.owner == vault_authority.key()
+        bump = user_details.bump
     )]
-    vault: Account<'info, TokenAccount>,
+    user_details: Account<'info, UserDetails>,
     #[account(
-        mut,
-        constraint = vault.mint == target_wallet.mint
+        init,
+        payer = owner,
+        space = RepairRecord::LEN,
+        seeds = [
+            user_details.key().as_ref(),
+            "repair".as_ref(),
+            args.repair_id.to_be_bytes().as_ref(),
+        ],
+        bump,
     )]
-    target_wallet: Account<'info, TokenAccount>,
+    repair_record: Account<'info, RepairRecord>,
 
-    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+}
+
+/// Keeps a redeemed claim ticket, so it can't be used twice.
+#[account]
+pub struct TicketUsage {
+    user: Pubkey,
+    amount: u64,
+    claimed_at_ts: u64,
+    bump: u8,
+    account_version: u8,
+}
+
+impl TicketUsage {
+    pub const LEN: usize = 8 + std::mem::size_of::<Self>();
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct ClaimArgs {
+pub struct ClaimTicketArgs {
+    ticket_id: u64,
     amount: u64,
-    merkle_proof: Vec<[u8; 32]>,
+    expires_at_ts: u64,
+}
+
+impl ClaimTicketArgs {
+    /// Message signed by the ticket signer:
+    /// distributor (32) | user (32) | ticket_id (8) | amount (8) | expires_at_ts (8),
+    /// integers are big endian.
+    fn message(&self, distributor: &Pubkey, user: &Pubkey) -> Vec<u8> {
+        let mut message = Vec::with_capacity(88);
+        message.extend_from_slice(distributor.as_ref());
+        message.extend_from_slice(user.as_ref());
+        message.extend_from_slice(&self.ticket_id.to_be_bytes());
+        message.extend_from_slice(&self.amount.to_be_bytes());
+        message.extend_from_slice(&self.expires_at_ts.to_be_bytes());
+        message
+    }
 }
 
 #[derive(Accounts)]
-#[instruction(args: ClaimArgs)]
-pub struct Claim<'info> {
+#[instruction(args: ClaimTicketArgs)]
+pub struct ClaimTicket<'info> {
     distributor: Account<'info, MerkleDistributor>,
-    user: Signer<'info>,
+    #[account(
+        seeds = [
+            distributor.key().as_ref(),
+            "root".as_ref(),
+        ],
+        bump = root.bump
+    )]
+    root: Account<'info, MerkleRoot>,
     #[account(
         mut,
         seeds = [
             distributor.key().as_ref(),
-            distributor.merkle_index.to_be_bytes().as_ref(),
-            user.key().as_ref(),
+            "stats".as_ref(),
         ],
-        bump = user_details.bump
+        bump = stats.bump
     )]
-    user_details: Account<'info, UserDetails>,
+    stats: Account<'info, ClaimStats>,
+    #[account(mut)]
+    user: Signer<'info>,
+    #[account(
+        init,
+        payer = user,
+        space = TicketUsage::LEN,
+        seeds = [
+            distributor.key().as_ref(),
+            "ticket".as_ref(),
+            args.ticket_id.to_be_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    ticket_usage: Account<'info, TicketUsage>,
 
     /// CHECK:
     #[account(
@@ -725,17 +4321,490 @@ pub struct Claim<'info> {
     vault_authority: AccountInfo<'info>,
     #[account(
         mut,
-        constraint = vault.owner == vault_authority.key()
+        constraint = vault.owner == vault_authority.key(),
+        constraint = vault.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = vault.key() == distributor.vault @ ErrorCode::InvalidVault
     )]
     vault: Account<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = vault.mint == target_wallet.mint
+        constraint = target_wallet.mint == distributor.mint @ ErrorCode::InvalidMint,
+        constraint = target_wallet.owner == user.key() @ ErrorCode::ForeignTargetWallet
     )]
     target_wallet: Account<'info, TokenAccount>,
 
+    /// CHECK:
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    instructions: AccountInfo<'info>,
+    system_program: Program<'info, System>,
     token_program: Program<'info, Token>,
-    clock: Sysvar<'info, Clock>,
+}
+
+/// Checks that ed25519 program instruction `data` verifies exactly one signature
+/// of `signer` over `message`, with all the data inlined into the instruction itself.
+fn check_ticket_signature(data: &[u8], signer: &Pubkey, message: &[u8]) -> Result<()> {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+    const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+    require!(
+        data.len() >= OFFSETS_START + OFFSETS_LEN && data[0] == 1,
+        InvalidTicketSignature
+    );
+
+    let read_u16 = |position: usize| {
+        let offset = OFFSETS_START + position * 2;
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    };
+    let public_key_offset = read_u16(2) as usize;
+    let message_offset = read_u16(4) as usize;
+    let message_size = read_u16(5) as usize;
+
+    require!(
+        read_u16(1) == CURRENT_INSTRUCTION
+            && read_u16(3) == CURRENT_INSTRUCTION
+            && read_u16(6) == CURRENT_INSTRUCTION,
+        InvalidTicketSignature
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(ErrorCode::InvalidTicketSignature)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(ErrorCode::InvalidTicketSignature)?;
+
+    require!(public_key == signer.as_ref(), InvalidTicketSignature);
+    require!(signed_message == message, InvalidTicketSignature);
+
+    Ok(())
+}
+
+/// Computes the merkle tree leaf for the `user` allocation of `amount` tokens.
+fn allocation_leaf(user: &Pubkey, amount: u64) -> [u8; 32] {
+    let leaf = [&user.to_bytes()[..], &amount.to_be_bytes()];
+    keccak::hashv(&leaf).0
+}
+
+/// Verifies that the `leaf` is included in the tree with the given `root`.
+fn check_proof(leaf: [u8; 32], merkle_proof: &[[u8; 32]], root: &[u8; 32]) -> Result<()> {
+    let mut computed_hash = leaf;
+    for proof_element in merkle_proof {
+        if computed_hash <= *proof_element {
+            computed_hash = keccak::hashv(&[computed_hash.as_ref(), proof_element.as_ref()]).0;
+        } else {
+            computed_hash = keccak::hashv(&[proof_element.as_ref(), computed_hash.as_ref()]).0;
+        }
+    }
+
+    require!(computed_hash == *root, InvalidProof);
+
+    Ok(())
+}
+
+#[derive(AnchorDeserialize)]
+struct MetadataCreator {
+    _address: Pubkey,
+    _verified: bool,
+    _share: u8,
+}
+
+#[derive(AnchorDeserialize)]
+struct MetadataCollection {
+    verified: bool,
+    key: Pubkey,
+}
+
+/// Checks that `user` holds an NFT which is verified in the `collection`,
+/// the Metaplex metadata layout is parsed up to the collection field.
+fn check_gate_nft(
+    user: &Pubkey,
+    collection: &Pubkey,
+    nft_account: &AccountInfo,
+    metadata: &AccountInfo,
+) -> Result<()> {
+    fn next<T: AnchorDeserialize>(data: &mut &[u8]) -> Result<T> {
+        T::deserialize(data).map_err(|_| error!(ErrorCode::InvalidGateNft))
+    }
+
+    let nft_account = Account::<TokenAccount>::try_from(nft_account)?;
+    require!(
+        nft_account.owner == *user && nft_account.amount >= 1,
+        InvalidGateNft
+    );
+
+    let (metadata_address, _bump) = Pubkey::find_program_address(
+        &[
+            "metadata".as_ref(),
+            mpl_token_metadata::ID.as_ref(),
+            nft_account.mint.as_ref(),
+        ],
+        &mpl_token_metadata::ID,
+    );
+    require!(
+        metadata.key() == metadata_address && *metadata.owner == mpl_token_metadata::ID,
+        InvalidGateNft
+    );
+
+    let data = metadata.try_borrow_data()?;
+    let mut data: &[u8] = &data;
+
+    // key, update authority, mint
+    let _key: u8 = next(&mut data)?;
+    let _update_authority: Pubkey = next(&mut data)?;
+    let mint: Pubkey = next(&mut data)?;
+    // name, symbol, uri, seller fee, creators
+    let _name: String = next(&mut data)?;
+    let _symbol: String = next(&mut data)?;
+    let _uri: String = next(&mut data)?;
+    let _seller_fee_basis_points: u16 = next(&mut data)?;
+    let _creators: Option<Vec<MetadataCreator>> = next(&mut data)?;
+    // primary sale happened, is mutable, edition nonce, token standard
+    let _primary_sale_happened: bool = next(&mut data)?;
+    let _is_mutable: bool = next(&mut data)?;
+    let _edition_nonce: Option<u8> = next(&mut data)?;
+    let _token_standard: Option<u8> = next(&mut data)?;
+    let nft_collection: Option<MetadataCollection> = next(&mut data)?;
+
+    require!(
+        mint == nft_account.mint
+            && matches!(
+                nft_collection,
+                Some(MetadataCollection { verified: true, key }) if key == *collection
+            ),
+        InvalidGateNft
+    );
+
+    Ok(())
+}
+
+/// Position NFTs outlive trees, so they are derived from the distributor and the user only.
+pub fn find_position_mint_address(distributor: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[distributor.as_ref(), "position".as_ref(), user.as_ref()],
+        &crate::ID,
+    )
+}
+
+/// Once a position NFT is minted, only its holder is able to claim through `claim_position`.
+/// The position mint is passed first in remaining accounts (after the legacy clock)
+/// if position NFTs are enabled, the rest of the accounts is returned.
+fn check_no_position<'a, 'info>(
+    distributor: &Account<'info, MerkleDistributor>,
+    user: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a [AccountInfo<'info>]> {
+    let accounts = skip_clock_sysvar(accounts);
+    if !distributor.position_nfts {
+        return Ok(accounts);
+    }
+
+    let (position_mint, rest) = accounts
+        .split_first()
+        .ok_or(ErrorCode::PositionNftRequired)?;
+    let (expected, _bump) = find_position_mint_address(&distributor.key(), user);
+    require!(position_mint.key() == expected, InvalidPosition);
+
+    // a burned position is kept as a mint without supply
+    if !position_mint.data_is_empty() {
+        let position_mint = Account::<Mint>::try_from(position_mint)?;
+        require!(position_mint.supply == 0, PositionNftRequired);
+    }
+
+    Ok(rest)
+}
+
+/// Grows `account` up to `len` bytes, the payer tops it up to stay rent exempt.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    if account.data_len() >= len {
+        return Ok(());
+    }
+
+    let rent_required = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+    if rent_required > 0 {
+        invoke(
+            &system_instruction::transfer(&payer.key(), &account.key(), rent_required),
+            &[
+                payer.to_account_info(),
+                account.clone(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    account.realloc(len, true)?;
+
+    Ok(())
+}
+
+/// Clients built before clock accounts were dropped still pass the clock sysvar
+/// after the declared accounts, so it has to be skipped in remaining accounts.
+fn skip_clock_sysvar<'a, 'info>(accounts: &'a [AccountInfo<'info>]) -> &'a [AccountInfo<'info>] {
+    match accounts.split_first() {
+        Some((first, rest)) if first.key() == sysvar::clock::ID => rest,
+        _ => accounts,
+    }
+}
+
+struct ClaimTokens<'pay, 'info> {
+    distributor: &'pay Account<'info, MerkleDistributor>,
+    root: &'pay Account<'info, MerkleRoot>,
+    stats: &'pay mut Account<'info, ClaimStats>,
+    user: Pubkey,
+    vault_authority: &'pay AccountInfo<'info>,
+    vault: &'pay mut Account<'info, TokenAccount>,
+    target_wallet: &'pay Account<'info, TokenAccount>,
+    token_program: &'pay Program<'info, Token>,
+    /// Holds the holdback vault and the user's holdback record if holdback is enabled,
+    /// then the user's NFT token account and its metadata if claims are gated.
+    remaining_accounts: &'pay [AccountInfo<'info>],
+}
+
+/// What the claimed tokens are taken from.
+enum ClaimSource<'pay, 'info> {
+    /// Vested part of the user's allocation in the merkle tree.
+    Allocation {
+        user_details: &'pay mut Account<'info, UserDetails>,
+        args: ClaimArgs,
+    },
+    /// A ticket, its signature and usage are checked by the caller.
+    Ticket { amount: u64 },
+}
+
+impl ClaimTokens<'_, '_> {
+    fn make(self, source: ClaimSource) -> Result<()> {
+        let distributor = self.distributor;
+        let root = self.root;
+        let stats = self.stats;
+        let vault = self.vault;
+        let mut remaining_accounts = skip_clock_sysvar(self.remaining_accounts).iter();
+        let now = Clock::get()?.unix_timestamp as u64;
+
+        let mut holdback = match distributor.holdback {
+            Some(holdback) => {
+                let holdback_vault = remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::HoldbackVaultMissing)?;
+                require!(holdback_vault.key() == holdback.vault, InvalidHoldbackVault);
+
+                let holdback_record = remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::HoldbackRecordMissing)?;
+                let holdback_record = Account::<HoldbackRecord>::try_from(holdback_record)?;
+                let expected = Pubkey::create_program_address(
+                    &[
+                        distributor.key().as_ref(),
+                        "holdback".as_ref(),
+                        self.user.as_ref(),
+                        &[holdback_record.bump],
+                    ],
+                    &crate::ID,
+                )
+                .map_err(|_| ErrorCode::InvalidHoldbackRecord)?;
+                require!(holdback_record.key() == expected, InvalidHoldbackRecord);
+                check_account_version(holdback_record.account_version)?;
+
+                Some((
+                    holdback,
+                    Account::<TokenAccount>::try_from(holdback_vault)?,
+                    holdback_record,
+                ))
+            }
+            None => None,
+        };
+
+        if let Some(gate_collection) = distributor.gate_collection {
+            let nft_account = remaining_accounts.next().ok_or(ErrorCode::GateNftMissing)?;
+            let metadata = remaining_accounts.next().ok_or(ErrorCode::GateNftMissing)?;
+
+            check_gate_nft(&self.user, &gate_collection, nft_account, metadata)?;
+        }
+
+        check_account_version(distributor.account_version)?;
+        check_account_version(root.account_version)?;
+        check_account_version(stats.account_version)?;
+
+        require!(distributor.frozen_at_ts.is_none(), VaultFrozen);
+        require!(!distributor.stopped, DistributorStopped);
+        require!(!root.paused, Paused);
+        require!(!distributor.tge_pending(), TgeNotActivated);
+
+        let from_ticket = matches!(source, ClaimSource::Ticket { .. });
+        let (amount_to_transfer, bonus) = match source {
+            ClaimSource::Allocation { user_details, args } => {
+                // deep proofs shouldn't blow the compute budget mid-verification
+                root.check_proof_len(&args.merkle_proof)?;
+                check_account_version(user_details.account_version)?;
+
+                require!(user_details.claimed_amount < args.amount, AlreadyClaimed);
+                // another claim in the same transaction (or second) would count
+                // the current interval twice because of the interval alignment,
+                // so it only pays out what the per transaction limit left pending
+                let same_second = user_details.last_claimed_at_ts == now;
+                require!(
+                    !same_second || user_details.pending_amount > 0,
+                    NothingToClaim
+                );
+
+                let leaf = allocation_leaf(&self.user, args.amount);
+                user_details.verify_allocation(root, leaf, args.amount, &args.merkle_proof)?;
+
+                let mut claimable = distributor.claimable_amounts(user_details, args.amount, now);
+                if same_second {
+                    claimable.amount = 0;
+                    claimable.amount_to_add = 0;
+                    claimable.bonus = 0;
+                }
+                let ClaimableAmounts {
+                    amount,
+                    amount_to_add,
+                    bonus,
+                    ..
+                } = claimable;
+
+                let amount_to_transfer = amount + user_details.pending_amount;
+                require!(amount_to_transfer > 0, NothingToClaim);
+
+                let amount_to_transfer = match distributor.max_claim_per_tx {
+                    Some(max_claim_per_tx) => std::cmp::min(amount_to_transfer, max_claim_per_tx),
+                    None => amount_to_transfer,
+                };
+                user_details.pending_amount =
+                    amount + user_details.pending_amount - amount_to_transfer;
+
+                // protects the vault from a corrupted root or proof bug
+                let total_claimed = stats
+                    .total_claimed
+                    .checked_add(amount_to_transfer + amount_to_add)
+                    .ok_or(ErrorCode::IntegerOverflow)?;
+                require!(
+                    total_claimed + stats.tickets_claimed <= root.total_allocation,
+                    TotalAllocationExceeded
+                );
+                stats.total_claimed = total_claimed;
+                // split exactly what is accounted, so periods always sum up to `total_claimed`
+                let claimed_by_period =
+                    claimable.split_by_period(amount_to_transfer + amount_to_add);
+                for (claimed, period_amount) in
+                    stats.claimed_per_period.iter_mut().zip(claimed_by_period)
+                {
+                    *claimed += period_amount;
+                }
+                if user_details.claimed_amount == 0 {
+                    stats.claimers += 1;
+                }
+
+                // bonuses are paid from their own budget, never from allocations,
+                // and never lift the claim over the per transaction limit
+                let bonus = std::cmp::min(bonus, distributor.bonus_reserve(stats));
+                let bonus = match distributor.max_claim_per_tx {
+                    Some(max_claim_per_tx) => {
+                        std::cmp::min(bonus, max_claim_per_tx - amount_to_transfer)
+                    }
+                    None => bonus,
+                };
+                stats.bonus_paid += bonus;
+
+                user_details.claimed_amount += amount_to_transfer;
+                user_details.claimed_amount += amount_to_add;
+
+                user_details.last_claimed_at_ts = now;
+
+                if user_details.claim_count == 0 {
+                    user_details.first_claimed_at_ts = now;
+                }
+                user_details.claim_count += 1;
+
+                (amount_to_transfer, bonus)
+            }
+            ClaimSource::Ticket { amount } => {
+                // tickets are paid from the same vault, so they fit into the same cap
+                let tickets_claimed = stats
+                    .tickets_claimed
+                    .checked_add(amount)
+                    .ok_or(ErrorCode::IntegerOverflow)?;
+                require!(
+                    stats.total_claimed + tickets_claimed <= root.total_allocation,
+                    TotalAllocationExceeded
+                );
+                stats.tickets_claimed = tickets_claimed;
+
+                (amount, 0)
+            }
+        };
+
+        let distributor_key = distributor.key();
+        let seeds = &[distributor_key.as_ref(), &[distributor.vault_bump]];
+        let signers = &[&seeds[..]];
+
+        let mut amount_to_user = amount_to_transfer;
+
+        if let Some((holdback, holdback_vault, holdback_record)) = holdback.as_mut() {
+            let amount_to_hold = amount_to_transfer * holdback.bps / 10000;
+
+            if amount_to_hold > 0 {
+                TokenTransfer {
+                    amount: amount_to_hold,
+                    from: &mut *vault,
+                    to: holdback_vault,
+                    authority: self.vault_authority,
+                    token_program: self.token_program,
+                    signers: Some(signers),
+                }
+                .make()?;
+
+                amount_to_user -= amount_to_hold;
+                let release_ts = holdback_record.deposit(amount_to_hold, now + holdback.window_sec);
+                // remaining accounts aren't written back by Anchor
+                holdback_record.exit(&crate::ID)?;
+
+                emit!(TokensHeldBack {
+                    merkle_index: root.merkle_index,
+                    account: self.user,
+                    amount: amount_to_hold,
+                    release_ts,
+                });
+            }
+        }
+
+        TokenTransfer {
+            amount: amount_to_user + bonus,
+            from: vault,
+            to: self.target_wallet,
+            authority: self.vault_authority,
+            token_program: self.token_program,
+            signers: Some(signers),
+        }
+        .make()?;
+
+        // ticket claims are reported with `TicketClaimed` by the caller
+        if !from_ticket {
+            emit!(Claimed {
+                merkle_index: root.merkle_index,
+                account: self.user,
+                token_account: self.target_wallet.key(),
+                amount: amount_to_user,
+                category: distributor.category,
+            });
+        }
+
+        if bonus > 0 {
+            emit!(LoyaltyBonusPaid {
+                merkle_index: root.merkle_index,
+                account: self.user,
+                amount: bonus,
+            });
+        }
+
+        Ok(())
+    }
 }
 
 struct TokenTransfer<'pay, 'info> {
@@ -783,3 +4852,55 @@ impl TokenTransfer<'_, '_> {
         Ok(())
     }
 }
+
+struct CreationFee<'pay, 'info> {
+    amount: u64,
+    distributor: Pubkey,
+    payer: &'pay Signer<'info>,
+    remaining_accounts: &'pay [AccountInfo<'info>],
+    system_program: &'pay Program<'info, System>,
+}
+
+impl CreationFee<'_, '_> {
+    fn charge(self) -> Result<()> {
+        // the treasury is only passed in remaining accounts when a fee is charged,
+        // so factories without fees don't need one
+        if self.amount == 0 {
+            return Ok(());
+        }
+
+        let treasury = self
+            .remaining_accounts
+            .first()
+            .ok_or(ErrorCode::TreasuryMissing)?;
+        let mut treasury = Account::<Treasury>::try_from(treasury)?;
+        let expected =
+            Pubkey::create_program_address(&["treasury".as_ref(), &[treasury.bump]], &crate::ID)
+                .map_err(|_| ErrorCode::InvalidTreasury)?;
+        require!(treasury.key() == expected, InvalidTreasury);
+
+        invoke(
+            &system_instruction::transfer(&self.payer.key(), &treasury.key(), self.amount),
+            &[
+                self.payer.to_account_info(),
+                treasury.to_account_info(),
+                self.system_program.to_account_info(),
+            ],
+        )?;
+
+        treasury.total_collected = treasury
+            .total_collected
+            .checked_add(self.amount)
+            .ok_or(ErrorCode::IntegerOverflow)?;
+        // remaining accounts aren't written back by Anchor
+        treasury.exit(&crate::ID)?;
+
+        emit!(CreationFeePaid {
+            distributor: self.distributor,
+            payer: self.payer.key(),
+            amount: self.amount,
+        });
+
+        Ok(())
+    }
+}
@@ -80,16 +80,22 @@ describe('claiming-factory', () => {
     ];
   }
 
-  async function setupDistributor(schedule: claiming.Period[] = mockSchedule()) {
+  async function setupDistributor(schedule: claiming.Period[] = mockSchedule(), positionNfts: boolean = false) {
+    const clawbackDestination = await serumCmn.createTokenAccount(provider, mint.publicKey, provider.wallet.publicKey);
     const distributor = await client.createDistributor(
       mint.publicKey,
       merkleData.root,
-      schedule
+      schedule,
+      new anchor.BN(merkleData.totalTokens),
+      positionNfts,
+      clawbackDestination
     );
     const distributorAccount = await program.account.merkleDistributor.fetch(distributor);
+    const [root, _rootBump] = await client.findRootAddress(distributor);
+    const [stats, _statsBump] = await client.findStatsAddress(distributor);
 
     const vault = distributorAccount.vault;
-    mint.mintTo(vault, provider.wallet.publicKey, [], 1000);
+    await mint.mintTo(vault, provider.wallet.publicKey, [], 1000);
 
     const vaultAuthority = await anchor.web3.PublicKey.createProgramAddress(
       [
@@ -102,8 +108,11 @@ describe('claiming-factory', () => {
     return {
       distributor,
       distributorAccount,
+      root,
+      stats,
       vault,
       vaultAuthority,
+      clawbackDestination,
     };
   }
 
@@ -137,6 +146,55 @@ describe('claiming-factory', () => {
     return [merkleElement, claimingUser];
   }
 
+  // claims with remaining accounts the SDK doesn't pass, e.g. the holdback vault and record,
+  // or into a token account of somebody else
+  async function claimWithAccounts(
+    ctx: any,
+    index: number,
+    remainingAccounts: anchor.web3.AccountMeta[],
+    targetWallet?: anchor.web3.PublicKey,
+    custodialDestination: boolean = false
+  ) {
+    const merkleElement = merkleData.proofs[index];
+    const claimingUser = claimingUsers[index];
+    const elementClient = new claiming.Client(claimingUser.wallet, claiming.LOCALNET);
+    await elementClient.initUserDetails(ctx.distributor, merkleElement.address);
+    const [userDetails] = await client.findUserDetailsAddress(ctx.distributor, merkleElement.address);
+
+    while (true) {
+      try {
+        await program.rpc.claim(
+          {
+            amount: merkleElement.amount,
+            merkleProof: merkleElement.proofs,
+            custodialDestination,
+          },
+          {
+            accounts: {
+              distributor: ctx.distributor,
+              root: ctx.root,
+              stats: ctx.stats,
+              user: claimingUser.wallet.publicKey,
+              userDetails,
+              vaultAuthority: ctx.vaultAuthority,
+              vault: ctx.vault,
+              targetWallet: targetWallet ? targetWallet : claimingUser.tokenAccount,
+              tokenProgram: spl.TOKEN_PROGRAM_ID,
+            },
+            remainingAccounts,
+            signers: [claimingUser.wallet.payer],
+          }
+        );
+        break;
+      } catch (err: any) {
+        if (err.code != 6015) {
+          throw err;
+        }
+        await serumCmn.sleep(4000);
+      }
+    }
+  }
+
   before(async () => {
     mint = await createMint(provider);
     config = await client.createConfig();
@@ -198,7 +256,7 @@ describe('claiming-factory', () => {
     it("shouldn't allow deploy new distributor if not owner or admin", async function () {
       await assert.rejects(
         async () => {
-          await userClient.createDistributor(mint.publicKey, merkleData.root, []);
+          await userClient.createDistributor(mint.publicKey, merkleData.root, [], new anchor.BN(merkleData.totalTokens));
         },
         (err) => {
           assert.equal(err.code, 6006);
@@ -213,7 +271,8 @@ describe('claiming-factory', () => {
       const distributor = await adminClient.createDistributor(
         mint.publicKey,
         merkleData.root,
-        mockSchedule()
+        mockSchedule(),
+        new anchor.BN(merkleData.totalTokens)
       );
       await program.account.merkleDistributor.fetch(distributor);
     });
@@ -222,25 +281,479 @@ describe('claiming-factory', () => {
       const distributor = await client.createDistributor(
         mint.publicKey,
         merkleData.root,
-        mockSchedule()
+        mockSchedule(),
+        new anchor.BN(merkleData.totalTokens)
       );
       await program.account.merkleDistributor.fetch(distributor);
     });
   });
 
+  context('allowed mints', async function () {
+    async function setAllowedMint(add: boolean, signer: anchor.web3.Keypair, allowedMint: anchor.web3.PublicKey) {
+      const accounts = {
+        accounts: {
+          config,
+          owner: signer.publicKey,
+          mint: allowedMint,
+        },
+        signers: [signer],
+      };
+      if (add) {
+        await program.rpc.addAllowedMint(accounts);
+      } else {
+        await program.rpc.removeAllowedMint(accounts);
+      }
+    }
+
+    it("shouldn't allow to add allowed mint by user", async function () {
+      await assert.rejects(
+        async () => {
+          await setAllowedMint(true, user, mint.publicKey);
+        },
+        (err) => {
+          assert.equal(err.code, 6005);
+          return true;
+        }
+      );
+    });
+
+    it("shouldn't allow to remove allowed mint by user", async function () {
+      await setAllowedMint(true, provider.wallet.payer, mint.publicKey);
+
+      try {
+        await assert.rejects(
+          async () => {
+            await setAllowedMint(false, user, mint.publicKey);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      } finally {
+        // other tests create distributors of any mint
+        await setAllowedMint(false, provider.wallet.payer, mint.publicKey);
+      }
+    });
+
+    it("shouldn't create distributor with a mint not on the allowlist", async function () {
+      await setAllowedMint(true, provider.wallet.payer, mint.publicKey);
+      const otherMint = await createMint(provider);
+
+      try {
+        await assert.rejects(
+          async () => {
+            await client.createDistributor(
+              otherMint.publicKey,
+              merkleData.root,
+              mockSchedule(),
+              new anchor.BN(merkleData.totalTokens)
+            );
+          },
+          (err) => {
+            assert.equal(err.code, 6037);
+            return true;
+          }
+        );
+
+        await setupDistributor();
+      } finally {
+        await setAllowedMint(false, provider.wallet.payer, mint.publicKey);
+      }
+    });
+  });
+
+  context('version', async function () {
+    it("shouldn't allow to set version by user", async function () {
+      await assert.rejects(
+        async () => {
+          await program.rpc.setVersion({
+            accounts: {
+              config,
+              owner: user.publicKey,
+            },
+            signers: [user],
+          });
+        },
+        (err) => {
+          assert.equal(err.code, 6005);
+          return true;
+        }
+      );
+    });
+
+    it("should return the program and config versions", async function () {
+      await program.rpc.setVersion({
+        accounts: {
+          config,
+          owner: provider.wallet.publicKey,
+        }
+      });
+
+      const result = await program.simulate.getVersion({
+        accounts: {
+          config,
+        }
+      });
+
+      // `VersionInfo` is only available as return data, which the runtime logs
+      const prefix = `Program return: ${program.programId.toBase58()} `;
+      const log = result.raw.find((line) => line.startsWith(prefix));
+      assert.ok(log);
+      const data = Buffer.from(log.slice(prefix.length), 'base64');
+      assert.equal(data.readUInt32LE(0), 1);
+      assert.equal(data.readUInt32LE(4), 1);
+    });
+  });
+
+  context('migrate config', async function () {
+    async function migrateConfig(owner: anchor.web3.Keypair) {
+      await program.rpc.migrateConfig({
+        accounts: {
+          config,
+          owner: owner.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [owner],
+      });
+    }
+
+    it("shouldn't allow to migrate the config by user", async function () {
+      await assert.rejects(
+        async () => {
+          await migrateConfig(user);
+        },
+        (err) => {
+          assert.equal(err.code, 6005);
+          return true;
+        }
+      );
+    });
+
+    it("should keep the current config as is", async function () {
+      const before = await program.account.config.fetch(config);
+      await migrateConfig(provider.wallet.payer);
+      const after = await program.account.config.fetch(config);
+
+      assert.ok(after.owner.equals(before.owner));
+      assert.deepStrictEqual(after.admins, before.admins);
+      assert.equal(after.version, before.version);
+      assert.equal(after.accountVersion, 1);
+    });
+  });
+
+  context('migrate distributor', async function () {
+    // preloaded into the test validator by Anchor.toml
+    const legacyDistributor = new anchor.web3.PublicKey("DbVkZtuEYFt4xzDPsfJo2uaYz3VbzCSQYokWDvNCdZAw");
+    const legacyVault = new anchor.web3.PublicKey("U6vMSTUd2xJezxmdVxNnXXWBSGePrQv42ZyJdxEgAHA");
+    const legacyMint = new anchor.web3.PublicKey("uT1jAeELAm6HjrXWUPM6XZcmSbCgWx21Jwj2FgVvX64");
+    const legacyRoot = Buffer.from("5b86ffd388e4e795ed1640ae6a0f710b1f26aba02befcc54e8e23b4f030daaeb", 'hex');
+
+    async function migrateDistributor(owner: anchor.web3.Keypair, clawbackDestination: anchor.web3.PublicKey) {
+      const [root, _rootBump] = await client.findRootAddress(legacyDistributor);
+      const [stats, _statsBump] = await client.findStatsAddress(legacyDistributor);
+
+      await program.rpc.migrateDistributor(
+        {
+          totalAllocation: new anchor.BN(600),
+          clawbackDestination,
+          category: { airdrop: {} },
+        },
+        {
+          accounts: {
+            distributor: legacyDistributor,
+            root,
+            stats,
+            config,
+            owner: owner.publicKey,
+            vault: legacyVault,
+            mint: legacyMint,
+            clawbackDestination,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          },
+          signers: [owner],
+        }
+      );
+    }
+
+    it("shouldn't allow to migrate a distributor by user", async function () {
+      const clawbackDestination = await serumCmn.createTokenAccount(provider, legacyMint, user.publicKey);
+
+      await assert.rejects(
+        async () => {
+          await migrateDistributor(user, clawbackDestination);
+        },
+        (err) => {
+          assert.equal(err.code, 6005);
+          return true;
+        }
+      );
+    });
+
+    it("should migrate a distributor in the legacy layout by owner", async function () {
+      const clawbackDestination = await serumCmn.createTokenAccount(provider, legacyMint, provider.wallet.publicKey);
+      await migrateDistributor(provider.wallet.payer, clawbackDestination);
+
+      const distributorAccount = await program.account.merkleDistributor.fetch(legacyDistributor);
+      assert.ok(distributorAccount.vault.equals(legacyVault));
+      assert.ok(distributorAccount.mint.equals(legacyMint));
+      assert.equal(distributorAccount.mintDecimals, 6);
+      assert.ok(distributorAccount.clawbackDestination.equals(clawbackDestination));
+      assert.equal(distributorAccount.vesting.schedule.length, 1);
+      assert.ok(distributorAccount.vesting.schedule[0].tokenPercentage.eqn(10000));
+      assert.equal(distributorAccount.completedPeriods.length, 1);
+      assert.equal(distributorAccount.accountVersion, 1);
+
+      const [root, _rootBump] = await client.findRootAddress(legacyDistributor);
+      const rootAccount = await program.account.merkleRoot.fetch(root);
+      assert.ok(rootAccount.merkleIndex.eqn(2));
+      assert.ok(Buffer.from(rootAccount.merkleRoot).equals(legacyRoot));
+      assert.ok(rootAccount.totalAllocation.eqn(600));
+
+      const [stats, _statsBump] = await client.findStatsAddress(legacyDistributor);
+      const statsAccount = await program.account.claimStats.fetch(stats);
+      assert.ok(statsAccount.totalClaimed.eqn(0));
+      assert.equal(statsAccount.claimedPerPeriod.length, 1);
+
+      // the root and stats exist now, so it can't be migrated twice
+      await assert.rejects(migrateDistributor(provider.wallet.payer, clawbackDestination));
+    });
+  });
+
+  context('treasury', async function () {
+    before(async function () {
+      this.treasury = await client.createTreasury();
+
+      // fees are sent to the treasury by distributor creators, an airdrop stands in for them
+      const tx = await provider.connection.requestAirdrop(this.treasury, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(tx);
+
+      this.feeWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, this.treasury);
+      await mint.mintTo(this.feeWallet, provider.wallet.publicKey, [], 100);
+      [this.feeTokenStats] = await anchor.web3.PublicKey.findProgramAddress(
+        [new TextEncoder().encode("treasury"), mint.publicKey.toBytes()],
+        program.programId
+      );
+    });
+
+    async function withdrawFees(ctx: any, signer: anchor.web3.Keypair, target: anchor.web3.PublicKey, amount: number) {
+      await program.rpc.withdrawFees(new anchor.BN(amount), {
+        accounts: {
+          config,
+          owner: signer.publicKey,
+          treasury: ctx.treasury,
+          target,
+        },
+        signers: [signer],
+      });
+    }
+
+    async function withdrawFeeTokens(ctx: any, signer: anchor.web3.Keypair, targetWallet: anchor.web3.PublicKey, amount: number) {
+      await program.rpc.withdrawFeeTokens(new anchor.BN(amount), {
+        accounts: {
+          config,
+          owner: signer.publicKey,
+          treasury: ctx.treasury,
+          feeWallet: ctx.feeWallet,
+          targetWallet,
+          feeTokenStats: ctx.feeTokenStats,
+          tokenProgram: spl.TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [signer],
+      });
+    }
+
+    it("shouldn't allow to withdraw fees by user", async function () {
+      await assert.rejects(
+        async () => {
+          await withdrawFees(this, user, user.publicKey, 1000);
+        },
+        (err) => {
+          assert.equal(err.code, 6005);
+          return true;
+        }
+      );
+    });
+
+    it("should withdraw fees by owner", async function () {
+      const target = anchor.web3.Keypair.generate().publicKey;
+      const before = await program.account.treasury.fetch(this.treasury);
+
+      await withdrawFees(this, provider.wallet.payer, target, LAMPORTS_PER_SOL / 2);
+
+      assert.equal(await provider.connection.getBalance(target), LAMPORTS_PER_SOL / 2);
+      const after = await program.account.treasury.fetch(this.treasury);
+      assert.ok(after.totalWithdrawn.sub(before.totalWithdrawn).eqn(LAMPORTS_PER_SOL / 2));
+    });
+
+    it("shouldn't withdraw more fees than the treasury holds above rent", async function () {
+      const balance = await provider.connection.getBalance(this.treasury);
+
+      await assert.rejects(
+        async () => {
+          await withdrawFees(this, provider.wallet.payer, provider.wallet.publicKey, balance);
+        },
+        (err) => {
+          assert.equal(err.code, 6038);
+          return true;
+        }
+      );
+    });
+
+    it("shouldn't allow to withdraw fee tokens by user", async function () {
+      const targetWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, user.publicKey);
+
+      await assert.rejects(
+        async () => {
+          await withdrawFeeTokens(this, user, targetWallet, 10);
+        },
+        (err) => {
+          assert.equal(err.code, 6005);
+          return true;
+        }
+      );
+    });
+
+    it("should withdraw fee tokens by owner and count them per mint", async function () {
+      const targetWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, provider.wallet.publicKey);
+
+      await withdrawFeeTokens(this, provider.wallet.payer, targetWallet, 30);
+      await withdrawFeeTokens(this, provider.wallet.payer, targetWallet, 10);
+
+      const targetWalletAccount = await serumCmn.getTokenAccount(provider, targetWallet);
+      assert.ok(targetWalletAccount.amount.eqn(40));
+      const feeTokenStats = await program.account.feeTokenStats.fetch(this.feeTokenStats);
+      assert.ok(feeTokenStats.mint.equals(mint.publicKey));
+      assert.ok(feeTokenStats.totalWithdrawn.eqn(40));
+    });
+
+    it("shouldn't withdraw more fee tokens than the treasury holds", async function () {
+      const targetWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, provider.wallet.publicKey);
+      const feeWalletAccount = await serumCmn.getTokenAccount(provider, this.feeWallet);
+
+      await assert.rejects(
+        async () => {
+          await withdrawFeeTokens(this, provider.wallet.payer, targetWallet, feeWalletAccount.amount.toNumber() + 1);
+        },
+        (err) => {
+          assert.equal(err.code, 6038);
+          return true;
+        }
+      );
+    });
+
+    context('creation fee', async function () {
+      async function setCreationFee(signer: anchor.web3.Keypair, lamports: number) {
+        await program.rpc.setCreationFee(new anchor.BN(lamports), {
+          accounts: {
+            config,
+            owner: signer.publicKey,
+          },
+          signers: [signer],
+        });
+      }
+
+      afterEach(async function () {
+        await setCreationFee(provider.wallet.payer, 0);
+      });
+
+      it("shouldn't allow to set the creation fee by user", async function () {
+        await assert.rejects(
+          async () => {
+            await setCreationFee(user, 1000);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should charge the creation fee into the treasury", async function () {
+        await setCreationFee(provider.wallet.payer, 1000);
+        const before = await program.account.treasury.fetch(this.treasury);
+        const balanceBefore = await provider.connection.getBalance(this.treasury);
+
+        await setupDistributor();
+
+        const after = await program.account.treasury.fetch(this.treasury);
+        assert.ok(after.totalCollected.sub(before.totalCollected).eqn(1000));
+        assert.equal(await provider.connection.getBalance(this.treasury), balanceBefore + 1000);
+      });
+
+      it("shouldn't create a distributor without the treasury when a fee is set", async function () {
+        await setCreationFee(provider.wallet.payer, 1000);
+
+        const distributor = anchor.web3.Keypair.generate();
+        const [vaultAuthority, vaultBump] = await client.findVaultAuthority(distributor.publicKey);
+        const [root, _rootBump] = await client.findRootAddress(distributor.publicKey);
+        const [stats, _statsBump] = await client.findStatsAddress(distributor.publicKey);
+        const vault = await serumCmn.createTokenAccount(provider, mint.publicKey, vaultAuthority);
+        const clawbackDestination = await serumCmn.createTokenAccount(provider, mint.publicKey, provider.wallet.publicKey);
+
+        await assert.rejects(
+          async () => {
+            await program.rpc.initialize(
+              {
+                vaultBump,
+                merkleRoot: merkleData.root,
+                schedule: mockSchedule(),
+                positionNfts: false,
+                maxClaimPerTx: null,
+                category: { ido: {} },
+                totalAllocation: new anchor.BN(merkleData.totalTokens),
+                relativeSchedule: false,
+                maxProofLen: null,
+              },
+              {
+                accounts: {
+                  distributor: distributor.publicKey,
+                  root,
+                  stats,
+                  adminOrOwner: provider.wallet.publicKey,
+                  vaultAuthority,
+                  vault,
+                  mint: mint.publicKey,
+                  clawbackDestination,
+                  config,
+                  systemProgram: anchor.web3.SystemProgram.programId,
+                },
+                signers: [distributor],
+              }
+            );
+          },
+          (err) => {
+            assert.equal(err.code, 6070);
+            return true;
+          }
+        );
+      });
+    });
+  });
+
   context('distributor', async function () {
     beforeEach(async function () {
       const r = await setupDistributor();
 
       this.distributor = r.distributor;
       this.distributorAccount = r.distributorAccount;
+      this.root = r.root;
+      this.stats = r.stats;
       this.vault = r.vault;
       this.vaultAuthority = r.vaultAuthority;
+      this.clawbackDestination = r.clawbackDestination;
     });
 
     it('should have correct initial values', async function () {
-      assert.ok(this.distributorAccount.merkleIndex.eqn(0));
-      assert.ok(Buffer.from(this.distributorAccount.merkleRoot).equals(merkleData.root));
+      const rootAccount = await program.account.merkleRoot.fetch(this.root);
+      assert.ok(rootAccount.merkleIndex.eqn(0));
+      assert.ok(Buffer.from(rootAccount.merkleRoot).equals(merkleData.root));
+      assert.ok(rootAccount.totalAllocation.eqn(merkleData.totalTokens));
+
+      const statsAccount = await program.account.claimStats.fetch(this.stats);
+      assert.ok(statsAccount.totalClaimed.eqn(0));
+      assert.equal(statsAccount.claimedPerPeriod.length, this.distributorAccount.vesting.schedule.length);
     });
 
     context("withdraw tokens", async function () {
@@ -272,11 +785,24 @@ describe('claiming-factory', () => {
         );
       });
 
-      it("should withdraw token by owner", async function () {
+      it("shouldn't allow withdraw to a wallet other than the clawback destination", async function () {
         const targetWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, user.publicKey);
-        await client.withdrawTokens(new anchor.BN(100), this.distributor, targetWallet);
 
-        const targetWalletAccount = await serumCmn.getTokenAccount(provider, targetWallet);
+        await assert.rejects(
+          async () => {
+            await client.withdrawTokens(new anchor.BN(100), this.distributor, targetWallet);
+          },
+          (err) => {
+            assert.equal(err.code, 6061);
+            return true;
+          }
+        );
+      });
+
+      it("should withdraw token by owner", async function () {
+        await client.withdrawTokens(new anchor.BN(100), this.distributor, this.clawbackDestination);
+
+        const targetWalletAccount = await serumCmn.getTokenAccount(provider, this.clawbackDestination);
         assert.ok(targetWalletAccount.amount.eqn(100));
       });
     });
@@ -287,7 +813,7 @@ describe('claiming-factory', () => {
       it("shouldn't allow update by user", async function () {
         await assert.rejects(
           async () => {
-            await userClient.updateRoot(this.distributor, UPDATED_ROOT, false);
+            await userClient.updateRoot(this.distributor, UPDATED_ROOT, new anchor.BN(merkleData.totalTokens), false);
           },
           (err) => {
             assert.equal(err.code, 6006);
@@ -298,17 +824,17 @@ describe('claiming-factory', () => {
 
       it("should allow update by admin", async function () {
         await client.addAdmin(admin.publicKey);
-        await adminClient.updateRoot(this.distributor, UPDATED_ROOT, false);
+        await adminClient.updateRoot(this.distributor, UPDATED_ROOT, new anchor.BN(merkleData.totalTokens), false);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.deepStrictEqual(distributorAccount.merkleRoot, UPDATED_ROOT);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.deepStrictEqual(rootAccount.merkleRoot, UPDATED_ROOT);
       });
 
       it("should allow update by owner", async function () {
-        await client.updateRoot(this.distributor, UPDATED_ROOT, false);
+        await client.updateRoot(this.distributor, UPDATED_ROOT, new anchor.BN(merkleData.totalTokens), false);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.deepStrictEqual(distributorAccount.merkleRoot, UPDATED_ROOT);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.deepStrictEqual(rootAccount.merkleRoot, UPDATED_ROOT);
       });
     });
 
@@ -316,18 +842,18 @@ describe('claiming-factory', () => {
       it("should unpause if it's paused by admin", async function () {
         await client.pause(this.distributor);
         await client.addAdmin(admin.publicKey);
-        await adminClient.updateRoot(this.distributor, merkleData.root, true);
+        await adminClient.updateRoot(this.distributor, merkleData.root, new anchor.BN(merkleData.totalTokens), true);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
 
       it("should unpause if it paused by owner", async function () {
         await client.pause(this.distributor);
-        await client.updateRoot(this.distributor, merkleData.root, true);
+        await client.updateRoot(this.distributor, merkleData.root, new anchor.BN(merkleData.totalTokens), true);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
     });
 
@@ -343,8 +869,8 @@ describe('claiming-factory', () => {
           }
         );
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
 
       it("shouldn't allow to pause the program if it already paused", async function () {
@@ -364,23 +890,23 @@ describe('claiming-factory', () => {
         let balanceAfter = await provider.connection.getBalance(provider.wallet.publicKey);
         assert.equal(balanceBefore, balanceAfter);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, true);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, true);
       });
 
       it("should allow to pause the program by admin", async function () {
         await client.addAdmin(admin.publicKey);
         await adminClient.pause(this.distributor);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, true);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, true);
       });
 
       it("should allow to pause the program by owner", async function () {
         await client.pause(this.distributor);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, true);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, true);
       });
     });
 
@@ -396,8 +922,8 @@ describe('claiming-factory', () => {
           }
         );
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
 
       it("shouldn't allow to unpause the program if it already unpaused", async function () {
@@ -416,8 +942,8 @@ describe('claiming-factory', () => {
         let balanceAfter = await provider.connection.getBalance(provider.wallet.publicKey);
         assert.equal(balanceBefore, balanceAfter);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
 
       it("should allow to unpause the program by admin", async function () {
@@ -425,16 +951,16 @@ describe('claiming-factory', () => {
         await client.addAdmin(admin.publicKey);
         await adminClient.unpause(this.distributor);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
 
       it("should allow to unpause the program by owner", async function () {
         await client.pause(this.distributor);
         await client.unpause(this.distributor);
 
-        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
-        assert.equal(distributorAccount.paused, false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.equal(rootAccount.paused, false);
       });
     });
 
@@ -650,6 +1176,18 @@ describe('claiming-factory', () => {
         )
       });
 
+      it("shouldn't claim into a token account of another user", async function () {
+        await assert.rejects(
+          async () => {
+            await claimWithAccounts(this, 4, [], claimingUsers[3].tokenAccount);
+          },
+          (err) => {
+            assert.equal(err.code, 6022);
+            return true;
+          }
+        );
+      });
+
       it("shouldn't claim if reward has been claimed", async function () {
         await claim(this.distributor, 4);
 
@@ -677,7 +1215,12 @@ describe('claiming-factory', () => {
         }
         let updatedMerkleData = merkle.getMerkleProof(data);
 
-        await client.updateRoot(this.distributor, updatedMerkleData.root, true);
+        await client.updateRoot(
+          this.distributor,
+          updatedMerkleData.root,
+          new anchor.BN(updatedMerkleData.totalTokens),
+          true
+        );
 
         [merkleElement, claimingUser] = await claim(this.distributor, 2, updatedMerkleData.proofs[2]);
 
@@ -686,19 +1229,285 @@ describe('claiming-factory', () => {
       });
     });
 
-    context("complicated schedule", async function () {
-      it("should claim in two attempts", async function () {
+    context("double claim", async function () {
+      it("shouldn't claim twice in one transaction", async function () {
+        // only the first half is unlocked, so the second claim isn't stopped as already claimed
+        const nowTs = Date.now() / 1000;
         const r = await setupDistributor([
           {
             tokenPercentage: new anchor.BN(5000),
-            startTs: new anchor.BN(Date.now() / 1000),
+            startTs: new anchor.BN(nowTs + 2),
             intervalSec: new anchor.BN(1),
             times: new anchor.BN(1),
             airdropped: false,
           },
           {
             tokenPercentage: new anchor.BN(5000),
-            startTs: new anchor.BN(Date.now() / 1000 + 16),
+            startTs: new anchor.BN(nowTs + 3600),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          },
+        ]);
+
+        const merkleElement = merkleData.proofs[3];
+        const claimingUser = claimingUsers[3];
+        const elementClient = new claiming.Client(claimingUser.wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(r.distributor, merkleElement.address);
+        const [userDetails, _bump] = await elementClient.findUserDetailsAddress(
+          r.distributor,
+          merkleElement.address
+        );
+
+        const args = {
+          amount: merkleElement.amount,
+          merkleProof: merkleElement.proofs,
+          custodialDestination: false,
+        };
+        const accounts = {
+          distributor: r.distributor,
+          root: r.root,
+          stats: r.stats,
+          user: merkleElement.address,
+          userDetails,
+          vaultAuthority: r.vaultAuthority,
+          vault: r.vault,
+          targetWallet: claimingUser.tokenAccount,
+          tokenProgram: spl.TOKEN_PROGRAM_ID,
+        };
+
+        const balanceBefore = (await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount)).amount;
+
+        // wait until the first period is unlocked
+        await serumCmn.sleep(4000);
+
+        await assert.rejects(
+          async () => {
+            await program.rpc.claim(args, {
+              accounts,
+              instructions: [program.instruction.claim(args, { accounts })],
+              signers: [claimingUser.wallet.payer],
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6015);
+            return true;
+          }
+        );
+
+        let targetWalletAccount = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        assert.ok(targetWalletAccount.amount.eq(balanceBefore));
+
+        await program.rpc.claim(args, {
+          accounts,
+          signers: [claimingUser.wallet.payer],
+        });
+
+        const userDetailsAccount = await program.account.userDetails.fetch(userDetails);
+        assert.ok(userDetailsAccount.claimedAmount.gtn(0));
+        assert.ok(userDetailsAccount.claimedAmount.lt(merkleElement.amount));
+
+        targetWalletAccount = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        assert.ok(targetWalletAccount.amount.eq(balanceBefore.add(userDetailsAccount.claimedAmount)));
+      });
+
+      context("with the per transaction limit", async function () {
+        beforeEach(async function () {
+          // the allocation of 4 is paid as 3 and 1 left pending
+          await program.rpc.setMaxClaimPerTx(new anchor.BN(3), {
+            accounts: {
+              distributor: this.distributor,
+              config,
+              adminOrOwner: provider.wallet.publicKey,
+            }
+          });
+
+          const merkleElement = merkleData.proofs[4];
+          const claimingUser = claimingUsers[4];
+          const elementClient = new claiming.Client(claimingUser.wallet, claiming.LOCALNET);
+          await elementClient.initUserDetails(this.distributor, merkleElement.address);
+          const [userDetails, _bump] = await elementClient.findUserDetailsAddress(
+            this.distributor,
+            merkleElement.address
+          );
+
+          this.claimArgs = {
+            amount: merkleElement.amount,
+            merkleProof: merkleElement.proofs,
+            custodialDestination: false,
+          };
+          this.claimAccounts = {
+            distributor: this.distributor,
+            root: this.root,
+            stats: this.stats,
+            user: merkleElement.address,
+            userDetails,
+            vaultAuthority: this.vaultAuthority,
+            vault: this.vault,
+            targetWallet: claimingUser.tokenAccount,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          };
+
+          // wait until the schedule is unlocked
+          await serumCmn.sleep(4000);
+        });
+
+        async function checkAccounting(ctx: any) {
+          const userDetailsAccount = await program.account.userDetails.fetch(ctx.claimAccounts.userDetails);
+          assert.ok(userDetailsAccount.claimedAmount.eqn(4));
+          assert.ok(userDetailsAccount.pendingAmount.eqn(0));
+          assert.ok(userDetailsAccount.claimCount.eqn(2));
+
+          const statsAccount = await program.account.claimStats.fetch(ctx.stats);
+          assert.ok(statsAccount.totalClaimed.eqn(4));
+          assert.equal(statsAccount.claimers.toNumber(), 1);
+          const claimedPerPeriod = statsAccount.claimedPerPeriod.reduce((sum, amount) => sum.add(amount), new anchor.BN(0));
+          assert.ok(claimedPerPeriod.eqn(4));
+
+          await assert.rejects(
+            async () => {
+              await program.rpc.claim(ctx.claimArgs, {
+                accounts: ctx.claimAccounts,
+                signers: [claimingUsers[4].wallet.payer],
+              });
+            },
+            (err) => {
+              assert.equal(err.code, 6004);
+              return true;
+            }
+          );
+        }
+
+        it("should pay out the pending amount in the same transaction", async function () {
+          const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+
+          await program.rpc.claim(this.claimArgs, {
+            accounts: this.claimAccounts,
+            instructions: [program.instruction.claim(this.claimArgs, { accounts: this.claimAccounts })],
+            signers: [claimingUsers[4].wallet.payer],
+          });
+
+          const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+          assert.ok(after.amount.sub(before.amount).eqn(4));
+          await checkAccounting(this);
+        });
+
+        it("should account exactly across two claims", async function () {
+          const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+
+          await program.rpc.claim(this.claimArgs, {
+            accounts: this.claimAccounts,
+            signers: [claimingUsers[4].wallet.payer],
+          });
+          let after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+          assert.ok(after.amount.sub(before.amount).eqn(3));
+          const userDetailsAccount = await program.account.userDetails.fetch(this.claimAccounts.userDetails);
+          assert.ok(userDetailsAccount.pendingAmount.eqn(1));
+
+          await serumCmn.sleep(1500);
+          await program.rpc.claim(this.claimArgs, {
+            accounts: this.claimAccounts,
+            signers: [claimingUsers[4].wallet.payer],
+          });
+
+          after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+          assert.ok(after.amount.sub(before.amount).eqn(4));
+          await checkAccounting(this);
+        });
+      });
+    });
+
+    context("legacy clock account", async function () {
+      const legacyClock = [
+        { pubkey: anchor.web3.SYSVAR_CLOCK_PUBKEY, isWritable: false, isSigner: false },
+      ];
+
+      it("should claim if the clock sysvar is still passed", async function () {
+        const merkleElement = merkleData.proofs[1];
+        const claimingUser = claimingUsers[1];
+        const elementClient = new claiming.Client(claimingUser.wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(this.distributor, merkleElement.address);
+        const [userDetails, _bump] = await elementClient.findUserDetailsAddress(
+          this.distributor,
+          merkleElement.address
+        );
+
+        while (true) {
+          try {
+            await program.rpc.claim(
+              {
+                amount: merkleElement.amount,
+                merkleProof: merkleElement.proofs,
+                custodialDestination: false,
+              },
+              {
+                accounts: {
+                  distributor: this.distributor,
+                  root: this.root,
+                  stats: this.stats,
+                  user: merkleElement.address,
+                  userDetails,
+                  vaultAuthority: this.vaultAuthority,
+                  vault: this.vault,
+                  targetWallet: claimingUser.tokenAccount,
+                  tokenProgram: spl.TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts: legacyClock,
+                signers: [claimingUser.wallet.payer],
+              }
+            );
+            break;
+          } catch (err: any) {
+            if (err.code != 6015) {
+              throw err;
+            }
+            await serumCmn.sleep(15000);
+          }
+        }
+
+        const targetWalletAccount = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        assert.ok(targetWalletAccount.amount.eq(merkleElement.amount));
+      });
+
+      it("should update root if the clock sysvar is still passed", async function () {
+        await program.rpc.updateRoot(
+          {
+            merkleRoot: merkleData.root,
+            unpause: false,
+            totalAllocation: new anchor.BN(merkleData.totalTokens),
+            maxProofLen: null,
+          },
+          {
+            accounts: {
+              distributor: this.distributor,
+              root: this.root,
+              stats: this.stats,
+              config,
+              adminOrOwner: provider.wallet.publicKey,
+              vault: this.vault,
+            },
+            remainingAccounts: legacyClock,
+          }
+        );
+
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.ok(rootAccount.merkleIndex.eqn(1));
+      });
+    });
+
+    context("complicated schedule", async function () {
+      it("should claim in two attempts", async function () {
+        const r = await setupDistributor([
+          {
+            tokenPercentage: new anchor.BN(5000),
+            startTs: new anchor.BN(Date.now() / 1000),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          },
+          {
+            tokenPercentage: new anchor.BN(5000),
+            startTs: new anchor.BN(Date.now() / 1000 + 16),
             intervalSec: new anchor.BN(1),
             times: new anchor.BN(1),
             airdropped: false,
@@ -759,5 +1568,1748 @@ describe('claiming-factory', () => {
         );
       });
     });
+
+    context("position NFT", async function () {
+      beforeEach(async function () {
+        const r = await setupDistributor(mockSchedule(), true);
+        this.positionDistributor = r.distributor;
+
+        const merkleElement = merkleData.proofs[3];
+        this.positionOwnerClient = new claiming.Client(claimingUsers[3].wallet, claiming.LOCALNET);
+        await this.positionOwnerClient.initUserDetails(this.positionDistributor, merkleElement.address);
+        [this.positionMint] = await client.findPositionMintAddress(this.positionDistributor, merkleElement.address);
+      });
+
+      it("should mint a position NFT to the user", async function () {
+        const merkleElement = merkleData.proofs[3];
+        const positionTokenAccount = await this.positionOwnerClient.mintPosition(
+          this.positionDistributor,
+          merkleElement.amount,
+          merkleElement.proofs
+        );
+
+        const positionAccount = await serumCmn.getTokenAccount(provider, positionTokenAccount);
+        assert.ok(positionAccount.mint.equals(this.positionMint));
+        assert.ok(positionAccount.amount.eqn(1));
+      });
+
+      it("shouldn't allow the user to claim while the position exists", async function () {
+        const merkleElement = merkleData.proofs[3];
+        await this.positionOwnerClient.mintPosition(
+          this.positionDistributor,
+          merkleElement.amount,
+          merkleElement.proofs
+        );
+
+        await assert.rejects(
+          async () => {
+            await claim(this.positionDistributor, 3);
+          },
+          (err) => {
+            assert.equal(err.code, 6018);
+            return true;
+          }
+        );
+      });
+
+      it("should let the holder claim a transferred position and burn it", async function () {
+        const merkleElement = merkleData.proofs[3];
+        const ownerPositionAccount = await this.positionOwnerClient.mintPosition(
+          this.positionDistributor,
+          merkleElement.amount,
+          merkleElement.proofs
+        );
+
+        const positionToken = new spl.Token(
+          provider.connection,
+          this.positionMint,
+          spl.TOKEN_PROGRAM_ID,
+          provider.wallet.payer
+        );
+        const holderPositionAccount = await serumCmn.createTokenAccount(provider, this.positionMint, user.publicKey);
+        await positionToken.transfer(
+          ownerPositionAccount,
+          holderPositionAccount,
+          claimingUsers[3].wallet.payer,
+          [],
+          1
+        );
+
+        const holderWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, user.publicKey);
+        while (true) {
+          try {
+            await userClient.claimPosition(
+              this.positionDistributor,
+              merkleElement.address,
+              holderPositionAccount,
+              holderWallet,
+              merkleElement.amount,
+              merkleElement.proofs
+            );
+            break;
+          } catch (err: any) {
+            if (err.code != 6015) {
+              throw err;
+            }
+            await serumCmn.sleep(4000);
+          }
+        }
+
+        const holderWalletAccount = await serumCmn.getTokenAccount(provider, holderWallet);
+        assert.ok(holderWalletAccount.amount.eq(merkleElement.amount));
+
+        const positionMintAccount = await positionToken.getMintInfo();
+        assert.ok(positionMintAccount.supply.eqn(0));
+
+        // the burned position doesn't block the user, but the allocation is claimed already
+        await assert.rejects(
+          async () => {
+            await claim(this.positionDistributor, 3);
+          },
+          (err) => {
+            assert.equal(err.code, 6004);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't let the original user claim through the position after transfer", async function () {
+        const merkleElement = merkleData.proofs[3];
+        const ownerPositionAccount = await this.positionOwnerClient.mintPosition(
+          this.positionDistributor,
+          merkleElement.amount,
+          merkleElement.proofs
+        );
+
+        const positionToken = new spl.Token(
+          provider.connection,
+          this.positionMint,
+          spl.TOKEN_PROGRAM_ID,
+          provider.wallet.payer
+        );
+        const holderPositionAccount = await serumCmn.createTokenAccount(provider, this.positionMint, user.publicKey);
+        await positionToken.transfer(
+          ownerPositionAccount,
+          holderPositionAccount,
+          claimingUsers[3].wallet.payer,
+          [],
+          1
+        );
+
+        await assert.rejects(
+          async () => {
+            await this.positionOwnerClient.claimPosition(
+              this.positionDistributor,
+              merkleElement.address,
+              ownerPositionAccount,
+              claimingUsers[3].tokenAccount,
+              merkleElement.amount,
+              merkleElement.proofs
+            );
+          },
+          (err) => {
+            assert.equal(err.code, 6020);
+            return true;
+          }
+        );
+      });
+    });
+
+    context("claim ticket", async function () {
+      const ticketSigner = anchor.web3.Keypair.generate();
+
+      beforeEach(async function () {
+        await program.rpc.setTicketSigner(ticketSigner.publicKey, {
+          accounts: {
+            distributor: this.distributor,
+            config,
+            adminOrOwner: provider.wallet.publicKey,
+          }
+        });
+      });
+
+      function ticketMessage(distributor: anchor.web3.PublicKey, args: any): Buffer {
+        return Buffer.concat([
+          distributor.toBuffer(),
+          claimingUsers[0].wallet.publicKey.toBuffer(),
+          args.ticketId.toArrayLike(Buffer, 'be', 8),
+          args.amount.toArrayLike(Buffer, 'be', 8),
+          args.expiresAtTs.toArrayLike(Buffer, 'be', 8),
+        ]);
+      }
+
+      function ticketArgs(amount: number) {
+        return {
+          ticketId: new anchor.BN(1),
+          amount: new anchor.BN(amount),
+          expiresAtTs: new anchor.BN(Math.floor(Date.now() / 1000) + 600),
+        };
+      }
+
+      async function claimTicket(ctx: any, args: any, signatureIx: anchor.web3.TransactionInstruction) {
+        const claimingUser = claimingUsers[0];
+        const [ticketUsage, _bump] = await anchor.web3.PublicKey.findProgramAddress(
+          [
+            ctx.distributor.toBytes(),
+            new TextEncoder().encode("ticket"),
+            args.ticketId.toArrayLike(Buffer, 'be', 8),
+          ],
+          program.programId
+        );
+
+        await program.rpc.claimTicket(args, {
+          accounts: {
+            distributor: ctx.distributor,
+            root: ctx.root,
+            stats: ctx.stats,
+            user: claimingUser.wallet.publicKey,
+            ticketUsage,
+            vaultAuthority: ctx.vaultAuthority,
+            vault: ctx.vault,
+            targetWallet: claimingUser.tokenAccount,
+            instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          },
+          instructions: [signatureIx],
+          signers: [claimingUser.wallet.payer],
+        });
+      }
+
+      it("should claim a ticket signed by the ticket signer", async function () {
+        const args = ticketArgs(3);
+        const signatureIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: ticketSigner.secretKey,
+          message: ticketMessage(this.distributor, args),
+        });
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[0].tokenAccount);
+        await claimTicket(this, args, signatureIx);
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[0].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eqn(3));
+
+        const statsAccount = await program.account.claimStats.fetch(this.stats);
+        assert.ok(statsAccount.ticketsClaimed.eqn(3));
+        assert.ok(statsAccount.totalClaimed.eqn(0));
+      });
+
+      it("shouldn't claim a ticket over the total allocation", async function () {
+        const args = ticketArgs(merkleData.totalTokens + 1);
+        const signatureIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: ticketSigner.secretKey,
+          message: ticketMessage(this.distributor, args),
+        });
+
+        await assert.rejects(
+          async () => {
+            await claimTicket(this, args, signatureIx);
+          },
+          (err) => {
+            assert.equal(err.code, 6041);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't claim a ticket signed by another key", async function () {
+        const args = ticketArgs(3);
+        const signatureIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: anchor.web3.Keypair.generate().secretKey,
+          message: ticketMessage(this.distributor, args),
+        });
+
+        await assert.rejects(
+          async () => {
+            await claimTicket(this, args, signatureIx);
+          },
+          (err) => {
+            assert.equal(err.code, 6025);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't claim a ticket if another message is signed", async function () {
+        const args = ticketArgs(3);
+        const signatureIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: ticketSigner.secretKey,
+          message: ticketMessage(this.distributor, ticketArgs(1)),
+        });
+
+        await assert.rejects(
+          async () => {
+            await claimTicket(this, args, signatureIx);
+          },
+          (err) => {
+            assert.equal(err.code, 6025);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't claim a ticket if offsets point to another instruction", async function () {
+        const args = ticketArgs(3);
+        const signatureIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: ticketSigner.secretKey,
+          message: ticketMessage(this.distributor, args),
+        });
+        // the signature instruction goes first, so the ed25519 program still verifies
+        // the same data, but the offsets don't refer to the current instruction anymore
+        signatureIx.data.writeUInt16LE(0, 4);
+        signatureIx.data.writeUInt16LE(0, 8);
+        signatureIx.data.writeUInt16LE(0, 14);
+
+        await assert.rejects(
+          async () => {
+            await claimTicket(this, args, signatureIx);
+          },
+          (err) => {
+            assert.equal(err.code, 6025);
+            return true;
+          }
+        );
+      });
+    });
+
+    context("loyalty bonus", async function () {
+      async function setLoyaltyBonus(ctx: any, args: any) {
+        await program.rpc.setLoyaltyBonus(args, {
+          accounts: {
+            distributor: ctx.distributor,
+            root: ctx.root,
+            stats: ctx.stats,
+            config,
+            owner: provider.wallet.publicKey,
+            vault: ctx.vault,
+          }
+        });
+      }
+
+      it("shouldn't allow to withdraw the reserved bonus budget", async function () {
+        await setLoyaltyBonus(this, {
+          delaySec: new anchor.BN(0),
+          bonusBps: new anchor.BN(5000),
+          budget: new anchor.BN(900),
+        });
+
+        await assert.rejects(
+          async () => {
+            await client.withdrawTokens(new anchor.BN(200), this.distributor, this.clawbackDestination);
+          },
+          (err) => {
+            assert.equal(err.code, 6068);
+            return true;
+          }
+        );
+
+        await client.withdrawTokens(new anchor.BN(100), this.distributor, this.clawbackDestination);
+        const vaultAccount = await serumCmn.getTokenAccount(provider, this.vault);
+        assert.ok(vaultAccount.amount.eqn(900));
+      });
+
+      it("shouldn't update the root over the reserved bonus budget", async function () {
+        await setLoyaltyBonus(this, {
+          delaySec: new anchor.BN(0),
+          bonusBps: new anchor.BN(5000),
+          budget: new anchor.BN(900),
+        });
+
+        await assert.rejects(
+          async () => {
+            await client.updateRoot(this.distributor, merkleData.root, new anchor.BN(101), false);
+          },
+          (err) => {
+            assert.equal(err.code, 6040);
+            return true;
+          }
+        );
+
+        await client.updateRoot(this.distributor, merkleData.root, new anchor.BN(100), false);
+        const rootAccount = await program.account.merkleRoot.fetch(this.root);
+        assert.ok(rootAccount.totalAllocation.eqn(100));
+      });
+
+      it("should pay the bonus on top of the claim", async function () {
+        await setLoyaltyBonus(this, {
+          delaySec: new anchor.BN(0),
+          bonusBps: new anchor.BN(5000),
+          budget: new anchor.BN(100),
+        });
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        await claim(this.distributor, 4);
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eqn(6));
+
+        const statsAccount = await program.account.claimStats.fetch(this.stats);
+        assert.ok(statsAccount.totalClaimed.eqn(4));
+        assert.ok(statsAccount.bonusPaid.eqn(2));
+      });
+
+      it("shouldn't lift the claim with bonus over the per transaction limit", async function () {
+        await setLoyaltyBonus(this, {
+          delaySec: new anchor.BN(0),
+          bonusBps: new anchor.BN(5000),
+          budget: new anchor.BN(100),
+        });
+        await program.rpc.setMaxClaimPerTx(new anchor.BN(5), {
+          accounts: {
+            distributor: this.distributor,
+            config,
+            adminOrOwner: provider.wallet.publicKey,
+          }
+        });
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        await claim(this.distributor, 4);
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eqn(5));
+
+        const statsAccount = await program.account.claimStats.fetch(this.stats);
+        assert.ok(statsAccount.bonusPaid.eqn(1));
+      });
+    });
+
+    context("accelerate vesting", async function () {
+      beforeEach(async function () {
+        const nowTs = Date.now() / 1000;
+        const r = await setupDistributor([
+          {
+            tokenPercentage: new anchor.BN(10000),
+            startTs: new anchor.BN(nowTs + 3600),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          }
+        ]);
+        this.lockedDistributor = r.distributor;
+      });
+
+      it("shouldn't allow to accelerate vesting by user", async function () {
+        await assert.rejects(
+          async () => {
+            await program.rpc.accelerateVesting({
+              accounts: {
+                distributor: this.lockedDistributor,
+                config,
+                owner: user.publicKey,
+              },
+              signers: [user],
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should unlock the whole schedule by owner", async function () {
+        await program.rpc.accelerateVesting({
+          accounts: {
+            distributor: this.lockedDistributor,
+            config,
+            owner: provider.wallet.publicKey,
+          }
+        });
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.lockedDistributor);
+        assert.ok(distributorAccount.vesting.acceleratedAtTs !== null);
+
+        // the schedule starts in an hour, only the acceleration lets the claim through
+        const merkleElement = merkleData.proofs[4];
+        const elementClient = new claiming.Client(claimingUsers[4].wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(this.lockedDistributor, merkleElement.address);
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        await elementClient.claim(
+          this.lockedDistributor,
+          claimingUsers[4].tokenAccount,
+          merkleElement.amount,
+          merkleElement.proofs
+        );
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eq(merkleElement.amount));
+      });
+    });
+
+    context("shift schedule", async function () {
+      const SHIFT_SEC = 86400;
+
+      beforeEach(async function () {
+        const nowTs = Date.now() / 1000;
+        const r = await setupDistributor([
+          {
+            tokenPercentage: new anchor.BN(10000),
+            startTs: new anchor.BN(nowTs + 3600),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          }
+        ]);
+        this.lockedDistributor = r.distributor;
+        this.startTs = r.distributorAccount.vesting.schedule[0].startTs;
+      });
+
+      it("shouldn't allow to shift the schedule by user", async function () {
+        await assert.rejects(
+          async () => {
+            await program.rpc.shiftSchedule(new anchor.BN(SHIFT_SEC), {
+              accounts: {
+                distributor: this.lockedDistributor,
+                config,
+                adminOrOwner: user.publicKey,
+              },
+              signers: [user],
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6006);
+            return true;
+          }
+        );
+      });
+
+      it("should shift not started periods by admin", async function () {
+        await client.addAdmin(admin.publicKey);
+        await program.rpc.shiftSchedule(new anchor.BN(SHIFT_SEC), {
+          accounts: {
+            distributor: this.lockedDistributor,
+            config,
+            adminOrOwner: admin.publicKey,
+          },
+          signers: [admin],
+        });
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.lockedDistributor);
+        assert.ok(distributorAccount.vesting.schedule[0].startTs.eq(this.startTs.addn(SHIFT_SEC)));
+      });
+    });
+
+    context("freeze vault", async function () {
+      async function freezeVault(ctx: any) {
+        await program.rpc.freezeVault({
+          accounts: {
+            distributor: ctx.distributor,
+            config,
+            owner: provider.wallet.publicKey,
+          }
+        });
+      }
+
+      it("shouldn't allow to freeze the vault by admin", async function () {
+        await client.addAdmin(admin.publicKey);
+
+        await assert.rejects(
+          async () => {
+            await program.rpc.freezeVault({
+              accounts: {
+                distributor: this.distributor,
+                config,
+                owner: admin.publicKey,
+              },
+              signers: [admin],
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should freeze the vault by owner and block withdrawals", async function () {
+        await freezeVault(this);
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
+        assert.ok(distributorAccount.frozenAtTs !== null);
+
+        await assert.rejects(
+          async () => {
+            await client.withdrawTokens(new anchor.BN(100), this.distributor, this.clawbackDestination);
+          },
+          (err) => {
+            assert.equal(err.code, 6042);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't allow to unfreeze the vault before the timelock", async function () {
+        await freezeVault(this);
+
+        await assert.rejects(
+          async () => {
+            await program.rpc.unfreezeVault({
+              accounts: {
+                distributor: this.distributor,
+                config,
+                owner: provider.wallet.publicKey,
+              }
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6045);
+            return true;
+          }
+        );
+      });
+    });
+
+    context("recover token", async function () {
+      beforeEach(async function () {
+        const foreignMint = await createMint(provider);
+        this.foreignSource = await serumCmn.createTokenAccount(provider, foreignMint.publicKey, this.vaultAuthority);
+        this.foreignTarget = await serumCmn.createTokenAccount(provider, foreignMint.publicKey, provider.wallet.publicKey);
+        await foreignMint.mintTo(this.foreignSource, provider.wallet.publicKey, [], 50);
+      });
+
+      async function recoverToken(ctx: any, amount: number, source: anchor.web3.PublicKey, targetWallet: anchor.web3.PublicKey) {
+        await program.rpc.recoverToken(new anchor.BN(amount), {
+          accounts: {
+            distributor: ctx.distributor,
+            config,
+            owner: provider.wallet.publicKey,
+            vaultAuthority: ctx.vaultAuthority,
+            source,
+            targetWallet,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          }
+        });
+      }
+
+      it("shouldn't allow to recover tokens by user", async function () {
+        await assert.rejects(
+          async () => {
+            await program.rpc.recoverToken(new anchor.BN(50), {
+              accounts: {
+                distributor: this.distributor,
+                config,
+                owner: user.publicKey,
+                vaultAuthority: this.vaultAuthority,
+                source: this.foreignSource,
+                targetWallet: this.foreignTarget,
+                tokenProgram: spl.TOKEN_PROGRAM_ID,
+              },
+              signers: [user],
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should recover foreign tokens by owner", async function () {
+        await recoverToken(this, 50, this.foreignSource, this.foreignTarget);
+
+        const targetAccount = await serumCmn.getTokenAccount(provider, this.foreignTarget);
+        assert.ok(targetAccount.amount.eqn(50));
+      });
+
+      it("shouldn't allow to recover the distribution mint", async function () {
+        await assert.rejects(
+          async () => {
+            await recoverToken(this, 100, this.vault, this.clawbackDestination);
+          },
+          (err) => {
+            assert.equal(err.code, 6046);
+            return true;
+          }
+        );
+      });
+    });
+
+    context("claim on behalf", async function () {
+      const relayer = anchor.web3.Keypair.generate();
+
+      beforeEach(async function () {
+        await program.rpc.addRelayer({
+          accounts: {
+            config,
+            owner: provider.wallet.publicKey,
+            relayer: relayer.publicKey,
+          }
+        });
+
+        const merkleElement = merkleData.proofs[4];
+        const elementClient = new claiming.Client(claimingUsers[4].wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(this.distributor, merkleElement.address);
+        [this.userDetails] = await client.findUserDetailsAddress(this.distributor, merkleElement.address);
+      });
+
+      async function claimOnBehalf(ctx: any, signer: anchor.web3.Keypair) {
+        const merkleElement = merkleData.proofs[4];
+        await program.rpc.claimOnBehalf(
+          {
+            amount: merkleElement.amount,
+            merkleProof: merkleElement.proofs,
+            custodialDestination: false,
+          },
+          {
+            accounts: {
+              distributor: ctx.distributor,
+              root: ctx.root,
+              stats: ctx.stats,
+              config,
+              relayer: signer.publicKey,
+              user: merkleElement.address,
+              userDetails: ctx.userDetails,
+              vaultAuthority: ctx.vaultAuthority,
+              vault: ctx.vault,
+              targetWallet: claimingUsers[4].tokenAccount,
+              tokenProgram: spl.TOKEN_PROGRAM_ID,
+            },
+            signers: [signer],
+          }
+        );
+      }
+
+      it("shouldn't allow to claim on behalf by an unregistered relayer", async function () {
+        await assert.rejects(
+          async () => {
+            await claimOnBehalf(this, user);
+          },
+          (err) => {
+            assert.equal(err.code, 6028);
+            return true;
+          }
+        );
+      });
+
+      it("should claim on behalf of the user by a registered relayer", async function () {
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        while (true) {
+          try {
+            await claimOnBehalf(this, relayer);
+            break;
+          } catch (err: any) {
+            if (err.code != 6015) {
+              throw err;
+            }
+            await serumCmn.sleep(4000);
+          }
+        }
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eq(merkleData.proofs[4].amount));
+      });
+    });
+
+    context("gate collection", async function () {
+      // fixtures loaded by the validator, the mint authority is derived from a fixed seed
+      const gateCollection = new anchor.web3.PublicKey("GyGKxMyg1p9SsHfm15MkNUu1u9TN2JtTspcdmrtGUdse");
+      const gateMint = new anchor.web3.PublicKey("9hSR6S7WPtxmTojgo6GG3k4yDPecgJY292j7xrsUGWBu");
+      const gateMetadata = new anchor.web3.PublicKey("5WHKuWMRUkk1GfsgFDoUpQtryyKrmBAHUUqo7gmifcXh");
+      const gateMintAuthority = anchor.web3.Keypair.fromSeed(new Uint8Array(32).fill(1));
+
+      beforeEach(async function () {
+        await program.rpc.setGateCollection(gateCollection, {
+          accounts: {
+            distributor: this.distributor,
+            config,
+            adminOrOwner: provider.wallet.publicKey,
+          }
+        });
+      });
+
+      it("shouldn't allow to set the gate collection by user", async function () {
+        await assert.rejects(
+          async () => {
+            await program.rpc.setGateCollection(null, {
+              accounts: {
+                distributor: this.distributor,
+                config,
+                adminOrOwner: user.publicKey,
+              },
+              signers: [user],
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6006);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't claim without the gate NFT", async function () {
+        await assert.rejects(
+          async () => {
+            await claim(this.distributor, 4);
+          },
+          (err) => {
+            assert.equal(err.code, 6058);
+            return true;
+          }
+        );
+      });
+
+      it("should claim holding an NFT of the gate collection", async function () {
+        const merkleElement = merkleData.proofs[4];
+        const claimingUser = claimingUsers[4];
+        const elementClient = new claiming.Client(claimingUser.wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(this.distributor, merkleElement.address);
+        const [userDetails] = await client.findUserDetailsAddress(this.distributor, merkleElement.address);
+
+        const gateToken = new spl.Token(provider.connection, gateMint, spl.TOKEN_PROGRAM_ID, provider.wallet.payer);
+        const nftAccount = await serumCmn.createTokenAccount(provider, gateMint, claimingUser.wallet.publicKey);
+        await gateToken.mintTo(nftAccount, gateMintAuthority, [], 1);
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        while (true) {
+          try {
+            await program.rpc.claim(
+              {
+                amount: merkleElement.amount,
+                merkleProof: merkleElement.proofs,
+                custodialDestination: false,
+              },
+              {
+                accounts: {
+                  distributor: this.distributor,
+                  root: this.root,
+                  stats: this.stats,
+                  user: claimingUser.wallet.publicKey,
+                  userDetails,
+                  vaultAuthority: this.vaultAuthority,
+                  vault: this.vault,
+                  targetWallet: claimingUser.tokenAccount,
+                  tokenProgram: spl.TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts: [
+                  { pubkey: nftAccount, isWritable: false, isSigner: false },
+                  { pubkey: gateMetadata, isWritable: false, isSigner: false },
+                ],
+                signers: [claimingUser.wallet.payer],
+              }
+            );
+            break;
+          } catch (err: any) {
+            if (err.code != 6015) {
+              throw err;
+            }
+            await serumCmn.sleep(4000);
+          }
+        }
+        const after = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eq(merkleElement.amount));
+      });
+    });
+
+    context("holdback", async function () {
+      async function initHoldback(ctx: any, windowSec: number) {
+        [ctx.holdbackVault] = await anchor.web3.PublicKey.findProgramAddress(
+          [ctx.distributor.toBytes(), new TextEncoder().encode("holdback")],
+          program.programId
+        );
+        await program.rpc.initHoldback(
+          {
+            bps: new anchor.BN(5000),
+            windowSec: new anchor.BN(windowSec),
+          },
+          {
+            accounts: {
+              distributor: ctx.distributor,
+              config,
+              adminOrOwner: provider.wallet.publicKey,
+              vaultAuthority: ctx.vaultAuthority,
+              holdbackVault: ctx.holdbackVault,
+              mint: mint.publicKey,
+              systemProgram: anchor.web3.SystemProgram.programId,
+              tokenProgram: spl.TOKEN_PROGRAM_ID,
+              rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            }
+          }
+        );
+
+        const wallet = claimingUsers[4].wallet.publicKey;
+        [ctx.holdbackRecord] = await anchor.web3.PublicKey.findProgramAddress(
+          [ctx.distributor.toBytes(), new TextEncoder().encode("holdback"), wallet.toBytes()],
+          program.programId
+        );
+        await program.rpc.initHoldbackRecord({
+          accounts: {
+            payer: provider.wallet.publicKey,
+            user: wallet,
+            holdbackRecord: ctx.holdbackRecord,
+            distributor: ctx.distributor,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          }
+        });
+      }
+
+      async function claimWithHoldback(ctx: any) {
+        await claimWithAccounts(ctx, 4, [
+          { pubkey: ctx.holdbackVault, isWritable: true, isSigner: false },
+          { pubkey: ctx.holdbackRecord, isWritable: true, isSigner: false },
+        ]);
+      }
+
+      async function releaseHoldback(ctx: any, signer: anchor.web3.Keypair, targetWallet: anchor.web3.PublicKey) {
+        await program.rpc.releaseHoldback({
+          accounts: {
+            distributor: ctx.distributor,
+            user: signer.publicKey,
+            holdbackRecord: ctx.holdbackRecord,
+            vaultAuthority: ctx.vaultAuthority,
+            holdbackVault: ctx.holdbackVault,
+            targetWallet,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          },
+          signers: [signer],
+        });
+      }
+
+      it("shouldn't allow to init holdback by user", async function () {
+        const [holdbackVault] = await anchor.web3.PublicKey.findProgramAddress(
+          [this.distributor.toBytes(), new TextEncoder().encode("holdback")],
+          program.programId
+        );
+
+        await assert.rejects(
+          async () => {
+            await program.rpc.initHoldback(
+              {
+                bps: new anchor.BN(5000),
+                windowSec: new anchor.BN(0),
+              },
+              {
+                accounts: {
+                  distributor: this.distributor,
+                  config,
+                  adminOrOwner: user.publicKey,
+                  vaultAuthority: this.vaultAuthority,
+                  holdbackVault,
+                  mint: mint.publicKey,
+                  systemProgram: anchor.web3.SystemProgram.programId,
+                  tokenProgram: spl.TOKEN_PROGRAM_ID,
+                  rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                },
+                signers: [user],
+              }
+            );
+          },
+          (err) => {
+            assert.equal(err.code, 6006);
+            return true;
+          }
+        );
+      });
+
+      it("should split the claim between the user and the holdback vault", async function () {
+        await initHoldback(this, 3600);
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        await claimWithHoldback(this);
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eqn(2));
+
+        const holdbackVaultAccount = await serumCmn.getTokenAccount(provider, this.holdbackVault);
+        assert.ok(holdbackVaultAccount.amount.eqn(2));
+
+        const holdbackRecordAccount = await program.account.holdbackRecord.fetch(this.holdbackRecord);
+        assert.ok(holdbackRecordAccount.deposits[0].amount.eqn(2));
+      });
+
+      it("shouldn't release before the window ends", async function () {
+        await initHoldback(this, 3600);
+        await claimWithHoldback(this);
+
+        await assert.rejects(
+          async () => {
+            await releaseHoldback(this, claimingUsers[4].wallet.payer, claimingUsers[4].tokenAccount);
+          },
+          (err) => {
+            assert.equal(err.code, 6032);
+            return true;
+          }
+        );
+      });
+
+      it("should release to the user after the window ends", async function () {
+        await initHoldback(this, 0);
+        await claimWithHoldback(this);
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        await releaseHoldback(this, claimingUsers[4].wallet.payer, claimingUsers[4].tokenAccount);
+        const after = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eqn(2));
+
+        const holdbackVaultAccount = await serumCmn.getTokenAccount(provider, this.holdbackVault);
+        assert.ok(holdbackVaultAccount.amount.eqn(0));
+      });
+
+      it("shouldn't release the record of another user", async function () {
+        await initHoldback(this, 0);
+        await claimWithHoldback(this);
+        const targetWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, user.publicKey);
+
+        await assert.rejects(
+          async () => {
+            await releaseHoldback(this, user, targetWallet);
+          },
+          (err) => {
+            // the record is derived from the signer
+            assert.equal(err.code, 2006);
+            return true;
+          }
+        );
+      });
+    });
+    context("split distributor", async function () {
+      async function splitDistributor(ctx: any, amount: number, signer?: anchor.web3.Keypair) {
+        const newDistributor = anchor.web3.Keypair.generate();
+        const [newVaultAuthority, vaultBump] = await client.findVaultAuthority(newDistributor.publicKey);
+        const [newRoot, _rootBump] = await client.findRootAddress(newDistributor.publicKey);
+        const [newStats, _statsBump] = await client.findStatsAddress(newDistributor.publicKey);
+        const newVault = await serumCmn.createTokenAccount(provider, mint.publicKey, newVaultAuthority);
+
+        await program.rpc.splitDistributor(
+          {
+            amount: new anchor.BN(amount),
+            distributor: {
+              vaultBump,
+              merkleRoot: merkleData.root,
+              schedule: mockSchedule(),
+              positionNfts: false,
+              maxClaimPerTx: null,
+              category: { ido: {} },
+              totalAllocation: new anchor.BN(merkleData.totalTokens),
+              relativeSchedule: false,
+              maxProofLen: null,
+            },
+          },
+          {
+            accounts: {
+              config,
+              owner: signer ? signer.publicKey : provider.wallet.publicKey,
+              distributor: ctx.distributor,
+              vaultAuthority: ctx.vaultAuthority,
+              vault: ctx.vault,
+              newDistributor: newDistributor.publicKey,
+              newRoot,
+              newStats,
+              newVaultAuthority,
+              newVault,
+              mint: mint.publicKey,
+              tokenProgram: spl.TOKEN_PROGRAM_ID,
+              systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: signer ? [newDistributor, signer] : [newDistributor],
+          }
+        );
+
+        return newDistributor.publicKey;
+      }
+
+      it("shouldn't allow to split by user", async function () {
+        await assert.rejects(
+          async () => {
+            await splitDistributor(this, merkleData.totalTokens, user);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't split an amount other than the new allocation", async function () {
+        await assert.rejects(
+          async () => {
+            await splitDistributor(this, merkleData.totalTokens - 1);
+          },
+          (err) => {
+            assert.equal(err.code, 6069);
+            return true;
+          }
+        );
+      });
+
+      it("should stop claims on the old distributor", async function () {
+        await splitDistributor(this, merkleData.totalTokens);
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
+        assert.ok(distributorAccount.stopped);
+
+        await assert.rejects(
+          async () => {
+            await claim(this.distributor, 4);
+          },
+          (err) => {
+            assert.equal(err.code, 6064);
+            return true;
+          }
+        );
+      });
+
+      it("should claim from the new distributor", async function () {
+        const newDistributor = await splitDistributor(this, merkleData.totalTokens);
+
+        const newDistributorAccount = await program.account.merkleDistributor.fetch(newDistributor);
+        assert.ok(newDistributorAccount.splitFrom.equals(this.distributor));
+        let newVaultAccount = await serumCmn.getTokenAccount(provider, newDistributorAccount.vault);
+        assert.ok(newVaultAccount.amount.eqn(merkleData.totalTokens));
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        const [merkleElement, claimingUser] = await claim(newDistributor, 4);
+
+        const after = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eq(merkleElement.amount));
+        newVaultAccount = await serumCmn.getTokenAccount(provider, newDistributorAccount.vault);
+        assert.ok(newVaultAccount.amount.eq(new anchor.BN(merkleData.totalTokens).sub(merkleElement.amount)));
+      });
+    });
+    context("withdrawal schedule", async function () {
+      async function setWithdrawalSchedule(ctx: any, signer: anchor.web3.Keypair) {
+        const nowTs = Date.now() / 1000;
+        [ctx.withdrawalSchedule] = await anchor.web3.PublicKey.findProgramAddress(
+          [ctx.distributor.toBytes(), new TextEncoder().encode("withdrawal_schedule")],
+          program.programId
+        );
+
+        // half of the tokens is vested already, the other half in an hour
+        await program.rpc.setWithdrawalSchedule(
+          {
+            schedule: [
+              {
+                tokenPercentage: new anchor.BN(5000),
+                startTs: new anchor.BN(nowTs - 60),
+                intervalSec: new anchor.BN(1),
+                times: new anchor.BN(1),
+                airdropped: false,
+              },
+              {
+                tokenPercentage: new anchor.BN(5000),
+                startTs: new anchor.BN(nowTs + 3600),
+                intervalSec: new anchor.BN(1),
+                times: new anchor.BN(1),
+                airdropped: false,
+              },
+            ],
+            totalAmount: new anchor.BN(100),
+          },
+          {
+            accounts: {
+              distributor: ctx.distributor,
+              config,
+              owner: signer.publicKey,
+              withdrawalSchedule: ctx.withdrawalSchedule,
+              systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [signer],
+          }
+        );
+      }
+
+      async function withdrawVestedTokens(ctx: any, signer: anchor.web3.Keypair, amount: number) {
+        await program.rpc.withdrawVestedTokens(new anchor.BN(amount), {
+          accounts: {
+            distributor: ctx.distributor,
+            stats: ctx.stats,
+            config,
+            owner: signer.publicKey,
+            withdrawalSchedule: ctx.withdrawalSchedule,
+            vaultAuthority: ctx.vaultAuthority,
+            vault: ctx.vault,
+            targetWallet: ctx.clawbackDestination,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          },
+          signers: [signer],
+        });
+      }
+
+      it("shouldn't allow to set the withdrawal schedule by user", async function () {
+        await assert.rejects(
+          async () => {
+            await setWithdrawalSchedule(this, user);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't allow to withdraw vested tokens by user", async function () {
+        await setWithdrawalSchedule(this, provider.wallet.payer);
+
+        await assert.rejects(
+          async () => {
+            await withdrawVestedTokens(this, user, 10);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should withdraw up to the vested amount", async function () {
+        await setWithdrawalSchedule(this, provider.wallet.payer);
+
+        await withdrawVestedTokens(this, provider.wallet.payer, 50);
+        const clawbackAccount = await serumCmn.getTokenAccount(provider, this.clawbackDestination);
+        assert.ok(clawbackAccount.amount.eqn(50));
+
+        await assert.rejects(
+          async () => {
+            await withdrawVestedTokens(this, provider.wallet.payer, 1);
+          },
+          (err) => {
+            assert.equal(err.code, 6050);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't withdraw over the vested amount", async function () {
+        await setWithdrawalSchedule(this, provider.wallet.payer);
+
+        await assert.rejects(
+          async () => {
+            await withdrawVestedTokens(this, provider.wallet.payer, 51);
+          },
+          (err) => {
+            assert.equal(err.code, 6050);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't withdraw tokens at once after the schedule is set", async function () {
+        await setWithdrawalSchedule(this, provider.wallet.payer);
+
+        await assert.rejects(
+          async () => {
+            await client.withdrawTokens(new anchor.BN(10), this.distributor, this.clawbackDestination);
+          },
+          (err) => {
+            assert.equal(err.code, 6049);
+            return true;
+          }
+        );
+      });
+    });
+    context("repair user details", async function () {
+      async function repairUserDetails(ctx: any, signer: anchor.web3.Keypair, repairId: number, claimedAmount: number) {
+        const wallet = claimingUsers[4].wallet.publicKey;
+        const [userDetails] = await client.findUserDetailsAddress(ctx.distributor, wallet);
+        const [repairRecord] = await anchor.web3.PublicKey.findProgramAddress(
+          [userDetails.toBytes(), new TextEncoder().encode("repair"), new anchor.BN(repairId).toArrayLike(Buffer, "be", 8)],
+          program.programId
+        );
+
+        await program.rpc.repairUserDetails(
+          {
+            repairId: new anchor.BN(repairId),
+            claimedAmount: new anchor.BN(claimedAmount),
+            lastClaimedAtTs: new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+          },
+          {
+            accounts: {
+              distributor: ctx.distributor,
+              root: ctx.root,
+              config,
+              owner: signer.publicKey,
+              user: wallet,
+              userDetails,
+              repairRecord,
+              systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [signer],
+          }
+        );
+
+        return [userDetails, repairRecord];
+      }
+
+      beforeEach(async function () {
+        const elementClient = new claiming.Client(claimingUsers[4].wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(this.distributor, claimingUsers[4].wallet.publicKey);
+      });
+
+      it("shouldn't allow to repair by user", async function () {
+        await assert.rejects(
+          async () => {
+            await repairUserDetails(this, user, 1, 2);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should repair and keep a record by owner", async function () {
+        const [userDetails, repairRecord] = await repairUserDetails(this, provider.wallet.payer, 1, 2);
+
+        const userDetailsAccount = await program.account.userDetails.fetch(userDetails);
+        assert.ok(userDetailsAccount.claimedAmount.eqn(2));
+
+        const repairRecordAccount = await program.account.repairRecord.fetch(repairRecord);
+        assert.ok(repairRecordAccount.userDetails.equals(userDetails));
+        assert.ok(repairRecordAccount.repairedBy.equals(provider.wallet.publicKey));
+        assert.ok(repairRecordAccount.oldClaimedAmount.eqn(0));
+        assert.ok(repairRecordAccount.claimedAmount.eqn(2));
+      });
+
+      it("shouldn't repair to more than the tree holds", async function () {
+        await assert.rejects(
+          async () => {
+            await repairUserDetails(this, provider.wallet.payer, 1, merkleData.totalTokens + 1);
+          },
+          (err) => {
+            assert.equal(err.code, 6056);
+            return true;
+          }
+        );
+      });
+    });
+    context("custodians", async function () {
+      async function setCustodian(ctx: any, add: boolean, signer: anchor.web3.Keypair, custodian: anchor.web3.PublicKey) {
+        const accounts = {
+          accounts: {
+            distributor: ctx.distributor,
+            config,
+            owner: signer.publicKey,
+            custodian,
+          },
+          signers: [signer],
+        };
+        if (add) {
+          await program.rpc.addCustodian(accounts);
+        } else {
+          await program.rpc.removeCustodian(accounts);
+        }
+      }
+
+      beforeEach(async function () {
+        this.custodian = anchor.web3.Keypair.generate().publicKey;
+        this.custodianWallet = await serumCmn.createTokenAccount(provider, mint.publicKey, this.custodian);
+      });
+
+      it("shouldn't allow to add custodian by user", async function () {
+        await assert.rejects(
+          async () => {
+            await setCustodian(this, true, user, this.custodian);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't remove unknown custodian", async function () {
+        await assert.rejects(
+          async () => {
+            await setCustodian(this, false, provider.wallet.payer, this.custodian);
+          },
+          (err) => {
+            assert.equal(err.code, 6048);
+            return true;
+          }
+        );
+      });
+
+      it("should claim into the custodian's account with the flag on", async function () {
+        await setCustodian(this, true, provider.wallet.payer, this.custodian);
+
+        await claimWithAccounts(this, 4, [], this.custodianWallet, true);
+
+        const custodianAccount = await serumCmn.getTokenAccount(provider, this.custodianWallet);
+        assert.ok(custodianAccount.amount.eq(merkleData.proofs[4].amount));
+      });
+
+      it("shouldn't claim into the custodian's account with the flag off", async function () {
+        await setCustodian(this, true, provider.wallet.payer, this.custodian);
+
+        await assert.rejects(
+          async () => {
+            await claimWithAccounts(this, 4, [], this.custodianWallet, false);
+          },
+          (err) => {
+            assert.equal(err.code, 6022);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't claim into the account of a removed custodian", async function () {
+        await setCustodian(this, true, provider.wallet.payer, this.custodian);
+        await setCustodian(this, false, provider.wallet.payer, this.custodian);
+
+        await assert.rejects(
+          async () => {
+            await claimWithAccounts(this, 4, [], this.custodianWallet, true);
+          },
+          (err) => {
+            assert.equal(err.code, 6022);
+            return true;
+          }
+        );
+      });
+    });
+    context("clawback destination", async function () {
+      async function proposeClawbackDestination(ctx: any, signer: anchor.web3.Keypair, clawbackDestination: anchor.web3.PublicKey) {
+        await program.rpc.proposeClawbackDestination({
+          accounts: {
+            distributor: ctx.distributor,
+            config,
+            owner: signer.publicKey,
+            clawbackDestination,
+          },
+          signers: [signer],
+        });
+      }
+
+      beforeEach(async function () {
+        this.newClawbackDestination = await serumCmn.createTokenAccount(provider, mint.publicKey, provider.wallet.publicKey);
+      });
+
+      it("shouldn't allow to propose clawback destination by user", async function () {
+        await assert.rejects(
+          async () => {
+            await proposeClawbackDestination(this, user, this.newClawbackDestination);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't apply clawback destination before the timelock ends", async function () {
+        await proposeClawbackDestination(this, provider.wallet.payer, this.newClawbackDestination);
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
+        assert.ok(distributorAccount.pendingClawbackDestination.destination.equals(this.newClawbackDestination));
+
+        await assert.rejects(
+          async () => {
+            await program.rpc.applyClawbackDestination({
+              accounts: {
+                distributor: this.distributor,
+                config,
+                owner: provider.wallet.publicKey,
+              },
+            });
+          },
+          (err) => {
+            assert.equal(err.code, 6063);
+            return true;
+          }
+        );
+
+        const after = await program.account.merkleDistributor.fetch(this.distributor);
+        assert.ok(after.clawbackDestination.equals(this.clawbackDestination));
+      });
+    });
+    context("relative schedule", async function () {
+      beforeEach(async function () {
+        const distributor = anchor.web3.Keypair.generate();
+        const [vaultAuthority, vaultBump] = await client.findVaultAuthority(distributor.publicKey);
+        const [root, _rootBump] = await client.findRootAddress(distributor.publicKey);
+        const [stats, _statsBump] = await client.findStatsAddress(distributor.publicKey);
+        const vault = await serumCmn.createTokenAccount(provider, mint.publicKey, vaultAuthority);
+
+        // everything unlocks right at TGE
+        await program.rpc.initialize(
+          {
+            vaultBump,
+            merkleRoot: merkleData.root,
+            schedule: [
+              {
+                tokenPercentage: new anchor.BN(10000),
+                startTs: new anchor.BN(0),
+                intervalSec: new anchor.BN(1),
+                times: new anchor.BN(1),
+                airdropped: false,
+              },
+            ],
+            positionNfts: false,
+            maxClaimPerTx: null,
+            category: { ido: {} },
+            totalAllocation: new anchor.BN(merkleData.totalTokens),
+            relativeSchedule: true,
+            maxProofLen: null,
+          },
+          {
+            accounts: {
+              distributor: distributor.publicKey,
+              root,
+              stats,
+              adminOrOwner: provider.wallet.publicKey,
+              vaultAuthority,
+              vault,
+              mint: mint.publicKey,
+              clawbackDestination: this.clawbackDestination,
+              config,
+              systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [distributor],
+          }
+        );
+        await mint.mintTo(vault, provider.wallet.publicKey, [], 1000);
+
+        this.relativeDistributor = distributor.publicKey;
+      });
+
+      async function activateTge(distributor: anchor.web3.PublicKey, signer: anchor.web3.Keypair) {
+        await program.rpc.activateTge(null, {
+          accounts: {
+            distributor,
+            config,
+            owner: signer.publicKey,
+          },
+          signers: [signer],
+        });
+      }
+
+      it("shouldn't claim before TGE is activated", async function () {
+        await assert.rejects(
+          async () => {
+            await claim(this.relativeDistributor, 4);
+          },
+          (err) => {
+            assert.equal(err.code, 6054);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't allow to activate TGE by user", async function () {
+        await assert.rejects(
+          async () => {
+            await activateTge(this.relativeDistributor, user);
+          },
+          (err) => {
+            assert.equal(err.code, 6005);
+            return true;
+          }
+        );
+      });
+
+      it("should claim after TGE is activated", async function () {
+        await activateTge(this.relativeDistributor, provider.wallet.payer);
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.relativeDistributor);
+        assert.ok(distributorAccount.activationTs.gtn(0));
+        assert.ok(distributorAccount.vesting.schedule[0].startTs.eq(distributorAccount.activationTs));
+
+        const before = await serumCmn.getTokenAccount(provider, claimingUsers[4].tokenAccount);
+        const [merkleElement, claimingUser] = await claim(this.relativeDistributor, 4);
+        const after = await serumCmn.getTokenAccount(provider, claimingUser.tokenAccount);
+        assert.ok(after.amount.sub(before.amount).eq(merkleElement.amount));
+      });
+    });
+    context("complete period", async function () {
+      async function completePeriod(distributor: anchor.web3.PublicKey, index: number) {
+        // the crank needs no signer besides the fee payer
+        await program.rpc.completePeriod(new anchor.BN(index), {
+          accounts: {
+            distributor,
+          }
+        });
+      }
+
+      it("should complete an ended period", async function () {
+        // wait until the only period ends
+        await serumCmn.sleep(4000);
+
+        await completePeriod(this.distributor, 0);
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
+        assert.ok(distributorAccount.completedPeriods[0]);
+      });
+
+      it("should complete a period twice without changes", async function () {
+        await serumCmn.sleep(4000);
+
+        await completePeriod(this.distributor, 0);
+        await completePeriod(this.distributor, 0);
+
+        const distributorAccount = await program.account.merkleDistributor.fetch(this.distributor);
+        assert.ok(distributorAccount.completedPeriods[0]);
+      });
+
+      it("shouldn't complete a period which hasn't ended", async function () {
+        const nowTs = Date.now() / 1000;
+        const r = await setupDistributor([
+          {
+            tokenPercentage: new anchor.BN(10000),
+            startTs: new anchor.BN(nowTs + 3600),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          },
+        ]);
+
+        await assert.rejects(
+          async () => {
+            await completePeriod(r.distributor, 0);
+          },
+          (err) => {
+            assert.equal(err.code, 6052);
+            return true;
+          }
+        );
+      });
+
+      it("shouldn't complete a period out of the schedule", async function () {
+        await assert.rejects(
+          async () => {
+            await completePeriod(this.distributor, 1);
+          },
+          (err) => {
+            assert.equal(err.code, 6051);
+            return true;
+          }
+        );
+      });
+    });
+    context("verify proofs", async function () {
+      async function verifyProofs(ctx: any, allocations: any[]) {
+        await program.rpc.verifyProofs(
+          { allocations },
+          {
+            accounts: {
+              distributor: ctx.distributor,
+              root: ctx.root,
+              config,
+              adminOrOwner: provider.wallet.publicKey,
+            }
+          }
+        );
+      }
+
+      function allocation(proof: merkle.MerkleProof) {
+        return {
+          wallet: proof.address,
+          amount: proof.amount,
+          merkleProof: proof.proofs,
+        };
+      }
+
+      it("should verify valid proofs", async function () {
+        await verifyProofs(this, [allocation(merkleData.proofs[3]), allocation(merkleData.proofs[4])]);
+      });
+
+      it("should fail if any of the proofs is invalid", async function () {
+        const invalid = allocation(merkleData.proofs[4]);
+        invalid.merkleProof = merkleData.proofs[3].proofs;
+
+        await assert.rejects(
+          async () => {
+            await verifyProofs(this, [allocation(merkleData.proofs[3]), invalid]);
+          },
+          (err) => {
+            assert.equal(err.code, 6003);
+            return true;
+          }
+        );
+      });
+    });
+    context("user details analytics", async function () {
+      it("should count claims and keep the first claim time", async function () {
+        const nowTs = Date.now() / 1000;
+        const r = await setupDistributor([
+          {
+            tokenPercentage: new anchor.BN(5000),
+            startTs: new anchor.BN(nowTs + 2),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          },
+          {
+            tokenPercentage: new anchor.BN(5000),
+            startTs: new anchor.BN(nowTs + 10),
+            intervalSec: new anchor.BN(1),
+            times: new anchor.BN(1),
+            airdropped: false,
+          },
+        ]);
+        const [userDetails] = await client.findUserDetailsAddress(r.distributor, claimingUsers[4].wallet.publicKey);
+
+        // wait until the first period is unlocked
+        await serumCmn.sleep(4000);
+        const [merkleElement, claimingUser] = await claim(r.distributor, 4);
+
+        let userDetailsAccount = await program.account.userDetails.fetch(userDetails);
+        assert.ok(userDetailsAccount.claimCount.eqn(1));
+        assert.ok(userDetailsAccount.firstClaimedAtTs.gtn(0));
+        assert.ok(userDetailsAccount.firstClaimedAtTs.eq(userDetailsAccount.lastClaimedAtTs));
+        const firstClaimedAtTs = userDetailsAccount.firstClaimedAtTs;
+
+        // wait until the second period is unlocked
+        await serumCmn.sleep(8000);
+        const elementClient = new claiming.Client(claimingUser.wallet, claiming.LOCALNET);
+        await elementClient.claim(r.distributor, claimingUser.tokenAccount, merkleElement.amount, merkleElement.proofs);
+
+        userDetailsAccount = await program.account.userDetails.fetch(userDetails);
+        assert.ok(userDetailsAccount.claimCount.eqn(2));
+        assert.ok(userDetailsAccount.firstClaimedAtTs.eq(firstClaimedAtTs));
+        assert.ok(userDetailsAccount.lastClaimedAtTs.gt(firstClaimedAtTs));
+        assert.ok(userDetailsAccount.claimedAmount.eq(merkleElement.amount));
+      });
+
+      it("should keep current user details as is on migration", async function () {
+        const wallet = claimingUsers[4].wallet.publicKey;
+        const elementClient = new claiming.Client(claimingUsers[4].wallet, claiming.LOCALNET);
+        await elementClient.initUserDetails(this.distributor, wallet);
+        const [userDetails] = await client.findUserDetailsAddress(this.distributor, wallet);
+        const before = await program.account.userDetails.fetch(userDetails);
+
+        await program.rpc.migrateUserDetails({
+          accounts: {
+            payer: provider.wallet.publicKey,
+            user: wallet,
+            userDetails,
+            distributor: this.distributor,
+            root: this.root,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          }
+        });
+
+        const after = await program.account.userDetails.fetch(userDetails);
+        assert.ok(after.claimedAmount.eq(before.claimedAmount));
+        assert.ok(after.claimCount.eq(before.claimCount));
+        assert.equal(after.accountVersion, 1);
+      });
+    });
+    context("max proof length", async function () {
+      it("shouldn't claim with a proof longer than the tree depth", async function () {
+        // 5 leaves give proofs of 2 or 3 nodes
+        await program.rpc.updateRoot(
+          {
+            merkleRoot: merkleData.root,
+            unpause: false,
+            totalAllocation: new anchor.BN(merkleData.totalTokens),
+            maxProofLen: 1,
+          },
+          {
+            accounts: {
+              distributor: this.distributor,
+              root: this.root,
+              stats: this.stats,
+              config,
+              adminOrOwner: provider.wallet.publicKey,
+              vault: this.vault,
+            }
+          }
+        );
+
+        await assert.rejects(
+          async () => {
+            await claim(this.distributor, 4);
+          },
+          (err) => {
+            assert.equal(err.code, 6057);
+            return true;
+          }
+        );
+      });
+    });
   });
 });
@@ -133,6 +133,42 @@ export class Client {
     return config;
   }
 
+  /**
+   * Initializes the treasury, it's only required to charge the distributor creation fee
+   * @returns {Promise<anchor.web3.PublicKey>} Returns the public key of treasury
+   */
+  async createTreasury() {
+    const [config, _bump] = await this.findConfigAddress();
+    const [treasury, _treasuryBump] = await this.findTreasuryAddress();
+
+    await this.program.rpc.initTreasury(
+      {
+        accounts: {
+          config,
+          owner: this.provider.wallet.publicKey,
+          treasury,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        }
+      }
+    );
+
+    return treasury;
+  }
+
+  /**
+   * Find a valid program address of treasury account
+   * @returns {Promise<[anchor.web3.PublicKey, number]>} Returns the public key of treasury and the bump number
+   */
+  async findTreasuryAddress(): Promise<[anchor.web3.PublicKey, number]> {
+    const [treasury, bump] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        new TextEncoder().encode("treasury")
+      ],
+      this.program.programId,
+    );
+    return [treasury, bump];
+  }
+
   /**
    * Find a program address of vault authority
    * @param {anchor.web3.PublicKey} distributor - public key of distributor
@@ -148,18 +184,91 @@ export class Client {
     return [vaultAuthority, vaultBump];
   }
 
+  /**
+   * Find a program address of the merkle root account of distributor
+   * @param {anchor.web3.PublicKey} distributor - public key of distributor
+   * @returns {Promise<[anchor.web3.PublicKey, number]>} Returns the public key of merkle root account and the bump number
+   */
+  async findRootAddress(distributor: anchor.web3.PublicKey): Promise<[anchor.web3.PublicKey, number]> {
+    const [root, bump] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        distributor.toBytes(),
+        new TextEncoder().encode("root")
+      ],
+      this.program.programId,
+    );
+    return [root, bump];
+  }
+
+  /**
+   * Find a program address of the claim statistics account of distributor
+   * @param {anchor.web3.PublicKey} distributor - public key of distributor
+   * @returns {Promise<[anchor.web3.PublicKey, number]>} Returns the public key of claim statistics account and the bump number
+   */
+  async findStatsAddress(distributor: anchor.web3.PublicKey): Promise<[anchor.web3.PublicKey, number]> {
+    const [stats, bump] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        distributor.toBytes(),
+        new TextEncoder().encode("stats")
+      ],
+      this.program.programId,
+    );
+    return [stats, bump];
+  }
+
+  /**
+   * Find a program address of the position NFT mint of user
+   * @param {anchor.web3.PublicKey} distributor - public key of distributor
+   * @param {anchor.web3.PublicKey} user - public key of the original owner of the allocation
+   * @returns {Promise<[anchor.web3.PublicKey, number]>} Returns the public key of position mint and the bump number
+   */
+  async findPositionMintAddress(
+    distributor: anchor.web3.PublicKey,
+    user: anchor.web3.PublicKey
+  ): Promise<[anchor.web3.PublicKey, number]> {
+    const [positionMint, bump] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        distributor.toBytes(),
+        new TextEncoder().encode("position"),
+        user.toBytes(),
+      ],
+      this.program.programId,
+    );
+    return [positionMint, bump];
+  }
+
   /**
    * Initializes distributor
    * @param {anchor.web3.PublicKey} mint - public key of mint to distibute
    * @param {number[]} merkleRoot
    * @param {Period[]} schedule - token distribution data (amount, time)
+   * @param {anchor.BN} totalAllocation - sum of all allocations in the merkle tree
+   * @param {boolean} positionNfts (optional) - represent user allocations by transferable position NFTs
+   * @param {anchor.web3.PublicKey} clawbackDestination (optional) - token account unclaimed tokens are withdrawn to
    * @returns {Promise<anchor.web3.PublicKey>} Returns the public key of newly created distributor
    */
-  async createDistributor(mint: anchor.web3.PublicKey, merkleRoot: number[], schedule: Period[]): Promise<anchor.web3.PublicKey> {
+  async createDistributor(
+    mint: anchor.web3.PublicKey,
+    merkleRoot: number[],
+    schedule: Period[],
+    totalAllocation: anchor.BN,
+    positionNfts?: boolean,
+    clawbackDestination?: anchor.web3.PublicKey
+  ): Promise<anchor.web3.PublicKey> {
     const distributor = anchor.web3.Keypair.generate();
     const [vaultAuthority, vaultBump] = await this.findVaultAuthority(distributor.publicKey);
+    const [root, _rootBump] = await this.findRootAddress(distributor.publicKey);
+    const [stats, _statsBump] = await this.findStatsAddress(distributor.publicKey);
     const [config, _bump] = await this.findConfigAddress();
 
+    // the treasury is only required to collect the creation fee
+    const configAccount = await this.program.account.config.fetch(config);
+    const remainingAccounts = [];
+    if (!configAccount.creationFee.isZero()) {
+      const [treasury, _treasuryBump] = await this.findTreasuryAddress();
+      remainingAccounts.push({ pubkey: treasury, isWritable: true, isSigner: false });
+    }
+
     const vault = anchor.web3.Keypair.generate();
     const createTokenAccountInstrs = await serumCmn.createTokenAccountInstrs(
       this.program.provider,
@@ -168,23 +277,48 @@ export class Client {
       vaultAuthority
     );
 
+    // unclaimed tokens go back to the creator unless a treasury is given
+    const signers = [vault, distributor];
+    if (clawbackDestination === undefined) {
+      const clawbackAccount = anchor.web3.Keypair.generate();
+      createTokenAccountInstrs.push(...await serumCmn.createTokenAccountInstrs(
+        this.program.provider,
+        clawbackAccount.publicKey,
+        mint,
+        this.provider.wallet.publicKey
+      ));
+      signers.push(clawbackAccount);
+      clawbackDestination = clawbackAccount.publicKey;
+    }
+
     await this.program.rpc.initialize(
       {
         vaultBump,
         merkleRoot,
         schedule,
+        positionNfts: (positionNfts === undefined) ? false : positionNfts,
+        maxClaimPerTx: null,
+        category: { ido: {} },
+        totalAllocation,
+        relativeSchedule: false,
+        maxProofLen: null,
       },
       {
         accounts: {
           distributor: distributor.publicKey,
+          root,
+          stats,
           adminOrOwner: this.provider.wallet.publicKey,
           vaultAuthority,
           vault: vault.publicKey,
+          mint,
+          clawbackDestination,
           config,
           systemProgram: anchor.web3.SystemProgram.programId,
         },
+        remainingAccounts,
         instructions: createTokenAccountInstrs,
-        signers: [vault, distributor]
+        signers
       }
     );
 
@@ -248,11 +382,13 @@ export class Client {
    */
   async setPaused(distributor: anchor.web3.PublicKey, paused: boolean) {
     const [config, _bump] = await this.findConfigAddress();
+    const [root, _rootBump] = await this.findRootAddress(distributor);
     await this.program.rpc.setPaused(
       paused,
       {
         accounts: {
           distributor,
+          root,
           config,
           adminOrOwner: this.provider.wallet.publicKey
         }
@@ -270,11 +406,13 @@ export class Client {
     const distributorAccount = await this.program.account.merkleDistributor.fetch(distributor);
     const [config, _bump] = await this.findConfigAddress();
     const [vaultAuthority, _vaultBump] = await this.findVaultAuthority(distributor);
+    const [stats, _statsBump] = await this.findStatsAddress(distributor);
     await this.program.rpc.withdrawTokens(
       amount,
       {
         accounts: {
           distributor,
+          stats,
           config,
           owner: this.provider.wallet.publicKey,
           vaultAuthority,
@@ -290,22 +428,35 @@ export class Client {
    * Updates merkle root
    * @param {anchor.web3.PublicKey} distributor - public key of distributor, on which tokes were claimed
    * @param {number[]} merkleRoot - new merkle root to set
+   * @param {anchor.BN} totalAllocation - sum of all allocations in the new tree
    * @param {boolean} unpause (optional) - pause/unpause status
    */
-  async updateRoot(distributor: anchor.web3.PublicKey, merkleRoot: number[], unpause?: boolean) {
+  async updateRoot(
+    distributor: anchor.web3.PublicKey,
+    merkleRoot: number[],
+    totalAllocation: anchor.BN,
+    unpause?: boolean
+  ) {
+    const distributorAccount = await this.program.account.merkleDistributor.fetch(distributor);
+    const [root, _rootBump] = await this.findRootAddress(distributor);
+    const [stats, _statsBump] = await this.findStatsAddress(distributor);
     const [config, _bump] = await this.findConfigAddress();
     unpause = (unpause === undefined) ? false : unpause;
     await this.program.rpc.updateRoot(
       {
         merkleRoot,
         unpause,
+        totalAllocation,
+        maxProofLen: null,
       },
       {
         accounts: {
           distributor,
+          root,
+          stats,
           config,
           adminOrOwner: this.provider.wallet.publicKey,
-          clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
+          vault: distributorAccount.vault,
         }
       }
     );
@@ -318,6 +469,7 @@ export class Client {
    */
   async updateSchedule(distributor: anchor.web3.PublicKey, changes: any[]) {
     const [config, _bump] = await this.findConfigAddress();
+    const [stats, _statsBump] = await this.findStatsAddress(distributor);
     await this.program.rpc.updateSchedule(
       {
         changes
@@ -325,9 +477,9 @@ export class Client {
       {
         accounts: {
           distributor,
+          stats,
           config,
           adminOrOwner: this.provider.wallet.publicKey,
-          clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
         }
       }
     );
@@ -343,11 +495,12 @@ export class Client {
     distributor: anchor.web3.PublicKey,
     user: anchor.web3.PublicKey
   ): Promise<[anchor.web3.PublicKey, number]> {
-    const distributorAccount = await this.program.account.merkleDistributor.fetch(distributor);
+    const [root, _rootBump] = await this.findRootAddress(distributor);
+    const rootAccount = await this.program.account.merkleRoot.fetch(root);
     const [userDetails, bump] = await anchor.web3.PublicKey.findProgramAddress(
       [
         distributor.toBytes(),
-        distributorAccount.merkleIndex.toArray('be', 8),
+        rootAccount.merkleIndex.toArray('be', 8),
         user.toBytes(),
       ],
       this.program.programId
@@ -367,6 +520,7 @@ export class Client {
     user: anchor.web3.PublicKey
   ): Promise<anchor.web3.PublicKey> {
     const [userDetails, bump] = await this.findUserDetailsAddress(distributor, user);
+    const [root, _rootBump] = await this.findRootAddress(distributor);
     const userDetailsAccount = await this.getUserDetails(distributor, user);
 
     if (userDetailsAccount === null) {
@@ -378,6 +532,7 @@ export class Client {
             user,
             userDetails,
             distributor,
+            root,
             systemProgram: anchor.web3.SystemProgram.programId,
           }
         }
@@ -418,34 +573,145 @@ export class Client {
    * @param {anchor.web3.PublicKey} targetWallet - wallet of user, which will withdraw tokens
    * @param {anchor.BN} amount - amount of tokens to claim
    * @param {number[][]} merkleProof - merkle proof
+   * @param {boolean} custodialDestination (optional) - allow target wallet not owned by the user
    */
   async claim(
     distributor: anchor.web3.PublicKey,
     targetWallet: anchor.web3.PublicKey,
     amount: anchor.BN,
-    merkleProof: number[][]
+    merkleProof: number[][],
+    custodialDestination?: boolean
   ) {
     const distributorAccount = await this.program.account.merkleDistributor.fetch(distributor);
     const [vaultAuthority, _vaultBump] = await this.findVaultAuthority(distributor);
+    const [root, _rootBump] = await this.findRootAddress(distributor);
+    const [stats, _statsBump] = await this.findStatsAddress(distributor);
     const [userDetails, _userDetailsBump] = await this.findUserDetailsAddress(
       distributor,
       this.provider.wallet.publicKey
     );
+
+    // the program checks that no position NFT has been minted for the allocation
+    const remainingAccounts = [];
+    if (distributorAccount.positionNfts) {
+      const [positionMint, _positionBump] = await this.findPositionMintAddress(
+        distributor,
+        this.provider.wallet.publicKey
+      );
+      remainingAccounts.push({ pubkey: positionMint, isWritable: false, isSigner: false });
+    }
+
     await this.program.rpc.claim(
       {
         amount,
-        merkleProof
+        merkleProof,
+        custodialDestination: (custodialDestination === undefined) ? false : custodialDestination,
       },
       {
         accounts: {
           distributor,
+          root,
+          stats,
           user: this.provider.wallet.publicKey,
           userDetails,
           vaultAuthority,
           vault: distributorAccount.vault,
           targetWallet,
           tokenProgram: TOKEN_PROGRAM_ID,
-          clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
+        },
+        remainingAccounts,
+      }
+    );
+  }
+
+  /**
+   * Represents the allocation of user by a transferable position NFT
+   * @param {anchor.web3.PublicKey} distributor - public key of distributor with position NFTs enabled
+   * @param {anchor.BN} amount - allocation of user
+   * @param {number[][]} merkleProof - merkle proof
+   * @returns {Promise<anchor.web3.PublicKey>} Returns the public key of the token account holding the position NFT
+   */
+  async mintPosition(
+    distributor: anchor.web3.PublicKey,
+    amount: anchor.BN,
+    merkleProof: number[][]
+  ): Promise<anchor.web3.PublicKey> {
+    const user = this.provider.wallet.publicKey;
+    const [root, _rootBump] = await this.findRootAddress(distributor);
+    const [vaultAuthority, _vaultBump] = await this.findVaultAuthority(distributor);
+    const [userDetails, _userDetailsBump] = await this.findUserDetailsAddress(distributor, user);
+    const [positionMint, _positionBump] = await this.findPositionMintAddress(distributor, user);
+    const positionTokenAccount = anchor.web3.Keypair.generate();
+
+    await this.program.rpc.mintPosition(
+      {
+        amount,
+        merkleProof,
+      },
+      {
+        accounts: {
+          distributor,
+          root,
+          user,
+          userDetails,
+          vaultAuthority,
+          positionMint,
+          positionTokenAccount: positionTokenAccount.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+        signers: [positionTokenAccount],
+      }
+    );
+
+    return positionTokenAccount.publicKey;
+  }
+
+  /**
+   * Claims tokens of a position by the holder of its NFT, the NFT is burned after the full claim
+   * @param {anchor.web3.PublicKey} distributor - public key of distributor
+   * @param {anchor.web3.PublicKey} user - original owner of the allocation
+   * @param {anchor.web3.PublicKey} positionTokenAccount - token account of holder with the position NFT
+   * @param {anchor.web3.PublicKey} targetWallet - token account of holder, which receives tokens
+   * @param {anchor.BN} amount - allocation of the original owner
+   * @param {number[][]} merkleProof - merkle proof of the original owner
+   */
+  async claimPosition(
+    distributor: anchor.web3.PublicKey,
+    user: anchor.web3.PublicKey,
+    positionTokenAccount: anchor.web3.PublicKey,
+    targetWallet: anchor.web3.PublicKey,
+    amount: anchor.BN,
+    merkleProof: number[][]
+  ) {
+    const distributorAccount = await this.program.account.merkleDistributor.fetch(distributor);
+    const [root, _rootBump] = await this.findRootAddress(distributor);
+    const [stats, _statsBump] = await this.findStatsAddress(distributor);
+    const [vaultAuthority, _vaultBump] = await this.findVaultAuthority(distributor);
+    const [userDetails, _userDetailsBump] = await this.findUserDetailsAddress(distributor, user);
+    const [positionMint, _positionBump] = await this.findPositionMintAddress(distributor, user);
+
+    await this.program.rpc.claimPosition(
+      {
+        amount,
+        merkleProof,
+        custodialDestination: false,
+      },
+      {
+        accounts: {
+          distributor,
+          root,
+          stats,
+          holder: this.provider.wallet.publicKey,
+          user,
+          userDetails,
+          positionMint,
+          positionTokenAccount,
+          vaultAuthority,
+          vault: distributorAccount.vault,
+          targetWallet,
+          tokenProgram: TOKEN_PROGRAM_ID,
         }
       }
     );